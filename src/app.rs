@@ -1,46 +1,1084 @@
 use crossterm::{
-    event::{self, Event, KeyCode},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    event::{
+        self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event, KeyCode, KeyEvent,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
+    terminal::{self, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    layout::{Position, Rect},
+    DefaultTerminal,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
-use std::path::PathBuf;
 
 use crate::{
-    config::{self, Config},
-    theme::{all_themes, Theme},
-    ui,
+    clipboard,
+    command_palette::{Command, CommandPalette},
+    config::{
+        self,
+        diagnostic::Diagnostic,
+        layers::Layers,
+        loader::{Backup, LoadedConfig},
+        schema::expand_home,
+        watch::ConfigWatcher,
+        Config,
+    },
+    fleet::{Fleet, FleetFocus},
+    host_form::{FormField, HostForm},
+    hosts::HostView,
+    keys::{Action, KeyChord, Keymap, Scope},
+    ssh::session::{Selection, Session},
+    theme::{
+        background::{Background, Brightness},
+        depth::ColorSupport,
+        check_theme_name,
+        import::import_scheme,
+        ThemeName,
+    },
+    theme_editor::{EditorInput, ThemeEditor},
+    ui::{
+        self,
+        layout::{
+            drag_detail, drag_fleet, fleet_columns, hosts_panes, list_item_at, session_pane, split_main, tab_at,
+            DETAIL_WIDTH,
+        },
+    },
 };
 
+// how long the terminal size has to settle before remote ptys are resized
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(150);
+// how long a message stays on the main screen's status bar
+const NOTICE_TIME: Duration = Duration::from_secs(5);
+// lines of scrollback one notch of the mouse wheel moves
+const SCROLL_LINES: usize = 3;
+
 //screen state enum -- title screen, main screen, config, etc... 
 pub enum Screen {
     Title,
     Main,
     Config,
     Menu,
+    Fleet,
+    HostForm,
+    ThemeEditor,
+}
+
+// what moving the mouse with a button held does
+#[derive(Clone, Copy, PartialEq)]
+enum Drag {
+    HostDetail,
+    FleetSplit,
+    Selection,
+    /// The press went to the remote app, so the motion and release do too.
+    Remote,
 }
 
 pub struct App{
     pub screen: Screen,
     pub tabs: Vec<&'static str>,
     pub tab_index: usize,
-    pub theme: Theme,
+    pub theme: ThemeName,
+    /// What the terminal can show; palettes are fitted to it when drawn.
+    pub colors: ColorSupport,
+    /// The terminal's background, asked for when `run` starts and again
+    /// when the window regains focus.
+    pub background: Option<Background>,
     pub config: Config,
     pub config_path: Option<PathBuf>,
     pub config_error: Option<String>,
+    notified_at: Option<Instant>,
+    /// Where the parts of `config` came from; `config` is their merge.
+    pub layers: Layers,
+    /// Problems found the last time the config file was read.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Scroll offset of the Config screen's problems/sources panel.
+    pub config_scroll: u16,
+    pub hosts: HostView,
+    pub sessions: Vec<Session>,
+    pub session_index: usize,
+    pub fleet: Fleet,
+    pub host_form: Option<HostForm>,
+    pub confirm_delete: Option<usize>,
+    pub backups: Option<Vec<Backup>>,
+    pub backup_index: usize,
+    /// Path being typed for the Config screen's scheme import.
+    pub theme_import: Option<String>,
+    pub theme_editor: Option<ThemeEditor>,
+    /// The Ctrl-p overlay, drawn over whatever screen it was opened on.
+    pub command_palette: Option<CommandPalette>,
+    /// The `?` overlay listing the key bindings of this scope.
+    pub help: Option<Scope>,
+    pub help_scroll: u16,
+    /// The built-in key bindings with the config's `[keys]` applied.
+    pub keymap: Keymap,
+    /// The prefix key was pressed in a live session; the next key is a
+    /// `[keys.session]` binding instead of input.
+    pub prefix_armed: bool,
+    /// Width of the selected host's details beside the host list.
+    pub detail_width: u16,
+    /// How much of the fleet screen the host picker takes, in percent.
+    pub fleet_split: u16,
+    drag: Option<Drag>,
+    quitting: bool,
+    watcher: ConfigWatcher,
+    pending_resize: Option<(Instant, u16, u16)>,
 }
 
 impl App {
-    pub fn new() -> Self {
-        let loaded = config::loader::load_config();
+    pub fn new(loaded: LoadedConfig) -> Self {
         let theme = loaded.config.effective_theme();
+        let keymap = Keymap::new(&loaded.config.keys, loaded.config.prefix_key.as_deref());
+
+        let watcher = ConfigWatcher::new(loaded.layers.files());
 
         Self {
             screen: Screen::Title,
             tabs: vec!["Hosts", "Session"],
             tab_index: 0,
             theme,
+            colors: ColorSupport::detect(),
+            background: None,
             config: loaded.config,
             config_path: loaded.path,
+            notified_at: loaded.error.as_ref().map(|_| Instant::now()),
             config_error: loaded.error,
+            layers: loaded.layers,
+            diagnostics: loaded.diagnostics,
+            config_scroll: 0,
+            hosts: HostView::new(),
+            sessions: Vec::new(),
+            session_index: 0,
+            fleet: Fleet::new(),
+            host_form: None,
+            confirm_delete: None,
+            backups: None,
+            backup_index: 0,
+            theme_import: None,
+            theme_editor: None,
+            command_palette: None,
+            help: None,
+            help_scroll: 0,
+            keymap,
+            prefix_armed: false,
+            detail_width: DETAIL_WIDTH,
+            fleet_split: 30,
+            drag: None,
+            quitting: false,
+            watcher,
+            pending_resize: None,
+        }
+    }
+
+    /// The theme being drawn: the configured one, with `auto` resolved
+    /// against the terminal's background.
+    pub fn active_theme(&self) -> ThemeName {
+        match &self.theme {
+            ThemeName::Auto => {
+                let brightness = self.background.as_ref().map_or(Brightness::Dark, |bg| bg.brightness);
+                self.config.auto_theme(brightness)
+            }
+            theme => theme.clone(),
+        }
+    }
+
+    // asks again after focus returns from a terminal whose profile may have
+    // been switched. Only `auto` cares, and a key typed while the reply is
+    // awaited is lost, so other themes don't ask
+    fn recheck_background(&mut self) {
+        if self.theme == ThemeName::Auto {
+            self.background = Some(Background::detect());
+        }
+    }
+
+    // the status line: shown on the Config screen until the next message,
+    // and on the main screen's status bar for a few seconds
+    fn notify(&mut self, message: String) {
+        self.config_error = Some(message);
+        self.notified_at = Some(Instant::now());
+    }
+
+    /// The last message, while it's recent enough for the status bar.
+    pub fn notice(&self) -> Option<&str> {
+        self.config_error
+            .as_deref()
+            .filter(|_| self.notified_at.is_some_and(|at| at.elapsed() < NOTICE_TIME))
+    }
+
+    pub fn active_session(&self) -> Option<&Session> {
+        self.sessions.get(self.session_index)
+    }
+
+    // Enter on a section header folds it; on a host it connects
+    fn activate_selected(&mut self) {
+        if self.hosts.toggle_section(&self.config) {
+            return;
+        }
+        if let Some(index) = self.hosts.selected_host(&self.config)
+            && let Err(err) = self.open_session(index)
+        {
+            self.notify(err.to_string());
+        }
+    }
+
+    /// Opens a session to the named host and lands on its Session tab.
+    pub fn connect(&mut self, name: &str) -> anyhow::Result<()> {
+        let index = self
+            .config
+            .host_index(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown host: {name}"))?;
+        self.open_session(index)?;
+        self.screen = Screen::Main;
+        Ok(())
+    }
+
+    fn open_session(&mut self, index: usize) -> anyhow::Result<()> {
+        let Some(profile) = self.config.hosts.get(index).map(|host| self.config.resolve(host)) else {
+            return Ok(());
+        };
+        let (cols, rows) = terminal::size().unwrap_or((80, 24));
+        let pane = session_pane(Rect::new(0, 0, cols, rows));
+        let session = Session::spawn(&profile, pane.height, pane.width)
+            .map_err(|err| anyhow::anyhow!("Failed to connect to {}: {err}", profile.name))?;
+        self.sessions.push(session);
+        self.session_index = self.sessions.len() - 1;
+        self.hosts.focus_host(&self.config, index);
+        self.tab_index = 1;
+        Ok(())
+    }
+
+    fn reload_config(&mut self) {
+        let loaded = config::loader::load_config(self.config_path.clone());
+        self.apply_loaded(loaded);
+        self.hosts.cursor = 0;
+    }
+
+    fn apply_loaded(&mut self, loaded: LoadedConfig) {
+        self.theme = loaded.config.effective_theme();
+        self.keymap = Keymap::new(&loaded.config.keys, loaded.config.prefix_key.as_deref());
+        self.config = loaded.config;
+        self.notified_at = loaded.error.as_ref().map(|_| Instant::now());
+        self.config_error = loaded.error;
+        self.watcher.sync(loaded.layers.files());
+        self.layers = loaded.layers;
+        self.diagnostics = loaded.diagnostics;
+        self.config_scroll = 0;
+        // the fleet selection follows hosts by name; say which ones are gone
+        let dropped = self.fleet.sync_hosts(&self.config);
+        if !dropped.is_empty() {
+            let note = format!("Dropped {} from the fleet selection; no longer in the config.", dropped.join(", "));
+            let message = match self.config_error.take() {
+                Some(error) => format!("{error} {note}"),
+                None => note,
+            };
+            self.notify(message);
+        }
+    }
+
+    // picks up edits made to the config files by another program; a file
+    // that no longer parses leaves the last good config in place
+    fn check_config_changes(&mut self) {
+        // an open form or delete prompt holds a host index, so wait until
+        // it's closed before swapping the host list underneath it
+        if self.host_form.is_some() || self.confirm_delete.is_some() {
+            return;
+        }
+        if !self.watcher.poll() {
+            return;
+        }
+        let loaded = config::loader::load_config(self.config_path.clone());
+        if loaded.failed() {
+            let summary = loaded.error.unwrap_or_default();
+            self.notify(format!("Reload failed, keeping last good config. {summary}"));
+            self.diagnostics = loaded.diagnostics;
+            self.config_scroll = 0;
+            // keep watching whatever files the broken version pointed at
+            self.watcher.sync(loaded.layers.files());
+            return;
+        }
+        self.apply_loaded(loaded);
+        let rows = self.hosts.rows(&self.config).len();
+        self.hosts.cursor = self.hosts.cursor.min(rows.saturating_sub(1));
+        if self.diagnostics.is_empty() {
+            let note = self.config_error.take().map(|note| format!(" {note}")).unwrap_or_default();
+            let message = format!("Reloaded config after an outside change.{note}");
+            self.notify(message);
+        }
+    }
+
+    fn scroll_config_panel(&mut self, down: bool) {
+        // the problems and sources panels share one scroll offset, each
+        // stopping at its own last line
+        let migrations = self.layers.migrated.iter().map(|m| m.changes.len() + 1).sum::<usize>();
+        let sources = self.layers.layers.len() + migrations + self.config.hosts.len() + self.config.groups.len() + 6;
+        let problems = self.diagnostics.iter().map(|d| d.render().len() + 1).sum::<usize>();
+        let lines = sources.max(problems);
+        self.config_scroll = match down {
+            true => (self.config_scroll + 1).min(lines.saturating_sub(1) as u16),
+            false => self.config_scroll.saturating_sub(1),
+        };
+    }
+
+    fn open_backups(&mut self) {
+        let Some(path) = &self.config_path else {
+            return;
+        };
+        self.backups = Some(config::loader::list_backups(path));
+        self.backup_index = 0;
+    }
+
+    fn restore_selected_backup(&mut self) {
+        let (Some(path), Some(backups)) = (self.config_path.clone(), self.backups.take()) else {
+            return;
+        };
+        let Some(backup) = backups.get(self.backup_index) else {
+            return;
+        };
+        match config::loader::restore_backup(&path, backup) {
+            Ok(()) => {
+                self.reload_config();
+                if self.config_error.is_none() {
+                    self.notify(format!("Restored {}.", backup.path.display()));
+                }
+            }
+            Err(err) => self.notify(format!("Failed to restore backup: {err}")),
+        }
+    }
+
+    fn handle_backup_key(&mut self, key: KeyEvent) {
+        let count = self.backups.as_ref().map_or(0, Vec::len);
+        match key.code {
+            KeyCode::Esc => self.backups = None,
+            KeyCode::Enter => self.restore_selected_backup(),
+            KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+                self.backup_index = (self.backup_index + 1) % count;
+            }
+            KeyCode::Up | KeyCode::Char('k') if count > 0 => {
+                self.backup_index = self.backup_index.checked_sub(1).unwrap_or(count - 1);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_import_key(&mut self, key: KeyEvent) {
+        let Some(path) = self.theme_import.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.theme_import = None,
+            KeyCode::Enter => {
+                let path = expand_home(path.trim());
+                self.theme_import = None;
+                self.import_theme(&path);
+            }
+            KeyCode::Backspace => {
+                path.pop();
+            }
+            KeyCode::Char(c) => path.push(c),
+            _ => {}
+        }
+    }
+
+    // imports a scheme into the themes/ dir and switches to it
+    fn import_theme(&mut self, file: &Path) {
+        let Some(config_path) = self.config_path.clone() else {
+            self.notify("No config directory; can't import themes.".to_string());
+            return;
+        };
+        let (theme, format) = match import_scheme(file, None) {
+            Ok(imported) => imported,
+            Err(err) => {
+                self.notify(format!("{err:#}"));
+                return;
+            }
+        };
+        if let Err(problem) = check_theme_name(&theme.name) {
+            let message = format!("Can't import as `{}`: {problem} (`mangsh theme import --name` sets it)", theme.name);
+            self.notify(message);
+            return;
+        }
+        let target = config::loader::theme_path(&config_path, &theme.name);
+        if target.exists() || self.layers.inline_theme(&theme.name).is_some() {
+            self.notify(format!(
+                "A theme called {} already exists; use `mangsh theme import --name` to pick another name.",
+                theme.name
+            ));
+            return;
+        }
+        if let Err(err) = config::loader::save_theme(&config_path, &theme) {
+            self.notify(format!("Failed to save theme: {err}"));
+            return;
+        }
+        self.reload_config();
+        self.set_theme(ThemeName::Custom(theme.name.clone()));
+        if self.config_error.is_none() {
+            self.notify(format!("Imported {} scheme as {}.", format.label(), theme.name));
+        }
+    }
+
+    // the editor starts from whatever theme is active
+    fn open_theme_editor(&mut self) {
+        let theme = self.active_theme();
+        let palette = ui::panels::palette(&theme, &self.config);
+        self.theme_editor = Some(ThemeEditor::new(theme, palette));
+        self.screen = Screen::ThemeEditor;
+    }
+
+    fn close_theme_editor(&mut self) {
+        if self.theme_editor.take().is_some_and(|editor| editor.modified()) {
+            self.notify("Theme edits discarded.".to_string());
+        }
+        self.screen = Screen::Config;
+    }
+
+    // the hex and save-as prompts; the rest of the editor goes through the
+    // keymap
+    fn handle_editor_key(&mut self, key: KeyEvent) {
+        let Some(editor) = self.theme_editor.as_mut() else {
+            self.screen = Screen::Config;
+            return;
+        };
+        let saving = matches!(editor.input, Some(EditorInput::Name(_)));
+        match key.code {
+            KeyCode::Esc => {
+                editor.input = None;
+                editor.notice = None;
+            }
+            KeyCode::Enter if saving => self.save_edited_theme(),
+            KeyCode::Enter => editor.apply_hex(),
+            KeyCode::Backspace => editor.pop(),
+            KeyCode::Char(c) => editor.push(c),
+            _ => {}
+        }
+    }
+
+    fn editor_action(&mut self, action: Action) {
+        let Some(editor) = self.theme_editor.as_mut() else {
+            self.screen = Screen::Config;
+            return;
+        };
+        match action {
+            Action::Back => self.close_theme_editor(),
+            Action::Down => editor.next_slot(),
+            Action::Up => editor.previous_slot(),
+            Action::NextField => editor.next_channel(),
+            Action::PreviousField => editor.previous_channel(),
+            Action::Increase => editor.adjust(1.0),
+            Action::Decrease => editor.adjust(-1.0),
+            Action::IncreaseMore => editor.adjust(10.0),
+            Action::DecreaseMore => editor.adjust(-10.0),
+            Action::ToggleColorModel => editor.toggle_model(),
+            Action::EnterColor => editor.start_hex(),
+            Action::ResetColor => editor.reset_slot(),
+            Action::Save => editor.start_save(),
+            _ => {}
+        }
+    }
+
+    // writes the edited palette to the themes/ dir and switches to it
+    fn save_edited_theme(&mut self) {
+        let Some(config_path) = self.config_path.clone() else {
+            if let Some(editor) = self.theme_editor.as_mut() {
+                editor.notice = Some("No config directory; can't save themes.".to_string());
+            }
+            return;
+        };
+        let Some(theme) = self
+            .theme_editor
+            .as_mut()
+            .and_then(|editor| editor.named_theme(&self.config, &config_path))
+        else {
+            return;
+        };
+        let path = match config::loader::save_theme(&config_path, &theme) {
+            Ok(path) => path,
+            Err(err) => {
+                if let Some(editor) = self.theme_editor.as_mut() {
+                    editor.notice = Some(format!("Failed to save theme: {err}"));
+                }
+                return;
+            }
+        };
+        self.theme_editor = None;
+        self.screen = Screen::Config;
+        self.reload_config();
+        self.set_theme(ThemeName::Custom(theme.name.clone()));
+        if self.config_error.is_none() {
+            self.notify(format!("Saved theme {} to {}.", theme.name, path.display()));
+        }
+    }
+
+    fn open_host_form(&mut self, edit: bool) {
+        let form = match self.hosts.selected_host(&self.config) {
+            Some(index) if edit => HostForm::edit(index, &self.config.hosts[index]),
+            _ if edit => return,
+            _ => HostForm::new_host(),
+        };
+        self.host_form = Some(form);
+        self.screen = Screen::HostForm;
+    }
+
+    fn save_host_form(&mut self) {
+        let Some(form) = self.host_form.as_mut() else {
+            return;
+        };
+        let Some(host) = form.validate(&self.config) else {
+            return;
+        };
+
+        let editing = form.editing;
+        let (index, old) = match editing {
+            Some(index) => {
+                let old = self.config.hosts[index].clone();
+                self.config.replace_host(index, host);
+                (index, Some(old))
+            }
+            None => {
+                self.config.hosts.push(host);
+                (self.config.hosts.len() - 1, None)
+            }
+        };
+
+        if self.persist_config("host") {
+            if let Some(old) = &old {
+                self.fleet.rename(&old.name, &self.config.hosts[index].name);
+            }
+            self.host_form = None;
+            self.screen = Screen::Main;
+            self.hosts.focus_host(&self.config, index);
+            return;
+        }
+        // what didn't save doesn't stay in memory either; the form keeps it
+        match old {
+            Some(old) => self.config.replace_host(index, old),
+            None => {
+                self.config.hosts.pop();
+            }
+        }
+        if let Some(form) = self.host_form.as_mut() {
+            form.notice = self.config_error.clone();
+        }
+    }
+
+    fn delete_host(&mut self, index: usize) {
+        let Some(host) = self.config.hosts.get(index) else {
+            return;
+        };
+        // the user file can override an inherited host but not remove it
+        if let Some(origin) = self.layers.inherited_host(&host.name) {
+            let kept = if self.layers.user_has_host(&host.name) { " Its overrides here are kept." } else { "" };
+            self.notify(format!(
+                "Can't delete {}: it's inherited from {}, which mangsh never writes. Remove it there by hand.{kept}",
+                host.name,
+                origin.label()
+            ));
+            return;
+        }
+        self.config.remove_host(index);
+        self.fleet.sync_hosts(&self.config);
+        self.hosts.cursor = self.hosts.cursor.saturating_sub(1);
+        self.persist_config("host removal");
+    }
+
+    fn handle_form_key(&mut self, key: KeyEvent) {
+        let Some(form) = self.host_form.as_mut() else {
+            self.screen = Screen::Main;
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.host_form = None;
+                self.screen = Screen::Main;
+            }
+            KeyCode::Enter => self.save_host_form(),
+            KeyCode::Tab | KeyCode::Down => form.next_field(),
+            KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+            KeyCode::Right if form.field() == FormField::Identity => form.cycle_identity(true),
+            KeyCode::Left if form.field() == FormField::Identity => form.cycle_identity(false),
+            KeyCode::Backspace => form.backspace(),
+            KeyCode::Char(c) => form.input(c),
+            _ => {}
+        }
+    }
+
+    fn open_fleet(&mut self) {
+        self.fleet.sync_hosts(&self.config);
+        self.screen = Screen::Fleet;
+    }
+
+    // true while a text field owns the keyboard
+    fn typing(&self) -> bool {
+        match self.screen {
+            Screen::Fleet => self.fleet.focus == FleetFocus::Command,
+            Screen::HostForm => true,
+            Screen::Config => self.theme_import.is_some(),
+            Screen::ThemeEditor => self.theme_editor.as_ref().is_some_and(|editor| editor.input.is_some()),
+            Screen::Main => self.tab_index == 0 && self.hosts.search.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Whether the session tab is up with a live session in it.
+    pub fn in_session(&self) -> bool {
+        self.tab_index == 1 && self.active_session().is_some_and(Session::is_open)
+    }
+
+    // which key bindings apply right now; none while a prompt, form or text
+    // field has the keyboard
+    fn key_scope(&self) -> Option<Scope> {
+        if self.typing() || self.command_palette.is_some() || self.help.is_some() {
+            return None;
+        }
+        match self.screen {
+            Screen::Title => Some(Scope::Title),
+            Screen::Main if self.confirm_delete.is_some() => None,
+            Screen::Main if self.in_session() => Some(Scope::Session),
+            Screen::Main => Some(Scope::Main),
+            Screen::Config if self.backups.is_some() => None,
+            Screen::Config => Some(Scope::Config),
+            Screen::Menu => Some(Scope::Menu),
+            Screen::Fleet if self.fleet.detail => None,
+            Screen::Fleet => Some(Scope::Fleet),
+            Screen::HostForm => None,
+            Screen::ThemeEditor => Some(Scope::Editor),
+        }
+    }
+
+    // notices stay up until the next key press
+    fn clear_notices(&mut self, scope: Scope) {
+        match scope {
+            Scope::Fleet => self.fleet.notice = None,
+            Scope::Editor => {
+                if let Some(editor) = self.theme_editor.as_mut() {
+                    editor.notice = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Does what a bound key asks for on the current screen.
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.quitting = true,
+            Action::OpenHosts => {
+                self.screen = Screen::Main;
+                self.tab_index = 0;
+            }
+            Action::OpenConfig => self.screen = Screen::Config,
+            Action::OpenMenu => self.screen = Screen::Menu,
+            Action::OpenFleet => self.open_fleet(),
+            Action::NextTheme => self.next_theme(),
+            Action::PreviousTheme => self.previous_theme(),
+            Action::Help => {
+                self.help = self.key_scope();
+                self.help_scroll = 0;
+            }
+            Action::CommandPalette => {
+                let scope = self.key_scope().unwrap_or(Scope::Global);
+                self.command_palette = Some(CommandPalette::new(scope, &self.keymap, &self.config, &self.theme));
+            }
+            action => match self.screen {
+                Screen::Main => self.main_action(action),
+                Screen::Config => self.config_action(action),
+                Screen::Fleet => self.fleet_action(action),
+                Screen::ThemeEditor => self.editor_action(action),
+                Screen::Menu if action == Action::Back => self.screen = Screen::Title,
+                _ => {}
+            },
+        }
+    }
+
+    fn handle_palette_key(&mut self, key: KeyEvent) {
+        let Some(palette) = self.command_palette.as_mut() else {
+            return;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => self.command_palette = None,
+            KeyCode::Enter => {
+                let command = palette.selected_command();
+                self.command_palette = None;
+                if let Some(command) = command {
+                    self.run_command(command);
+                }
+            }
+            KeyCode::Down | KeyCode::Tab => palette.next(),
+            KeyCode::Up | KeyCode::BackTab => palette.previous(),
+            KeyCode::Char('n') if ctrl => palette.next(),
+            KeyCode::Char('p') if ctrl => palette.previous(),
+            KeyCode::Backspace => palette.pop(),
+            KeyCode::Char(c) if !ctrl => palette.push(c),
+            _ => {}
+        }
+    }
+
+    // the help overlay scrolls; any other key closes it
+    fn handle_help_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_help(true),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_help(false),
+            _ => self.help = None,
+        }
+    }
+
+    // kept within the text, so scrolling back up after overshooting the end
+    // moves straight away
+    fn scroll_help(&mut self, down: bool) {
+        let (cols, rows) = terminal::size().unwrap_or((80, 24));
+        let max = ui::panels::help_max_scroll(self, Rect::new(0, 0, cols, rows));
+        self.help_scroll = match down {
+            true => (self.help_scroll + 1).min(max),
+            false => self.help_scroll.saturating_sub(1).min(max),
+        };
+    }
+
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Action(action) => self.perform(action),
+            Command::Connect(name) => {
+                if let Err(err) = self.connect(&name) {
+                    self.notify(err.to_string());
+                }
+            }
+            Command::Theme(theme) => self.set_theme(theme),
+            Command::Unavailable(reason) => self.notify(reason.to_string()),
+        }
+    }
+
+    fn main_action(&mut self, action: Action) {
+        // the session tab only switches tabs
+        let hosts_tab = self.tab_index == 0;
+        match action {
+            Action::NextTab => self.next_tab(),
+            Action::PreviousTab => self.previous_tab(),
+            Action::Down if hosts_tab => self.hosts.next(&self.config),
+            Action::Up if hosts_tab => self.hosts.previous(&self.config),
+            Action::Select if hosts_tab => self.activate_selected(),
+            Action::CycleGrouping if hosts_tab => self.hosts.cycle_grouping(),
+            Action::CycleTagFilter if hosts_tab => self.hosts.cycle_tag_filter(&self.config),
+            Action::Search if hosts_tab => self.hosts.start_search(),
+            Action::AddHost if hosts_tab => self.open_host_form(false),
+            Action::EditHost if hosts_tab => self.open_host_form(true),
+            Action::DeleteHost if hosts_tab => self.confirm_delete = self.hosts.selected_host(&self.config),
+            _ => {}
+        }
+    }
+
+    fn config_action(&mut self, action: Action) {
+        match action {
+            Action::Backups => self.open_backups(),
+            Action::ImportTheme => self.theme_import = Some(String::new()),
+            Action::EditTheme => self.open_theme_editor(),
+            Action::Down => self.scroll_config_panel(true),
+            Action::Up => self.scroll_config_panel(false),
+            _ => {}
+        }
+    }
+
+    // a live session gets every key except the prefix, which makes the one
+    // after it a `[keys.session]` binding; the prefix twice sends it through
+    // and anything unbound just disarms it
+    fn handle_session_key(&mut self, key: KeyEvent) {
+        let prefix = KeyChord::from_event(&key) == self.keymap.prefix;
+        if !self.prefix_armed {
+            match prefix {
+                true => self.prefix_armed = true,
+                false => self.send_to_session(key),
+            }
+            return;
+        }
+        self.prefix_armed = false;
+        if prefix {
+            self.send_to_session(key);
+        } else if let Some(action) = self.keymap.action(Scope::Session, &key) {
+            self.perform(action);
+        }
+    }
+
+    fn send_to_session(&mut self, key: KeyEvent) {
+        if let Some(session) = self.sessions.get_mut(self.session_index)
+            && let Err(err) = session.send_key(key)
+        {
+            session.status = Some(format!("write failed: {err}"));
+        }
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.hosts.stop_search(),
+            KeyCode::Enter => self.connect_search_hit(),
+            KeyCode::Down => self.hosts.next(&self.config),
+            KeyCode::Up => self.hosts.previous(&self.config),
+            KeyCode::Backspace => self.hosts.search_pop(),
+            KeyCode::Char(c) => self.hosts.search_push(c),
+            _ => {}
+        }
+    }
+
+    fn connect_search_hit(&mut self) {
+        if let Some(index) = self.hosts.selected_host(&self.config) {
+            self.hosts.stop_search();
+            if let Err(err) = self.open_session(index) {
+                self.notify(err.to_string());
+            }
+        }
+    }
+
+    // clicks pick tabs and hosts, the wheel scrolls, and dragging moves pane
+    // borders or selects session text
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let (cols, rows) = terminal::size().unwrap_or((80, 24));
+        let area = Rect::new(0, 0, cols, rows);
+        if let Some(drag) = self.drag {
+            match mouse.kind {
+                MouseEventKind::Drag(_) => self.drag_to(drag, mouse, area),
+                MouseEventKind::Up(_) => {
+                    self.drag = None;
+                    match drag {
+                        Drag::Selection => self.copy_selection(),
+                        Drag::Remote => self.forward_mouse(mouse, session_pane(area)),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+        if self.help.is_some() {
+            match mouse.kind {
+                MouseEventKind::ScrollDown => self.scroll_help(true),
+                MouseEventKind::ScrollUp => self.scroll_help(false),
+                MouseEventKind::Down(_) => self.help = None,
+                _ => {}
+            }
+            return;
+        }
+        if let Some(palette) = self.command_palette.as_mut() {
+            match mouse.kind {
+                MouseEventKind::ScrollDown => palette.next(),
+                MouseEventKind::ScrollUp => palette.previous(),
+                _ => {}
+            }
+            return;
+        }
+        match self.screen {
+            Screen::Main if self.confirm_delete.is_none() => self.main_mouse(mouse, area),
+            Screen::Fleet if !self.fleet.detail => {
+                // either side of the border between the picker and the rest
+                let [hosts, _] = fleet_columns(area, self.fleet_split);
+                if mouse.kind == MouseEventKind::Down(MouseButton::Left)
+                    && (hosts.right() - 1..=hosts.right()).contains(&mouse.column)
+                {
+                    self.drag = Some(Drag::FleetSplit);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn main_mouse(&mut self, mouse: MouseEvent, area: Rect) {
+        let chunks = split_main(area);
+        let at = Position::new(mouse.column, mouse.row);
+        if self.tab_index == 1 && session_pane(area).contains(at) {
+            self.session_mouse(mouse, session_pane(area));
+            return;
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if chunks[0].contains(at) => {
+                if let Some(index) = tab_at(chunks[0], &self.tabs, mouse.column) {
+                    self.tab_index = index;
+                    self.prefix_armed = false;
+                }
+            }
+            _ if self.tab_index == 0 => self.hosts_mouse(mouse, chunks[1]),
+            _ => {}
+        }
+    }
+
+    // a click selects a host or section, and a click on the selected one
+    // does what Enter would
+    fn hosts_mouse(&mut self, mouse: MouseEvent, body: Rect) {
+        let panes = hosts_panes(body, self.detail_width, self.hosts.selected_host(&self.config).is_some());
+        let at = Position::new(mouse.column, mouse.row);
+        let on_border = panes
+            .detail
+            .is_some_and(|detail| mouse.column == detail.x && (detail.y..detail.bottom()).contains(&mouse.row));
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if on_border => self.drag = Some(Drag::HostDetail),
+            MouseEventKind::Down(MouseButton::Left) if panes.list.contains(at) => {
+                let index = list_item_at(panes.list, self.hosts.cursor, mouse.row);
+                if index >= self.hosts.rows(&self.config).len() {
+                    return;
+                }
+                if index != self.hosts.cursor {
+                    self.hosts.cursor = index;
+                } else if self.hosts.search.is_some() {
+                    self.connect_search_hit();
+                } else {
+                    self.activate_selected();
+                }
+            }
+            MouseEventKind::ScrollDown if panes.list.contains(at) => self.hosts.next(&self.config),
+            MouseEventKind::ScrollUp if panes.list.contains(at) => self.hosts.previous(&self.config),
+            _ => {}
+        }
+    }
+
+    // a remote app that asked for the mouse (htop, vim) gets it, unless
+    // shift is held; otherwise the wheel scrolls back and dragging selects
+    fn session_mouse(&mut self, mouse: MouseEvent, pane: Rect) {
+        let Some(session) = self.sessions.get_mut(self.session_index) else {
+            return;
+        };
+        let cell = pane_cell(pane, mouse);
+        if session.is_open() && session.wants_mouse() && !mouse.modifiers.contains(KeyModifiers::SHIFT) {
+            // a release without its press, e.g. from the click that opened
+            // this session, means nothing to the remote app
+            if matches!(mouse.kind, MouseEventKind::Down(_)) {
+                self.drag = Some(Drag::Remote);
+            } else if matches!(mouse.kind, MouseEventKind::Up(_) | MouseEventKind::Drag(_)) {
+                return;
+            }
+            self.forward_mouse(mouse, pane);
+            return;
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                session.selection = Some(Selection { anchor: cell, head: cell });
+                self.drag = Some(Drag::Selection);
+            }
+            MouseEventKind::ScrollUp => session.scroll(true, SCROLL_LINES),
+            MouseEventKind::ScrollDown => session.scroll(false, SCROLL_LINES),
+            _ => {}
+        }
+    }
+
+    fn drag_to(&mut self, drag: Drag, mouse: MouseEvent, area: Rect) {
+        match drag {
+            Drag::HostDetail => self.detail_width = drag_detail(split_main(area)[1], mouse.column),
+            Drag::FleetSplit => self.fleet_split = drag_fleet(area, mouse.column),
+            Drag::Selection => {
+                let head = pane_cell(session_pane(area), mouse);
+                if let Some(selection) = self.sessions.get_mut(self.session_index).and_then(|s| s.selection.as_mut()) {
+                    selection.head = head;
+                }
+            }
+            Drag::Remote => self.forward_mouse(mouse, session_pane(area)),
+        }
+    }
+
+    fn forward_mouse(&mut self, mouse: MouseEvent, pane: Rect) {
+        let (row, col) = pane_cell(pane, mouse);
+        if let Some(session) = self.sessions.get_mut(self.session_index)
+            && let Err(err) = session.send_mouse(mouse, row, col)
+        {
+            session.status = Some(format!("write failed: {err}"));
+        }
+    }
+
+    // a plain click leaves nothing selected
+    fn copy_selection(&mut self) {
+        let Some(session) = self.sessions.get_mut(self.session_index) else {
+            return;
+        };
+        match session.selection {
+            Some(selection) if selection.anchor != selection.head => {
+                if let Some(text) = session.selected_text() {
+                    match clipboard::copy(&text) {
+                        Ok(()) => self.notify(format!("Copied {} characters.", text.chars().count())),
+                        Err(err) => self.notify(format!("Failed to copy: {err}")),
+                    }
+                }
+            }
+            _ => session.selection = None,
+        }
+    }
+
+    // the detail view and the command line own the keyboard; the rest of
+    // the fleet screen goes through the keymap
+    fn handle_fleet_key(&mut self, key: KeyEvent) {
+        let fleet = &mut self.fleet;
+        fleet.notice = None;
+        if fleet.detail {
+            match key.code {
+                KeyCode::Esc | KeyCode::Enter => fleet.detail = false,
+                KeyCode::Down | KeyCode::Char('j') => fleet.detail_scroll = fleet.detail_scroll.saturating_add(1),
+                KeyCode::Up | KeyCode::Char('k') => fleet.detail_scroll = fleet.detail_scroll.saturating_sub(1),
+                KeyCode::PageDown => fleet.detail_scroll = fleet.detail_scroll.saturating_add(10),
+                KeyCode::PageUp => fleet.detail_scroll = fleet.detail_scroll.saturating_sub(10),
+                KeyCode::Right | KeyCode::Char('l') => fleet.next_result(),
+                KeyCode::Left | KeyCode::Char('h') => fleet.previous_result(),
+                _ => {}
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Tab => fleet.next_focus(),
+            KeyCode::Esc => fleet.focus = FleetFocus::Hosts,
+            KeyCode::Enter => {
+                fleet.notice = fleet.start(&self.config).err().map(|err| err.to_string());
+            }
+            KeyCode::Backspace => {
+                fleet.command.pop();
+            }
+            KeyCode::Char(c) => fleet.command.push(c),
+            _ => {}
+        }
+    }
+
+    fn fleet_action(&mut self, action: Action) {
+        let fleet = &mut self.fleet;
+        match (fleet.focus, action) {
+            (_, Action::NextField) => fleet.next_focus(),
+            (_, Action::Back) => self.screen = Screen::Main,
+
+            (FleetFocus::Hosts, Action::Down) => fleet.next_host(),
+            (FleetFocus::Hosts, Action::Up) => fleet.previous_host(),
+            (FleetFocus::Hosts, Action::Toggle) => fleet.toggle(),
+            (FleetFocus::Hosts, Action::SelectAll) => fleet.toggle_all(),
+            (FleetFocus::Hosts, Action::NextGroup) => fleet.next_group(&self.config),
+            (FleetFocus::Hosts, Action::Select) => fleet.focus = FleetFocus::Command,
+
+            (FleetFocus::Results, Action::Down) => fleet.next_result(),
+            (FleetFocus::Results, Action::Up) => fleet.previous_result(),
+            (FleetFocus::Results, Action::Select) => fleet.open_detail(),
+            _ => {}
+        }
+    }
+
+    fn queue_resize(&mut self, cols: u16, rows: u16) {
+        self.pending_resize = Some((Instant::now(), cols, rows));
+    }
+
+    // only resize once the terminal stops changing, so a window drag doesn't
+    // turn into a stream of window-change requests
+    fn flush_resize(&mut self) {
+        let Some((at, cols, rows)) = self.pending_resize else {
+            return;
+        };
+        if at.elapsed() < RESIZE_DEBOUNCE {
+            return;
+        }
+        self.pending_resize = None;
+
+        let pane = session_pane(Rect::new(0, 0, cols, rows));
+        let failed = self
+            .sessions
+            .iter_mut()
+            .filter(|s| s.is_open())
+            .filter_map(|session| {
+                let err = session.resize(pane.height, pane.width).err()?;
+                Some(format!("Failed to resize {}: {err}", session.host))
+            })
+            .last();
+        if let Some(message) = failed {
+            self.notify(message);
         }
     }
 
@@ -57,110 +1095,145 @@ impl App {
     }
 
     pub fn next_theme(&mut self) {
-        let themes = all_themes();
+        let mut themes = self.config.theme_names();
         if let Some(pos) = themes.iter().position(|t| *t == self.theme) {
             let next = (pos + 1) % themes.len();
-            self.set_theme(themes[next]);
+            self.set_theme(themes.swap_remove(next));
         }
     }
 
     pub fn previous_theme(&mut self) {
-        let themes = all_themes();
+        let mut themes = self.config.theme_names();
         if let Some(pos) = themes.iter().position(|t| *t == self.theme) {
             let prev = if pos == 0 { themes.len() - 1 } else { pos - 1 };
-            self.set_theme(themes[prev]);
+            self.set_theme(themes.swap_remove(prev));
         }
     }
 
-    pub fn set_theme(&mut self, theme: Theme) {
-        self.theme = theme;
+    pub fn set_theme(&mut self, theme: ThemeName) {
+        self.theme = theme.clone();
         self.config.theme = Some(theme);
-        match config::loader::save_config(&self.config) {
+        self.persist_config("theme");
+    }
+
+    // writes the in-memory config back to disk; `what` names the change for
+    // the error message
+    fn persist_config(&mut self, what: &str) -> bool {
+        let user = self.layers.user_view(&self.config);
+        match config::loader::save_config(&user, self.config_path.as_deref()) {
             Ok(Some(path)) => {
+                self.layers.set_user(user, &self.config);
+                self.watcher.sync(self.layers.files());
                 self.config_path = Some(path);
                 self.config_error = None;
+                true
             }
             Ok(None) => {
-                self.config_error = Some("No config directory; theme not persisted.".to_string());
+                self.notify(format!("No config directory; {what} not persisted."));
+                false
             }
             Err(err) => {
-                self.config_error = Some(format!("Failed to save config: {err}"));
+                self.notify(format!("Failed to save config: {err}"));
+                false
             }
-        };
+        }
     }
 
     pub fn run(&mut self) -> anyhow::Result<()> {
         enable_raw_mode()?;
+        // always asked, since the theme can be switched to `auto` later
+        self.background = Some(Background::detect());
         let mut terminal = ratatui::init();
+        let result = execute!(io::stdout(), EnableFocusChange, EnableMouseCapture)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| self.event_loop(&mut terminal));
 
+        // put the terminal back even when the loop failed
+        let _ = execute!(io::stdout(), DisableMouseCapture, DisableFocusChange);
+        let _ = disable_raw_mode();
+        ratatui::restore();
+        result
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
         loop {
+            for session in &mut self.sessions {
+                session.pump();
+            }
+            // the prefix only means something to a live session
+            if !self.in_session() {
+                self.prefix_armed = false;
+            }
+            self.flush_resize();
+            self.fleet.pump();
+            self.check_config_changes();
+
             // DRAW SCREEN
             terminal.draw(|frame| {
-                ui::draw(frame, &self);
+                ui::draw(frame, self);
             })?;
 
             // CHECK FOR INPUT
-            if event::poll(std::time::Duration::from_millis(50))? {
-                if let Event::Key(key) = event::read()? {
-                    // global exit
-                    if let KeyCode::Char('q') = key.code {
-                        break;
-                    }
-                    match self.screen {
-                        Screen::Title => {
-                            match key.code {
-                                KeyCode::Enter => {
-                                    self.screen = Screen::Main;
-                                    self.tab_index = 0;
-                                }
-                                KeyCode::Char('m') | KeyCode::Char('M') => {
-                                    self.screen = Screen::Menu;
-                                }
-                                _ => {}
-                            }
-                        }
-
-                        Screen::Config => {
-                            match key.code {
-                                KeyCode::Char('m') | KeyCode::Char('M') => {
-                                    self.screen = Screen::Menu;
-                                }
-                                KeyCode::Char('q') => break,
-                                KeyCode::Right | KeyCode::Char('l') => self.next_theme(),
-                                KeyCode::Left | KeyCode::Char('h') => self.previous_theme(),
-                                _ => {}
-                            }
-                        }
+            if !event::poll(Duration::from_millis(50))? {
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Resize(cols, rows) => {
+                    self.queue_resize(cols, rows);
+                    continue;
+                }
+                Event::Mouse(mouse) => {
+                    self.handle_mouse(mouse);
+                    continue;
+                }
+                // crossterm only reads input inside `poll` and `read`, so
+                // nothing races the query for the reply here
+                Event::FocusGained => {
+                    self.recheck_background();
+                    continue;
+                }
+                _ => continue,
+            };
 
-                        Screen::Main => {
-                            match key.code {
-                                KeyCode::Char('q') => break,
-                                KeyCode::Char('c') => self.screen = Screen::Config,
-                                KeyCode::Char('m') | KeyCode::Char('M') => self.screen = Screen::Menu,
-                                KeyCode::Right | KeyCode::Char('l') => self.next_tab(),
-                                KeyCode::Left | KeyCode::Char('h') => self.previous_tab(),
-                                _ => {}
-                            }
-                        }
-                        Screen::Menu => {
-                            match key.code {
-                                KeyCode::Char('1') | KeyCode::Enter => {
-                                    self.screen = Screen::Main;
-                                    self.tab_index = 0;
-                                }
-                                KeyCode::Char('2') | KeyCode::Char('c') => self.screen = Screen::Config,
-                                KeyCode::Esc => self.screen = Screen::Title,
-                                KeyCode::Char('q') => break,
-                                _ => {}
-                            }
-                        }
+            match self.key_scope() {
+                Some(Scope::Session) => self.handle_session_key(key),
+                Some(scope) => {
+                    self.prefix_armed = false;
+                    self.clear_notices(scope);
+                    if let Some(action) = self.keymap.action(scope, &key) {
+                        self.perform(action);
                     }
                 }
+                None => match self.screen {
+                    _ if self.command_palette.is_some() => self.handle_palette_key(key),
+                    _ if self.help.is_some() => self.handle_help_key(key),
+                    Screen::Main if self.confirm_delete.is_some() => {
+                        if let (KeyCode::Char('y') | KeyCode::Char('Y'), Some(index)) = (key.code, self.confirm_delete) {
+                            self.delete_host(index);
+                        }
+                        self.confirm_delete = None;
+                    }
+                    Screen::Main => self.handle_search_key(key),
+                    Screen::Config if self.backups.is_some() => self.handle_backup_key(key),
+                    Screen::Config => self.handle_import_key(key),
+                    Screen::Fleet => self.handle_fleet_key(key),
+                    Screen::HostForm => self.handle_form_key(key),
+                    Screen::ThemeEditor => self.handle_editor_key(key),
+                    Screen::Title | Screen::Menu => {}
+                },
+            }
+            if self.quitting {
+                return Ok(());
             }
         }
+    }
+}
 
-        disable_raw_mode()?;
-        ratatui::restore();
-        Ok(())
-    }   
+// the session cell under the mouse; past the pane's edge, the nearest one
+// on it, so a drag out of the pane keeps going up to the edge
+fn pane_cell(pane: Rect, mouse: MouseEvent) -> (u16, u16) {
+    let row = mouse.row.clamp(pane.y, pane.bottom().saturating_sub(1)) - pane.y;
+    let col = mouse.column.clamp(pane.x, pane.right().saturating_sub(1)) - pane.x;
+    (row, col)
 }
@@ -0,0 +1,408 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use crate::{
+    app::App,
+    config::{
+        diagnostic::Diagnostic,
+        layers::Origin,
+        loader::{
+            default_config_path, load_config, read_config, read_layered, save_config, save_theme,
+            theme_path,
+        },
+        schema::HostProfile,
+    },
+    host_form::check_host,
+    ssh::exec::{spawn_exec, ExecEvent, ExecOutcome, Stream, DEFAULT_CONCURRENCY},
+    theme::{check_theme_name, import::import_scheme},
+};
+
+#[derive(Parser)]
+#[command(name = "mangsh", version, about = "A terminal-based SSH client")]
+pub struct Cli {
+    /// Use this config file instead of the default location (or set
+    /// MANGSH_CONFIG)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Open the TUI straight into a session with a host
+    Connect { host: String },
+    /// List configured hosts
+    List {
+        /// Print the hosts as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Add a host profile
+    Add {
+        name: String,
+        #[arg(long)]
+        addr: String,
+        /// Leave out to inherit from a template or [defaults]
+        #[arg(long)]
+        user: Option<String>,
+        #[arg(long)]
+        port: Option<u16>,
+        /// Route the connection over Tor
+        #[arg(long)]
+        tor: bool,
+        /// Private key to authenticate with
+        #[arg(long, value_name = "FILE")]
+        identity: Option<String>,
+        /// Template to inherit settings from
+        #[arg(long, value_name = "NAME")]
+        template: Option<String>,
+        /// Host to hop through: a configured host or [user@]addr[:port]
+        #[arg(long, value_name = "HOST")]
+        jump: Option<String>,
+        /// Seconds between keepalive probes
+        #[arg(long, value_name = "SECS")]
+        keepalive: Option<u32>,
+        /// Tag the host (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Remove a host profile
+    Remove { name: String },
+    /// Run a command on one or more hosts
+    Exec {
+        /// Hosts, groups or `tag:<tag>` to run on
+        #[arg(required = true)]
+        targets: Vec<String>,
+        /// Remote command, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+        /// How many hosts to run on at once
+        #[arg(short = 'j', long, default_value_t = DEFAULT_CONCURRENCY)]
+        concurrency: usize,
+        /// How to print output while running
+        #[arg(long, value_enum, default_value_t = OutputMode::Prefixed)]
+        output: OutputMode,
+        /// Print a JSON report instead of streaming output
+        #[arg(long)]
+        json: bool,
+    },
+    /// Inspect the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Manage custom themes
+    #[command(subcommand)]
+    Theme(ThemeCommand),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputMode {
+    /// Stream every line as it arrives, prefixed with the host name
+    Prefixed,
+    /// Print each host's output in one block once it finishes
+    Grouped,
+}
+
+#[derive(Subcommand)]
+pub enum ThemeCommand {
+    /// Import a base16 YAML, Alacritty TOML or iTerm .itermcolors scheme
+    /// into the themes/ dir next to the config
+    Import {
+        file: PathBuf,
+        /// Theme name; defaults to the file name
+        #[arg(long)]
+        name: Option<String>,
+        /// Replace a theme file of the same name
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the config file location
+    Path,
+    /// Parse the config file and report problems; exits 1 on errors and 2
+    /// when there are only warnings
+    Check,
+}
+
+pub fn run(cli: Cli) -> Result<ExitCode> {
+    let path = cli
+        .config
+        .or_else(|| env::var_os("MANGSH_CONFIG").map(PathBuf::from))
+        .or_else(default_config_path);
+
+    let done = match cli.command {
+        None => launch(path, None),
+        Some(Command::Connect { host }) => launch(path, Some(&host)),
+        Some(Command::List { json }) => list(path.as_deref(), json),
+        Some(Command::Add { name, addr, user, port, tor, identity, template, jump, keepalive, tags }) => {
+            let tor = tor.then_some(true);
+            add(
+                require_path(&path)?,
+                HostProfile { name, addr, template, port, user, tor, identity, jump, keepalive, tags },
+            )
+        }
+        Some(Command::Remove { name }) => remove(require_path(&path)?, &name),
+        Some(Command::Exec { targets, command, concurrency, output, json }) => {
+            exec(path.as_deref(), &targets, command, concurrency, output, json)
+        }
+        Some(Command::Config(ConfigCommand::Path)) => {
+            println!("{}", require_path(&path)?.display());
+            Ok(())
+        }
+        Some(Command::Config(ConfigCommand::Check)) => return check(require_path(&path)?),
+        Some(Command::Theme(ThemeCommand::Import { file, name, force })) => {
+            import_theme(require_path(&path)?, &file, name.as_deref(), force)
+        }
+    };
+    done.map(|()| ExitCode::SUCCESS)
+}
+
+fn require_path(path: &Option<PathBuf>) -> Result<&Path> {
+    path.as_deref()
+        .ok_or_else(|| anyhow!("No config directory available; pass --config <FILE>."))
+}
+
+// one line each on stderr, so they stay out of piped and `--json` output
+fn warn(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{}: {diagnostic}", diagnostic.severity.label());
+    }
+}
+
+fn launch(path: Option<PathBuf>, host: Option<&str>) -> Result<()> {
+    let mut app = App::new(load_config(path));
+    if let Some(host) = host {
+        app.connect(host)?;
+    }
+    app.run()
+}
+
+fn list(path: Option<&Path>, json: bool) -> Result<()> {
+    let (config, warnings) = read_layered(path)?;
+    warn(&warnings);
+    let hosts = config.hosts.iter().map(|host| config.resolve(host)).collect::<Vec<_>>();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&hosts)?);
+        return Ok(());
+    }
+
+    for host in &hosts {
+        let tor = if host.tor { "  tor" } else { "" };
+        let jump = host.jump.as_ref().map(|jump| format!("  via {jump}")).unwrap_or_default();
+        let tags = if host.tags.is_empty() {
+            String::new()
+        } else {
+            format!("  [{}]", host.tags.join(", "))
+        };
+        println!("{}\t{}:{}{tor}{jump}{tags}", host.name, host.destination(), host.port);
+    }
+    Ok(())
+}
+
+fn add(path: &Path, host: HostProfile) -> Result<()> {
+    let mut config = read_config(path, true)?;
+    // checked like the host form does, against everything that's loaded
+    let problems = check_host(&host, None, &load_config(Some(path.to_path_buf())).config)
+        .into_iter()
+        .map(|(field, problem)| format!("{}: {problem}", field.label().to_lowercase()))
+        .collect::<Vec<_>>();
+    if !problems.is_empty() {
+        bail!("Can't add {}: {}", host.name, problems.join("; "));
+    }
+    let name = host.name.clone();
+    config.hosts.push(host);
+    save_config(&config, Some(path))?;
+    println!("Added {name}");
+    Ok(())
+}
+
+fn remove(path: &Path, name: &str) -> Result<()> {
+    let mut config = read_config(path, false)?;
+    let Some(index) = config.host_index(name) else {
+        bail!("Unknown host: {name}");
+    };
+    config.remove_host(index);
+    save_config(&config, Some(path))?;
+    println!("Removed {name}");
+    Ok(())
+}
+
+fn import_theme(path: &Path, file: &Path, name: Option<&str>, force: bool) -> Result<()> {
+    let (theme, format) = import_scheme(file, name)?;
+    if let Err(problem) = check_theme_name(&theme.name) {
+        bail!("Can't import as `{}`: {problem} (--name sets the name)", theme.name);
+    }
+    let loaded = load_config(Some(path.to_path_buf()));
+    if let Some(origin) = loaded.layers.inline_theme(&theme.name) {
+        bail!("{} already defines a theme called {}; pick another name with --name", origin.label(), theme.name);
+    }
+    let target = theme_path(path, &theme.name);
+    if target.exists() && !force {
+        bail!("{} already exists; pass --force to replace it", target.display());
+    }
+    let saved = save_theme(path, &theme)?;
+    println!("Imported {} scheme as {} ({})", format.label(), theme.name, saved.display());
+    println!("Pick it on the Config screen or set theme = \"{}\"", theme.name);
+    Ok(())
+}
+
+// only warnings exit 2; errors come back as an `Err` and exit 1
+fn check(path: &Path) -> Result<ExitCode> {
+    if !path.is_file() {
+        bail!("Unable to read {}: no such file", path.display());
+    }
+    let loaded = load_config(Some(path.to_path_buf()));
+    for layer in &loaded.layers.layers {
+        let status = match (layer.found, &layer.origin) {
+            (false, _) => "not found".to_string(),
+            (true, Origin::Theme(_)) => "custom theme".to_string(),
+            (true, _) => format!("{} host{}", layer.hosts, if layer.hosts == 1 { "" } else { "s" }),
+        };
+        println!("{:<8} {}  ({status})", layer.origin.kind(), layer.origin.label());
+    }
+    for migrated in &loaded.layers.migrated {
+        println!("{}", migrated.summary());
+    }
+    let diagnostics = &loaded.diagnostics;
+    for diagnostic in diagnostics {
+        for line in diagnostic.render() {
+            eprintln!("{line}");
+        }
+        eprintln!();
+    }
+    if loaded.failed() {
+        bail!("config has errors");
+    }
+    let config = &loaded.config;
+    println!(
+        "{}: ok ({} host{})",
+        path.display(),
+        config.hosts.len(),
+        if config.hosts.len() == 1 { "" } else { "s" }
+    );
+    if !diagnostics.is_empty() {
+        let count = diagnostics.len();
+        eprintln!("{count} warning{}", if count == 1 { "" } else { "s" });
+        return Ok(ExitCode::from(2));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[derive(Serialize)]
+struct ExecReport {
+    host: String,
+    exit_code: Option<i32>,
+    duration_ms: u128,
+    error: Option<String>,
+    stdout: Vec<String>,
+    stderr: Vec<String>,
+}
+
+fn exec(
+    path: Option<&Path>,
+    targets: &[String],
+    command: Vec<String>,
+    concurrency: usize,
+    output: OutputMode,
+    json: bool,
+) -> Result<()> {
+    let (config, warnings) = read_layered(path)?;
+    warn(&warnings);
+    let hosts = config.resolve_targets(targets)?;
+    let width = hosts.iter().map(|h| h.name.len()).max().unwrap_or(0);
+
+    let mut reports = hosts
+        .iter()
+        .map(|host| ExecReport {
+            host: host.name.clone(),
+            exit_code: None,
+            duration_ms: 0,
+            error: None,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    let mut outcomes: Vec<Option<ExecOutcome>> = vec![None; hosts.len()];
+
+    for event in spawn_exec(hosts, command, concurrency) {
+        match event {
+            ExecEvent::Started { .. } => {}
+            ExecEvent::Output { index, stream, line } => {
+                let report = &mut reports[index];
+                if !json && matches!(output, OutputMode::Prefixed) {
+                    match stream {
+                        Stream::Stdout => println!("{:width$} | {line}", report.host),
+                        Stream::Stderr => eprintln!("{:width$} ! {line}", report.host),
+                    }
+                }
+                match stream {
+                    Stream::Stdout => report.stdout.push(line),
+                    Stream::Stderr => report.stderr.push(line),
+                }
+            }
+            ExecEvent::Finished { index, outcome } => {
+                let report = &mut reports[index];
+                report.exit_code = outcome.exit_code;
+                report.duration_ms = outcome.duration.as_millis();
+                report.error = outcome.error.clone();
+                if !json && matches!(output, OutputMode::Grouped) {
+                    println!("=== {} ===", report.host);
+                    for line in &report.stdout {
+                        println!("{line}");
+                    }
+                    for line in &report.stderr {
+                        eprintln!("{line}");
+                    }
+                }
+                outcomes[index] = Some(outcome);
+            }
+        }
+    }
+
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| !outcome.as_ref().is_some_and(ExecOutcome::success))
+        .count();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        println!();
+        for (report, outcome) in reports.iter().zip(&outcomes) {
+            let ok = outcome.as_ref().is_some_and(ExecOutcome::success);
+            let code = report
+                .exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let error = report
+                .error
+                .as_deref()
+                .map(|err| format!("  ({err})"))
+                .unwrap_or_default();
+            println!(
+                "{:width$}  {:4}  exit {code:>3}  {:.1}s{error}",
+                report.host,
+                if ok { "ok" } else { "FAIL" },
+                report.duration_ms as f64 / 1000.0,
+            );
+        }
+        println!("{} host(s), {failed} failed", reports.len());
+    }
+
+    if failed > 0 {
+        bail!("{failed} of {} host(s) failed", reports.len());
+    }
+    Ok(())
+}
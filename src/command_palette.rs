@@ -0,0 +1,175 @@
+use crate::{
+    config::Config,
+    fuzzy::fuzzy_match,
+    keys::{Action, Keymap, Scope},
+    theme::ThemeName,
+};
+
+// planned features, by label, with what picking them says
+const UNAVAILABLE: [(&str, &str); 2] = [
+    ("New port forward", "Port forwards aren't supported yet."),
+    ("Start recording", "Session recording isn't supported yet."),
+];
+
+/// What picking a palette entry does.
+#[derive(Clone)]
+pub enum Command {
+    Action(Action),
+    /// Open a session to the host with this name, looked up when picked
+    /// since a reload can move hosts around while the palette is open.
+    Connect(String),
+    Theme(ThemeName),
+    /// Something mangsh can't do yet, listed so it can be found; picking it
+    /// says why.
+    Unavailable(&'static str),
+}
+
+pub struct Entry {
+    pub command: Command,
+    pub label: String,
+    /// Shown on the right: the keys for an action, the address for a host.
+    pub detail: String,
+}
+
+/// An entry that fits the query, with the label chars that matched.
+pub struct PaletteHit {
+    pub entry: usize,
+    pub positions: Vec<usize>,
+}
+
+// state for the Ctrl-p overlay: everything that can be done from the screen
+// it was opened on, filtered as you type
+pub struct CommandPalette {
+    pub entries: Vec<Entry>,
+    pub query: String,
+    /// Index into `hits()`.
+    pub selected: usize,
+}
+
+impl CommandPalette {
+    /// The actions `scope` has with the keys bound to them, then a connect
+    /// for every host, a switch for every theme and the features still to
+    /// come, greyed out.
+    pub fn new(scope: Scope, keymap: &Keymap, config: &Config, theme: &ThemeName) -> Self {
+        let mut entries = Action::ALL
+            .into_iter()
+            // moving a cursor is no use from a list
+            .filter(|action| scope.has(*action) && !matches!(action, Action::Up | Action::Down | Action::CommandPalette))
+            .map(|action| Entry {
+                command: Command::Action(action),
+                label: action.label().to_string(),
+                detail: hint(keymap, scope, action),
+            })
+            .collect::<Vec<_>>();
+        entries.extend(config.hosts.iter().map(|host| Entry {
+            command: Command::Connect(host.name.clone()),
+            label: format!("Connect to {}", host.name),
+            detail: host.addr.clone(),
+        }));
+        entries.extend(config.theme_names().into_iter().map(|name| Entry {
+            label: format!("Switch theme to {}", name.label()),
+            detail: if name == *theme { "current".to_string() } else { String::new() },
+            command: Command::Theme(name),
+        }));
+        entries.extend(UNAVAILABLE.into_iter().map(|(label, reason)| Entry {
+            command: Command::Unavailable(reason),
+            label: label.to_string(),
+            detail: "not available yet".to_string(),
+        }));
+        Self {
+            entries,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// Entries matching the query, best first; all of them, in order, while
+    /// it's empty.
+    pub fn hits(&self) -> Vec<PaletteHit> {
+        let mut hits = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let found = fuzzy_match(&self.query, &entry.label)?;
+                Some((found.score, PaletteHit { entry: index, positions: found.positions }))
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by_key(|(score, _)| -score);
+        hits.into_iter().map(|(_, hit)| hit).collect()
+    }
+
+    pub fn selected_command(&self) -> Option<Command> {
+        let hit = self.hits().into_iter().nth(self.selected)?;
+        Some(self.entries[hit.entry].command.clone())
+    }
+
+    pub fn next(&mut self) {
+        let count = self.hits().len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        let count = self.hits().len();
+        if count > 0 {
+            self.selected = self.selected.checked_sub(1).unwrap_or(count - 1);
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+}
+
+// e.g. `→/l`, or `Ctrl+a c` for something only reachable behind the prefix
+fn hint(keymap: &Keymap, scope: Scope, action: Action) -> String {
+    let keys = keymap.keys(scope, action);
+    let keys = keys.iter().map(|chord| chord.label()).collect::<Vec<_>>().join("/");
+    match scope {
+        Scope::Session if !keys.is_empty() => format!("{} {keys}", keymap.prefix.label()),
+        _ => keys,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, CommandPalette};
+    use crate::{
+        config::Config,
+        keys::{Keymap, Scope},
+        theme::ThemeName,
+    };
+
+    fn palette(query: &str) -> CommandPalette {
+        let config = Config::default();
+        let keymap = Keymap::new(&config.keys, None);
+        let mut palette = CommandPalette::new(Scope::Main, &keymap, &config, &ThemeName::Auto);
+        query.chars().for_each(|c| palette.push(c));
+        palette
+    }
+
+    #[test]
+    fn lists_features_still_to_come() {
+        for query in ["new forward", "start recording"] {
+            let palette = palette(query);
+            assert!(matches!(palette.selected_command(), Some(Command::Unavailable(_))), "{query}");
+        }
+    }
+
+    #[test]
+    fn actions_come_with_their_keys() {
+        let palette = palette("open config");
+        let hit = &palette.hits()[0];
+        let entry = &palette.entries[hit.entry];
+        assert!(matches!(entry.command, Command::Action(_)));
+        assert_eq!(entry.detail, "c");
+    }
+}
@@ -0,0 +1,459 @@
+use std::{
+    fmt, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use toml_edit::{ImDocument, Item, Table, TableLike};
+
+use super::Config;
+use crate::{
+    keys::{Action, KeyChord, KeyConflict, Scope, DEFAULT_PREFIX, UNBOUND},
+    theme::CustomTheme,
+};
+
+// keys mangsh reads, per table; anything else is kept but checked for typos
+const TOP_KEYS: &[&str] = &[
+    "version", "theme", "light_theme", "dark_theme", "include", "themes", "defaults", "templates", "hosts", "groups",
+    "prefix_key", "keys",
+];
+const THEME_KEYS: &[&str] = &[
+    "name", "base", "base_alt", "surface", "overlay", "accent", "accent_soft", "text", "text_muted", "ansi",
+];
+const SETTING_KEYS: &[&str] = &["user", "port", "identity", "jump", "tor", "keepalive"];
+const TEMPLATE_KEYS: &[&str] = &["name", "match", "user", "port", "identity", "jump", "tor", "keepalive"];
+const HOST_KEYS: &[&str] = &[
+    "name", "addr", "template", "port", "user", "tor", "identity", "jump", "keepalive", "tags",
+];
+const GROUP_KEYS: &[&str] = &["name", "hosts", "tags"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// One problem found in a config file, pinned to where it is.
+#[derive(Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: PathBuf,
+    /// 1-based; 0 when the problem isn't tied to a position.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// The offending source line and the char range in it to underline.
+    pub snippet: Option<(String, Range<usize>)>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, path: &Path, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            path: path.to_path_buf(),
+            line: 0,
+            column: 0,
+            message: message.into(),
+            snippet: None,
+            help: None,
+        }
+    }
+
+    fn at(mut self, source: &str, span: Range<usize>) -> Self {
+        let mut start = floor_char_boundary(source, span.start.min(source.len()));
+        // errors at end of input point past the final newline; show them at
+        // the end of the last line instead
+        if start == source.len() && source.ends_with('\n') {
+            start -= 1;
+        }
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let text = source[line_start..line_end].trim_end_matches('\r');
+
+        let column = source[line_start..start].chars().count();
+        let end = floor_char_boundary(source, span.end.clamp(start, line_end));
+        let width = source[start..end].chars().count().max(1);
+
+        self.line = source[..line_start].matches('\n').count() + 1;
+        self.column = column + 1;
+        self.snippet = Some((text.to_string(), column..column + width));
+        self
+    }
+
+    /// `file:line:col`, or just the file for positionless problems.
+    pub fn location(&self) -> String {
+        match self.line {
+            0 => self.path.display().to_string(),
+            line => format!("{}:{line}:{}", self.path.display(), self.column),
+        }
+    }
+
+    /// Multi-line report in the style of compiler output.
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("{}: {}", self.severity.label(), self.message),
+            format!("  --> {}", self.location()),
+        ];
+        if let Some((text, range)) = &self.snippet {
+            let number = self.line.to_string();
+            let pad = " ".repeat(number.len());
+            lines.push(format!("{pad} |"));
+            lines.push(format!("{number} | {text}"));
+            lines.push(format!(
+                "{pad} | {}{}",
+                " ".repeat(range.start),
+                "^".repeat(range.len())
+            ));
+        }
+        if let Some(help) = &self.help {
+            lines.push(format!("  = help: {help}"));
+        }
+        lines
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location(), self.message)?;
+        if let Some(help) = &self.help {
+            write!(f, " ({help})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `source` (read from `path`), returning the config when it is
+/// usable along with every error and warning found.
+pub fn check_source(path: &Path, source: &str) -> (Option<Config>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    if let Ok(doc) = ImDocument::parse(source) {
+        let root = doc.as_table();
+        unknown_keys(path, source, root, TOP_KEYS, &mut diagnostics);
+        if let Some(defaults) = root.get("defaults").and_then(Item::as_table) {
+            unknown_keys(path, source, defaults, SETTING_KEYS, &mut diagnostics);
+        }
+        let tables = [("themes", THEME_KEYS), ("templates", TEMPLATE_KEYS), ("hosts", HOST_KEYS), ("groups", GROUP_KEYS)];
+        for (key, known) in tables {
+            if let Some(tables) = root.get(key).and_then(Item::as_array_of_tables) {
+                for table in tables.iter() {
+                    unknown_keys(path, source, table, known, &mut diagnostics);
+                }
+            }
+        }
+        if let Some(prefix) = root.get("prefix_key") {
+            check_prefix(path, source, prefix, &mut diagnostics);
+        }
+        if let Some(keys) = root.get("keys").and_then(Item::as_table_like) {
+            check_keys(path, source, keys, &mut diagnostics);
+        }
+    }
+
+    match toml::from_str::<Config>(source) {
+        Ok(config) => (Some(config), diagnostics),
+        Err(err) => {
+            diagnostics.insert(0, parse_error(path, source, &err));
+            (None, diagnostics)
+        }
+    }
+}
+
+/// Warning for two `[keys]` bindings that get in each other's way, pointing
+/// at the entry in `path` when it's there.
+pub fn key_conflict(path: &Path, conflict: &KeyConflict) -> Diagnostic {
+    let diagnostic = Diagnostic::new(Severity::Warning, path, conflict.message.clone());
+    let Ok(source) = fs::read_to_string(path) else {
+        return diagnostic;
+    };
+    let span = ImDocument::parse(source.as_str()).ok().and_then(|doc| {
+        let table = doc.get("keys")?.get(conflict.scope.key())?.as_table_like()?;
+        table.key(&conflict.key)?.span()
+    });
+    match span {
+        Some(span) => diagnostic.at(&source, span),
+        None => diagnostic,
+    }
+}
+
+/// `check_source` for a theme file from the `themes/` dir.
+pub fn check_theme_source(path: &Path, source: &str) -> (Option<CustomTheme>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    if let Ok(doc) = ImDocument::parse(source) {
+        unknown_keys(path, source, doc.as_table(), THEME_KEYS, &mut diagnostics);
+    }
+    match toml::from_str::<CustomTheme>(source) {
+        Ok(theme) => (Some(theme), diagnostics),
+        Err(err) => {
+            diagnostics.insert(0, parse_error(path, source, &err));
+            (None, diagnostics)
+        }
+    }
+}
+
+/// Warning for a `theme` (or `light_theme`/`dark_theme`, per `key`) that
+/// names no built-in or custom theme, pointing at the value in `path` when
+/// it's there. `fallback` says what's used instead.
+pub fn unknown_theme(path: &Path, key: &str, name: &str, known: &[&str], fallback: &str) -> Diagnostic {
+    let message = format!("unknown theme `{name}`; using {fallback}");
+    let mut diagnostic = Diagnostic::new(Severity::Warning, path, message);
+    diagnostic.help = closest(name, known).map(|best| format!("did you mean `{best}`?"));
+    let Ok(source) = fs::read_to_string(path) else {
+        return diagnostic;
+    };
+    let span = ImDocument::parse(source.as_str())
+        .ok()
+        .and_then(|doc| doc.get(key).and_then(Item::span));
+    match span {
+        Some(span) => diagnostic.at(&source, span),
+        None => diagnostic,
+    }
+}
+
+/// Warning for a host whose `template` names no template.
+pub fn unknown_template(path: &Path, host: &str, name: &str, known: &[&str]) -> Diagnostic {
+    let message = format!("host `{host}` uses unknown template `{name}`; nothing is inherited from it");
+    let mut diagnostic = Diagnostic::new(Severity::Warning, path, message);
+    diagnostic.help = closest(name, known).map(|best| format!("did you mean `{best}`?"));
+    at_host(diagnostic, host, &["template"])
+}
+
+/// Warning for a host that ends up with both `tor` and `jump`, which ssh
+/// can't do together.
+pub fn tor_with_jump(path: &Path, host: &str) -> Diagnostic {
+    let message = format!("host `{host}` sets both `tor` and `jump`; connecting over tor without the jump host");
+    let mut diagnostic = Diagnostic::new(Severity::Warning, path, message);
+    diagnostic.help = Some("drop one of them; tor's proxy replaces the jump".to_string());
+    at_host(diagnostic, host, &["jump", "tor"])
+}
+
+// pins a diagnostic on the first of `keys` in the `[[hosts]]` entry called
+// `host`, or on its name when it sets none of them (they were inherited)
+fn at_host(diagnostic: Diagnostic, host: &str, keys: &[&str]) -> Diagnostic {
+    let Ok(source) = fs::read_to_string(&diagnostic.path) else {
+        return diagnostic;
+    };
+    let span = ImDocument::parse(source.as_str()).ok().and_then(|doc| {
+        let table = doc
+            .get("hosts")?
+            .as_array_of_tables()?
+            .iter()
+            .find(|table| table.get("name").and_then(Item::as_str) == Some(host))?;
+        keys.iter().chain(&["name"]).find_map(|key| table.get(key)).and_then(Item::span)
+    });
+    match span {
+        Some(span) => diagnostic.at(&source, span),
+        None => diagnostic,
+    }
+}
+
+fn parse_error(path: &Path, source: &str, err: &toml::de::Error) -> Diagnostic {
+    let message = err
+        .message()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let mut diagnostic = Diagnostic::new(Severity::Error, path, message);
+    diagnostic.help = suggest_value(err.message());
+    match err.span() {
+        Some(span) => diagnostic.at(source, span),
+        None => diagnostic,
+    }
+}
+
+// serde reports bad enum values and fields as
+// "unknown variant `x`, expected one of `a`, `b`"
+fn suggest_value(message: &str) -> Option<String> {
+    if !message.starts_with("unknown variant") && !message.starts_with("unknown field") {
+        return None;
+    }
+    let mut quoted = message.split('`').skip(1).step_by(2);
+    let given = quoted.next()?;
+    let candidates = quoted.collect::<Vec<_>>();
+    closest(given, &candidates).map(|best| format!("did you mean `{best}`?"))
+}
+
+// keys that look like a typo of one mangsh knows; other extra keys are left
+// alone since saving preserves them
+fn unknown_keys(path: &Path, source: &str, table: &Table, known: &[&str], out: &mut Vec<Diagnostic>) {
+    for (key, _) in table.iter() {
+        if known.contains(&key) {
+            continue;
+        }
+        let Some(best) = closest(key, known) else {
+            continue;
+        };
+        let mut diagnostic = Diagnostic::new(Severity::Warning, path, format!("unknown key `{key}`"));
+        diagnostic.help = Some(format!("did you mean `{best}`?"));
+        let span = table.key(key).and_then(|k| k.span());
+        out.push(match span {
+            Some(span) => diagnostic.at(source, span),
+            None => diagnostic,
+        });
+    }
+}
+
+// `[keys.<screen>]` tables: each key has to be a key chord and each value an
+// action the screen has, or `none`
+fn check_keys(path: &Path, source: &str, keys: &dyn TableLike, out: &mut Vec<Diagnostic>) {
+    let pinned = |diagnostic: Diagnostic, span: Option<Range<usize>>| match span {
+        Some(span) => diagnostic.at(source, span),
+        None => diagnostic,
+    };
+    let scopes = Scope::ALL.map(|scope| scope.key());
+    let mut actions = Action::ALL.map(|action| action.name()).to_vec();
+    actions.push(UNBOUND);
+
+    for (name, item) in keys.iter() {
+        let span = keys.key(name).and_then(|key| key.span());
+        let Some(scope) = Scope::parse(name) else {
+            let mut diagnostic = Diagnostic::new(Severity::Warning, path, format!("unknown screen `keys.{name}`"));
+            diagnostic.help = Some(match closest(name, &scopes) {
+                Some(best) => format!("did you mean `{best}`?"),
+                None => format!("screens are {}", scopes.join(", ")),
+            });
+            out.push(pinned(diagnostic, span));
+            continue;
+        };
+        let Some(table) = item.as_table_like() else {
+            continue;
+        };
+        for (key, value) in table.iter() {
+            if let Err(err) = KeyChord::parse(key) {
+                let span = table.key(key).and_then(|key| key.span());
+                out.push(pinned(Diagnostic::new(Severity::Warning, path, err), span));
+                continue;
+            }
+            let Some(action_name) = value.as_str() else {
+                let message = format!("`{key}` in [keys.{name}] has to be an action name in quotes");
+                let mut diagnostic = Diagnostic::new(Severity::Warning, path, message);
+                diagnostic.help = Some(format!("e.g. `{key} = \"{}\"`, or `\"{UNBOUND}\"` to unbind it", actions[0]));
+                out.push(pinned(diagnostic, value.span()));
+                continue;
+            };
+            if action_name == UNBOUND {
+                continue;
+            }
+            match Action::parse(action_name) {
+                None => {
+                    let mut diagnostic =
+                        Diagnostic::new(Severity::Warning, path, format!("unknown action `{action_name}`"));
+                    diagnostic.help = closest(action_name, &actions).map(|best| format!("did you mean `{best}`?"));
+                    out.push(pinned(diagnostic, value.span()));
+                }
+                Some(action) if !scope.has(action) => {
+                    let message = format!("`{action_name}` does nothing on {}", scope.label());
+                    out.push(pinned(Diagnostic::new(Severity::Warning, path, message), value.span()));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+// `prefix_key` has to be a key, and one sessions can spare
+fn check_prefix(path: &Path, source: &str, item: &Item, out: &mut Vec<Diagnostic>) {
+    let Some(diagnostic) = item.as_str().and_then(|text| prefix_problem(path, text)) else {
+        return;
+    };
+    out.push(match item.span() {
+        Some(span) => diagnostic.at(source, span),
+        None => diagnostic,
+    });
+}
+
+/// What's wrong with `text` as the prefix key, if anything.
+pub fn prefix_problem(path: &Path, text: &str) -> Option<Diagnostic> {
+    let (message, help) = match KeyChord::parse(text) {
+        Err(err) => (format!("{err}; using `{DEFAULT_PREFIX}`"), None),
+        Ok(chord) if chord.is_plain() => (
+            format!("prefix key `{text}` can't be typed into sessions any more"),
+            Some(format!("use one with ctrl or alt, e.g. `ctrl-{chord}`")),
+        ),
+        Ok(_) => return None,
+    };
+    let mut diagnostic = Diagnostic::new(Severity::Warning, path, message);
+    diagnostic.help = help;
+    Some(diagnostic)
+}
+
+/// The candidate within typo distance of `given`, if any.
+fn closest<'a>(given: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let given = given.to_lowercase();
+    let limit = (given.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(&given, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance, counting a swap of neighbours as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, edit_distance};
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("port", "port"), 0);
+        assert_eq!(edit_distance("", "port"), 4);
+        assert_eq!(edit_distance("prot", "port"), 1);
+        assert_eq!(edit_distance("hots", "hosts"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn closest_stays_within_typo_distance() {
+        let keys = ["hostname", "port", "user", "identity_file"];
+        assert_eq!(closest("Hostnme", &keys), Some("hostname"));
+        assert_eq!(closest("prot", &keys), Some("port"));
+        assert_eq!(closest("identityfile", &keys), Some("identity_file"));
+        assert_eq!(closest("address", &keys), None);
+        assert_eq!(closest("x", &keys), None);
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{Context, Result};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+
+use super::Config;
+
+/// Applies `config` to an existing TOML document, touching only the keys
+/// whose values actually changed. Comments, ordering, formatting and keys
+/// mangsh doesn't know about are left alone.
+pub fn merge_into(doc: &mut DocumentMut, config: &Config) -> Result<()> {
+    // what mangsh would have written for the document as it stands; diffing
+    // against this (rather than the raw file) keeps defaulted keys such as an
+    // omitted `port` from being filled in
+    let old = match toml::from_str::<Config>(&doc.to_string()) {
+        Ok(current) => serialize(&current)?,
+        Err(_) => DocumentMut::new(),
+    };
+    let new = serialize(config)?;
+
+    merge_table(doc.as_table_mut(), old.as_table(), new.as_table());
+    Ok(())
+}
+
+fn serialize(config: &Config) -> Result<DocumentMut> {
+    toml::to_string(config)?
+        .parse::<DocumentMut>()
+        .context("Unable to build config document")
+}
+
+fn same(a: &Item, b: &Item) -> bool {
+    a.to_string().trim() == b.to_string().trim()
+}
+
+fn merge_table(target: &mut Table, old: &Table, new: &Table) {
+    for (key, new_item) in new.iter() {
+        let old_item = old.get(key);
+        if old_item.is_some_and(|old_item| same(old_item, new_item)) {
+            continue;
+        }
+
+        match (target.get_mut(key), new_item) {
+            (Some(Item::ArrayOfTables(existing)), Item::ArrayOfTables(new_tables)) => {
+                let empty = ArrayOfTables::new();
+                let old_tables = old_item.and_then(Item::as_array_of_tables).unwrap_or(&empty);
+                merge_tables(existing, old_tables, new_tables);
+            }
+            (Some(Item::Table(existing)), Item::Table(new_table)) => {
+                let empty = Table::new();
+                let old_table = old_item.and_then(Item::as_table).unwrap_or(&empty);
+                merge_table(existing, old_table, new_table);
+            }
+            (Some(Item::Value(existing)), Item::Value(value)) => {
+                let decor = existing.decor().clone();
+                *existing = value.clone();
+                *existing.decor_mut() = decor;
+            }
+            (Some(existing), _) => *existing = new_item.clone(),
+            (None, _) => {
+                target.insert(key, new_item.clone());
+            }
+        }
+    }
+
+    let removed = old
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect::<Vec<_>>();
+    for key in removed {
+        target.remove(&key);
+    }
+}
+
+fn name_of(table: &Table) -> Option<&str> {
+    table.get("name").and_then(Item::as_str)
+}
+
+// `[[hosts]]`-style arrays: entries are matched by `name`, so untouched
+// entries keep their place and comments. An entry only counts as renamed
+// when it's one of the same number of leftovers between the same two
+// name-matched neighbours on both sides; anything else is removed or
+// appended rather than guessed at
+fn merge_tables(target: &mut ArrayOfTables, old: &ArrayOfTables, new: &ArrayOfTables) {
+    // `old` was derived from `target`, so the two line up by index
+    let paired = old.len() == target.len();
+    let mut claimed = vec![false; target.len()];
+    let mut matches: Vec<Option<usize>> = new
+        .iter()
+        .map(|table| {
+            let name = name_of(table)?;
+            let index = target
+                .iter()
+                .enumerate()
+                .position(|(i, t)| !claimed[i] && name_of(t) == Some(name))?;
+            claimed[index] = true;
+            Some(index)
+        })
+        .collect();
+
+    let mut gap = Vec::new();
+    let mut start = 0;
+    for i in 0..=matches.len() {
+        let anchor = match matches.get(i) {
+            Some(None) => {
+                gap.push(i);
+                continue;
+            }
+            Some(Some(index)) => *index,
+            None => target.len(),
+        };
+        let leftovers = (start..anchor.max(start)).filter(|t| !claimed[*t]).collect::<Vec<_>>();
+        if leftovers.len() == gap.len() {
+            for (&i, &t) in gap.iter().zip(&leftovers) {
+                claimed[t] = true;
+                matches[i] = Some(t);
+            }
+        }
+        gap.clear();
+        start = start.max(anchor + 1);
+    }
+
+    let empty = Table::new();
+    let mut appended = Vec::new();
+    for (new_table, slot) in new.iter().zip(&matches) {
+        match slot {
+            Some(index) => {
+                let old_table = if paired { old.get(*index).unwrap_or(&empty) } else { &empty };
+                if let Some(existing) = target.get_mut(*index) {
+                    merge_table(existing, old_table, new_table);
+                }
+            }
+            None => appended.push(new_table.clone()),
+        }
+    }
+
+    let mut index = 0;
+    target.retain(|_| {
+        let keep = claimed[index];
+        index += 1;
+        keep
+    });
+    for table in appended {
+        target.push(table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml_edit::DocumentMut;
+
+    use super::merge_into;
+    use crate::config::Config;
+
+    const SOURCE: &str = r#"# my hosts
+theme = "mango" # the usual
+
+[[hosts]]
+# the gateway
+name = "gate"
+addr = "10.0.0.1"
+
+[[hosts]]
+name = "web"
+addr = "10.0.0.2"
+user = "ops" # shared account
+
+[[hosts]]
+name = "db"
+addr = "10.0.0.3"
+"#;
+
+    // parses `SOURCE`, lets `change` edit the config and merges it back
+    fn merged(change: impl FnOnce(&mut Config)) -> String {
+        let mut doc = SOURCE.parse::<DocumentMut>().unwrap();
+        let mut config = toml::from_str::<Config>(SOURCE).unwrap();
+        change(&mut config);
+        merge_into(&mut doc, &config).unwrap();
+        doc.to_string()
+    }
+
+    #[test]
+    fn unchanged_config_leaves_the_file_alone() {
+        assert_eq!(merged(|_| {}), SOURCE);
+    }
+
+    #[test]
+    fn changed_values_keep_their_comments() {
+        let text = merged(|config| config.hosts[1].user = Some("deploy".to_string()));
+        assert_eq!(text, SOURCE.replace("user = \"ops\"", "user = \"deploy\""));
+    }
+
+    #[test]
+    fn added_keys_and_hosts_are_appended() {
+        let text = merged(|config| {
+            config.hosts[0].port = Some(2222);
+            let mut extra = config.hosts[2].clone();
+            extra.name = "cache".to_string();
+            extra.addr = "10.0.0.4".to_string();
+            config.hosts.push(extra);
+        });
+        assert!(text.starts_with("# my hosts\n"));
+        assert!(text.contains("# the gateway\nname = \"gate\"\naddr = \"10.0.0.1\"\nport = 2222\n"));
+        assert!(text.ends_with("[[hosts]]\nname = \"cache\"\naddr = \"10.0.0.4\"\n"));
+    }
+
+    #[test]
+    fn removed_hosts_keep_the_others_in_place() {
+        let text = merged(|config| {
+            config.hosts.remove(1);
+        });
+        let web = "[[hosts]]\nname = \"web\"\naddr = \"10.0.0.2\"\nuser = \"ops\" # shared account\n\n";
+        assert_eq!(text, SOURCE.replace(web, ""));
+    }
+
+    #[test]
+    fn renamed_host_is_edited_in_place() {
+        let text = merged(|config| config.hosts[1].name = "www".to_string());
+        assert_eq!(text, SOURCE.replace("name = \"web\"", "name = \"www\""));
+    }
+
+    #[test]
+    fn defaulted_keys_stay_out() {
+        let text = merged(|config| config.hosts[2].addr = "10.0.0.9".to_string());
+        assert!(!text.contains("port"));
+        assert!(!text.contains("version"));
+    }
+}
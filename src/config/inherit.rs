@@ -0,0 +1,313 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::{
+    schema::{expand_home, HostProfile, HostSettings},
+    Config,
+};
+
+/// Port used when nothing sets one.
+pub const DEFAULT_PORT: u16 = 22;
+
+/// Which part of the config a resolved setting came from.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Source {
+    Builtin,
+    Defaults,
+    Template(String),
+    Host,
+}
+
+impl Source {
+    pub fn label(&self) -> String {
+        match self {
+            Source::Builtin => "built-in".to_string(),
+            Source::Defaults => "[defaults]".to_string(),
+            Source::Template(name) => format!("template {name}"),
+            Source::Host => "host".to_string(),
+        }
+    }
+}
+
+/// A host with everything it inherits filled in; what sessions and exec
+/// actually connect with.
+#[derive(Clone, Debug, Serialize)]
+pub struct ResolvedHost {
+    pub name: String,
+    pub addr: String,
+    /// `None` leaves the choice to ssh (the local user name).
+    pub user: Option<String>,
+    pub port: u16,
+    pub identity: Option<String>,
+    /// `-J` target, with configured host names expanded.
+    pub jump: Option<String>,
+    pub tor: bool,
+    pub keepalive: Option<u32>,
+    pub tags: Vec<String>,
+    /// Where each setting came from, in the order the detail view lists them.
+    #[serde(skip)]
+    pub sources: Vec<(&'static str, Source)>,
+}
+
+impl ResolvedHost {
+    /// The identity file with a leading `~` expanded.
+    pub fn identity_path(&self) -> Option<PathBuf> {
+        self.identity.as_deref().map(expand_home)
+    }
+
+    /// `user@addr`, or just the addr when no user is set.
+    pub fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{user}@{}", self.addr),
+            None => self.addr.clone(),
+        }
+    }
+
+    /// `[user@]addr:port` as `-J` takes it, with an IPv6 addr bracketed so
+    /// its colons aren't read as the port.
+    pub fn jump_spec(&self) -> String {
+        let addr = match self.addr.contains(':') && !self.addr.starts_with('[') {
+            true => format!("[{}]", self.addr),
+            false => self.addr.clone(),
+        };
+        match &self.user {
+            Some(user) => format!("{user}@{addr}:{}", self.port),
+            None => format!("{addr}:{}", self.port),
+        }
+    }
+
+    pub fn source(&self, setting: &str) -> Source {
+        self.sources
+            .iter()
+            .find(|(name, _)| *name == setting)
+            .map_or(Source::Builtin, |(_, source)| source.clone())
+    }
+}
+
+impl Config {
+    /// Fills in what `host` inherits. Lowest precedence first: built-in
+    /// defaults, `[defaults]`, templates whose patterns match the host's
+    /// name or addr (in config order), the template it names, then its own
+    /// settings.
+    pub fn resolve(&self, host: &HostProfile) -> ResolvedHost {
+        let mut layers = vec![(Source::Defaults, &self.defaults)];
+        for template in &self.templates {
+            let matched = template
+                .patterns
+                .iter()
+                .any(|pattern| wildcard_match(pattern, &host.name) || wildcard_match(pattern, &host.addr));
+            if matched && host.template.as_deref() != Some(template.name.as_str()) {
+                layers.push((Source::Template(template.name.clone()), &template.settings));
+            }
+        }
+        if let Some(template) = host.template.as_deref().and_then(|name| self.find_template(name)) {
+            layers.push((Source::Template(template.name.clone()), &template.settings));
+        }
+        let own = host.settings();
+        layers.push((Source::Host, &own));
+
+        let mut settings = HostSettings::default();
+        let mut sources = vec![("addr", Source::Host)];
+        for setting in ["user", "port", "identity", "jump", "tor", "keepalive"] {
+            let source = layers
+                .iter()
+                .rev()
+                .find(|(_, layer)| is_set(layer, setting))
+                .map_or(Source::Builtin, |(source, _)| source.clone());
+            sources.push((setting, source));
+        }
+        for (_, layer) in &layers {
+            settings.overlay(layer);
+        }
+
+        ResolvedHost {
+            name: host.name.clone(),
+            addr: host.addr.clone(),
+            user: settings.user,
+            port: settings.port.unwrap_or(DEFAULT_PORT),
+            identity: settings.identity,
+            jump: settings.jump.map(|jump| self.jump_target(&jump, &host.name)),
+            tor: settings.tor.unwrap_or(false),
+            keepalive: settings.keepalive,
+            tags: host.tags.clone(),
+            sources,
+        }
+    }
+
+    pub fn find_template(&self, name: &str) -> Option<&super::schema::HostTemplate> {
+        self.templates.iter().find(|template| template.name == name)
+    }
+
+    // a jump naming another configured host goes through that host's
+    // resolved user, addr and port; its own jump isn't followed
+    fn jump_target(&self, jump: &str, from: &str) -> String {
+        match self.hosts.iter().find(|host| host.name == jump && host.name != from) {
+            Some(host) => {
+                let mut via = self.resolve(&HostProfile {
+                    jump: None,
+                    ..host.clone()
+                });
+                via.jump = None;
+                via.jump_spec()
+            }
+            None => jump.to_string(),
+        }
+    }
+}
+
+fn is_set(settings: &HostSettings, setting: &str) -> bool {
+    match setting {
+        "user" => settings.user.is_some(),
+        "port" => settings.port.is_some(),
+        "identity" => settings.identity.is_some(),
+        "jump" => settings.jump.is_some(),
+        "tor" => settings.tor.is_some(),
+        "keepalive" => settings.keepalive.is_some(),
+        _ => false,
+    }
+}
+
+/// Shell-style match where `*` is any run of characters and `?` any one.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and how much text it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{wildcard_match, Source, DEFAULT_PORT};
+    use crate::config::Config;
+
+    #[test]
+    fn wildcards_match_whole_strings() {
+        assert!(wildcard_match("*.prod.example.com", "web.prod.example.com"));
+        assert!(!wildcard_match("*.prod.example.com", "web.staging.example.com"));
+        assert!(wildcard_match("10.0.?.*", "10.0.3.17"));
+        assert!(!wildcard_match("10.0.?.*", "10.0.12.17"));
+        assert!(wildcard_match("web-*-*", "web-eu-1"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("web", "web-1"));
+        assert!(!wildcard_match("?", ""));
+    }
+
+    #[test]
+    fn wildcards_backtrack() {
+        assert!(wildcard_match("*ab", "aab"));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("a*b*c", "aXbYbZ"));
+    }
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [defaults]
+            user = "ops"
+            keepalive = 30
+
+            [[templates]]
+            name = "prod"
+            match = ["*.prod"]
+            port = 2200
+            user = "deploy"
+
+            [[templates]]
+            name = "bastioned"
+            jump = "gate"
+            user = "admin"
+
+            [[hosts]]
+            name = "gate"
+            addr = "fe80::1"
+            user = "jumper"
+            port = 2222
+
+            [[hosts]]
+            name = "web.prod"
+            addr = "10.0.0.5"
+
+            [[hosts]]
+            name = "db.prod"
+            addr = "10.0.0.6"
+            template = "bastioned"
+            keepalive = 5
+
+            [[hosts]]
+            name = "plain"
+            addr = "example.com"
+            tor = true
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn resolve(config: &Config, name: &str) -> super::ResolvedHost {
+        let host = config.hosts.iter().find(|host| host.name == name).unwrap();
+        config.resolve(host)
+    }
+
+    #[test]
+    fn matching_templates_override_defaults() {
+        let host = resolve(&config(), "web.prod");
+        assert_eq!(host.user.as_deref(), Some("deploy"));
+        assert_eq!(host.port, 2200);
+        assert_eq!(host.keepalive, Some(30));
+        assert_eq!(host.source("user"), Source::Template("prod".to_string()));
+        assert_eq!(host.source("keepalive"), Source::Defaults);
+        assert_eq!(host.source("identity"), Source::Builtin);
+    }
+
+    #[test]
+    fn named_template_overrides_matching_ones() {
+        let host = resolve(&config(), "db.prod");
+        assert_eq!(host.user.as_deref(), Some("admin"));
+        assert_eq!(host.port, 2200);
+        assert_eq!(host.keepalive, Some(5));
+        assert_eq!(host.source("user"), Source::Template("bastioned".to_string()));
+        assert_eq!(host.source("port"), Source::Template("prod".to_string()));
+        assert_eq!(host.source("keepalive"), Source::Host);
+    }
+
+    #[test]
+    fn jumps_through_configured_hosts() {
+        let host = resolve(&config(), "db.prod");
+        assert_eq!(host.jump.as_deref(), Some("jumper@[fe80::1]:2222"));
+        assert_eq!(host.source("jump"), Source::Template("bastioned".to_string()));
+    }
+
+    #[test]
+    fn unset_settings_fall_back_to_builtins() {
+        let mut config = config();
+        config.defaults = Default::default();
+        let host = resolve(&config, "plain");
+        assert_eq!(host.user, None);
+        assert_eq!(host.port, DEFAULT_PORT);
+        assert!(host.tor);
+        assert_eq!(host.destination(), "example.com");
+        assert_eq!(host.source("port"), Source::Builtin);
+    }
+}
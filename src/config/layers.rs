@@ -0,0 +1,846 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    diagnostic::{
+        check_source, check_theme_source, key_conflict, prefix_problem, tor_with_jump, unknown_template, unknown_theme,
+        Diagnostic, Severity,
+    },
+    migrate::{migrate_source, Migrated, CURRENT_VERSION},
+    schema::expand_home,
+    Config,
+};
+use crate::{
+    keys::Keymap,
+    theme::{background::Brightness, ThemeName},
+};
+
+/// Machine-wide config, e.g. baked into mangOS images.
+pub const SYSTEM_CONFIG: &str = "/etc/mangsh/config.toml";
+// points the system layer somewhere else, mostly for testing images
+const SYSTEM_CONFIG_ENV: &str = "MANGSH_SYSTEM_CONFIG";
+// top-level settings that can be overridden from the environment, one
+// `MANGSH_<KEY>` variable each
+const THEME_ENV: &str = "MANGSH_THEME";
+const LIGHT_THEME_ENV: &str = "MANGSH_LIGHT_THEME";
+const DARK_THEME_ENV: &str = "MANGSH_DARK_THEME";
+const PREFIX_KEY_ENV: &str = "MANGSH_PREFIX_KEY";
+/// Dir next to the user file holding one custom theme per `.toml` file.
+pub const THEMES_DIR: &str = "themes";
+
+/// Where an effective config value came from.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Origin {
+    Default,
+    System(PathBuf),
+    Include(PathBuf),
+    Theme(PathBuf),
+    User(PathBuf),
+    Env(&'static str),
+}
+
+impl Origin {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Origin::Default => "default",
+            Origin::System(_) => "system",
+            Origin::Include(_) => "include",
+            Origin::Theme(_) => "theme",
+            Origin::User(_) => "user",
+            Origin::Env(_) => "env",
+        }
+    }
+
+    /// The file behind this origin, if it is one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Origin::System(path) | Origin::Include(path) | Origin::Theme(path) | Origin::User(path) => Some(path),
+            Origin::Default | Origin::Env(_) => None,
+        }
+    }
+
+    /// What diagnostics about a value from here point at: the file, or the
+    /// variable's name.
+    pub fn location(&self) -> &Path {
+        match self {
+            Origin::Env(var) => Path::new(var),
+            _ => self.path().unwrap_or(Path::new("")),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Origin::Default => "built-in default".to_string(),
+            Origin::System(path) | Origin::Include(path) | Origin::Theme(path) | Origin::User(path) => path.display().to_string(),
+            Origin::Env(var) => format!("${var}"),
+        }
+    }
+}
+
+/// One file (or the environment) that was consulted while loading.
+pub struct Layer {
+    pub origin: Origin,
+    pub found: bool,
+    pub hosts: usize,
+    pub groups: usize,
+}
+
+#[derive(Default)]
+struct Origins {
+    theme: Option<Origin>,
+    light_theme: Option<Origin>,
+    dark_theme: Option<Origin>,
+    prefix_key: Option<Origin>,
+    themes: HashMap<String, Origin>,
+    templates: HashMap<String, Origin>,
+    hosts: HashMap<String, Origin>,
+    groups: HashMap<String, Origin>,
+    // by (screen, key as written)
+    keys: HashMap<(String, String), Origin>,
+}
+
+/// The config as the stack of layers it was built from, lowest precedence
+/// first: the system file, included fragments, the user file, then the
+/// `MANGSH_<KEY>` overrides for the top-level settings. Only the user file
+/// is ever written back.
+pub struct Layers {
+    pub layers: Vec<Layer>,
+    /// Files that were upgraded from an older config version.
+    pub migrated: Vec<Migrated>,
+    // everything below the user file, merged
+    base: Config,
+    origins: Origins,
+    user: Config,
+    user_path: Option<PathBuf>,
+    // the environment overrides, as a config of their own
+    env: Config,
+}
+
+impl Layers {
+    /// Reads every layer. Files that fail to parse are left out and their
+    /// problems returned alongside. Old files are upgraded in memory only.
+    pub fn load(user_path: Option<&Path>) -> (Self, Vec<Diagnostic>) {
+        Self::load_with(user_path, |name| env::var(name).ok())
+    }
+
+    // `load` with the environment passed in
+    fn load_with(user_path: Option<&Path>, var: impl Fn(&str) -> Option<String>) -> (Self, Vec<Diagnostic>) {
+        let mut stack = Stack::default();
+
+        let system = var(SYSTEM_CONFIG_ENV).map_or_else(|| PathBuf::from(SYSTEM_CONFIG), PathBuf::from);
+        if let Some(config) = stack.read(&system, Origin::System(system.clone())) {
+            stack.merge(&config, Origin::System(system));
+        }
+        if let Some(dir) = user_path.and_then(Path::parent) {
+            stack.read_themes(&dir.join(THEMES_DIR));
+        }
+        let user = user_path
+            .and_then(|path| stack.read(path, Origin::User(path.to_path_buf())))
+            .unwrap_or_default();
+
+        let env = stack.read_env(var);
+
+        let layers = Self {
+            layers: stack.layers,
+            migrated: stack.migrated,
+            base: stack.base,
+            origins: stack.origins,
+            user,
+            user_path: user_path.map(Path::to_path_buf),
+            env,
+        };
+        let mut diagnostics = stack.diagnostics;
+        diagnostics.extend(layers.check_themes());
+        (layers, diagnostics)
+    }
+
+    // custom themes can't be picked when a built-in has their name, and a
+    // `theme` naming one nobody defined falls back to the default
+    fn check_themes(&self) -> Vec<Diagnostic> {
+        let effective = self.effective();
+        let mut diagnostics = effective
+            .themes
+            .iter()
+            .filter(|theme| !matches!(ThemeName::parse(&theme.name), ThemeName::Custom(_)))
+            .map(|theme| {
+                let origin = self.custom_theme_origin(&theme.name);
+                let message = format!("custom theme `{}` is hidden by the built-in of the same name", theme.name);
+                Diagnostic::new(Severity::Warning, origin.path().unwrap_or(Path::new("")), message)
+            })
+            .collect::<Vec<_>>();
+        diagnostics.extend(self.check_theme_names(&effective));
+        diagnostics.extend(self.check_keys(&effective));
+        diagnostics.extend(self.check_hosts(&effective));
+        diagnostics
+    }
+
+    fn check_hosts(&self, effective: &Config) -> Vec<Diagnostic> {
+        let templates = effective.templates.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        let mut diagnostics = Vec::new();
+        for host in &effective.hosts {
+            let origin = self.host_origin(&host.name);
+            let path = origin.path().unwrap_or(Path::new(""));
+            if let Some(name) = &host.template
+                && effective.find_template(name).is_none()
+            {
+                diagnostics.push(unknown_template(path, &host.name, name, &templates));
+            }
+            let resolved = effective.resolve(host);
+            if resolved.tor && resolved.jump.is_some() {
+                diagnostics.push(tor_with_jump(path, &host.name));
+            }
+        }
+        diagnostics
+    }
+
+    fn check_theme_names(&self, effective: &Config) -> Vec<Diagnostic> {
+        let known = effective.theme_names();
+        let known = known.iter().map(ThemeName::key).collect::<Vec<_>>();
+        let mut diagnostics = Vec::new();
+        if let Some(ThemeName::Custom(name)) = &effective.theme
+            && effective.find_theme(name).is_none()
+        {
+            let origin = self.theme_origin();
+            diagnostics.push(unknown_theme(origin.location(), "theme", name, &known, "the default"));
+        }
+        // `auto` can't stand in for itself
+        let known = known.iter().copied().filter(|key| *key != ThemeName::Auto.key()).collect::<Vec<_>>();
+        for brightness in [Brightness::Light, Brightness::Dark] {
+            let (key, configured) = match brightness {
+                Brightness::Light => ("light_theme", &effective.light_theme),
+                Brightness::Dark => ("dark_theme", &effective.dark_theme),
+            };
+            let name = match configured {
+                Some(ThemeName::Custom(name)) if effective.find_theme(name).is_none() => name.as_str(),
+                Some(ThemeName::Auto) => "auto",
+                _ => continue,
+            };
+            let origin = self.auto_theme_origin(brightness);
+            let fallback = format!("`{}`", effective.auto_theme(brightness).key());
+            diagnostics.push(unknown_theme(origin.location(), key, name, &known, &fallback));
+        }
+        diagnostics
+    }
+
+    fn check_keys(&self, effective: &Config) -> Vec<Diagnostic> {
+        Keymap::conflicts(&effective.keys, effective.prefix_key.as_deref())
+            .into_iter()
+            .map(|conflict| {
+                let origin = self.key_origin(conflict.scope.key(), &conflict.key);
+                key_conflict(origin.path().unwrap_or(Path::new("")), &conflict)
+            })
+            .collect()
+    }
+
+    /// The merged config the app runs with.
+    pub fn effective(&self) -> Config {
+        let mut config = self.base.clone();
+        overlay(&mut config, &self.user);
+        config.include = self.user.include.clone();
+        overlay(&mut config, &self.env);
+        config
+    }
+
+    /// What belongs in the user file for a given effective config: its own
+    /// entries plus anything added or changed, but not values that only
+    /// come from the layers around it.
+    pub fn user_view(&self, effective: &Config) -> Config {
+        let theme = match (&self.env.theme, &effective.theme) {
+            // still the environment's value, not something the user picked
+            (Some(env), Some(theme)) if env == theme => self.user.theme.clone(),
+            (_, theme) if *theme == self.base.theme && self.user.theme.is_none() => None,
+            (_, theme) => theme.clone(),
+        };
+        let hosts = effective
+            .hosts
+            .iter()
+            .filter(|host| {
+                self.user.hosts.iter().any(|own| own.name == host.name) || !self.base.hosts.contains(host)
+            })
+            .cloned()
+            .collect();
+        let groups = effective
+            .groups
+            .iter()
+            .filter(|group| {
+                self.user.groups.iter().any(|own| own.name == group.name) || !self.base.groups.contains(group)
+            })
+            .cloned()
+            .collect();
+
+        // defaults that are the same as the layers below would otherwise be
+        // copied up into the user file the first time anything is saved
+        let mut defaults = effective.defaults.clone();
+        let below = &self.base.defaults;
+        let own = &self.user.defaults;
+        drop_inherited(&mut defaults.user, &own.user, &below.user);
+        drop_inherited(&mut defaults.port, &own.port, &below.port);
+        drop_inherited(&mut defaults.identity, &own.identity, &below.identity);
+        drop_inherited(&mut defaults.jump, &own.jump, &below.jump);
+        drop_inherited(&mut defaults.tor, &own.tor, &below.tor);
+        drop_inherited(&mut defaults.keepalive, &own.keepalive, &below.keepalive);
+        let themes = effective
+            .themes
+            .iter()
+            .filter(|theme| {
+                self.user.themes.iter().any(|own| own.name == theme.name) || !self.base.themes.contains(theme)
+            })
+            .cloned()
+            .collect();
+        let templates = effective
+            .templates
+            .iter()
+            .filter(|template| {
+                self.user.templates.iter().any(|own| own.name == template.name)
+                    || !self.base.templates.contains(template)
+            })
+            .cloned()
+            .collect();
+
+        Config {
+            theme,
+            // nothing in the app changes these, so they're whatever the
+            // user file says, never the environment's overrides
+            light_theme: self.user.light_theme.clone(),
+            dark_theme: self.user.dark_theme.clone(),
+            prefix_key: self.user.prefix_key.clone(),
+            keys: self.user.keys.clone(),
+            include: self.user.include.clone(),
+            themes,
+            defaults,
+            templates,
+            hosts,
+            groups,
+            ..Config::default()
+        }
+    }
+
+    /// Records what was just written to the user file for `effective`, which
+    /// also upgraded it if it was outdated. Once
+    /// the theme has been changed away from `MANGSH_THEME`, the variable no
+    /// longer decides it for this run.
+    pub fn set_user(&mut self, user: Config, effective: &Config) {
+        // saving wrote the user file in the current version
+        self.migrated.retain(|migrated| Some(&migrated.path) != self.user_path.as_ref());
+        self.user = user;
+        if self.env.theme != effective.theme {
+            self.env.theme = None;
+        }
+    }
+
+    /// Every file consulted, found or not, so edits and new files are noticed.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.layers
+            .iter()
+            .filter_map(|layer| layer.origin.path().map(Path::to_path_buf))
+            .collect()
+    }
+
+    pub fn theme_origin(&self) -> Origin {
+        let env = self.env.theme.is_some().then_some(THEME_ENV);
+        self.scalar_origin(env, self.user.theme.is_some(), &self.origins.theme)
+    }
+
+    /// Where `light_theme` or `dark_theme` was set.
+    pub fn auto_theme_origin(&self, brightness: Brightness) -> Origin {
+        let (env, own, below) = match brightness {
+            Brightness::Light => {
+                let env = self.env.light_theme.is_some().then_some(LIGHT_THEME_ENV);
+                (env, &self.user.light_theme, &self.origins.light_theme)
+            }
+            Brightness::Dark => {
+                let env = self.env.dark_theme.is_some().then_some(DARK_THEME_ENV);
+                (env, &self.user.dark_theme, &self.origins.dark_theme)
+            }
+        };
+        self.scalar_origin(env, own.is_some(), below)
+    }
+
+    pub fn prefix_origin(&self) -> Origin {
+        let env = self.env.prefix_key.is_some().then_some(PREFIX_KEY_ENV);
+        self.scalar_origin(env, self.user.prefix_key.is_some(), &self.origins.prefix_key)
+    }
+
+    // the environment beats the user file, which beats the layers below
+    fn scalar_origin(&self, env: Option<&'static str>, own: bool, below: &Option<Origin>) -> Origin {
+        match (env, own, below) {
+            (Some(var), _, _) => Origin::Env(var),
+            (None, true, _) => self.user_origin(),
+            (None, false, Some(origin)) => origin.clone(),
+            (None, false, None) => Origin::Default,
+        }
+    }
+
+    /// Where a `[keys.<scope>]` entry was set.
+    pub fn key_origin(&self, scope: &str, key: &str) -> Origin {
+        if self.user.keys.get(scope).is_some_and(|table| table.contains_key(key)) {
+            return self.user_origin();
+        }
+        let entry = (scope.to_string(), key.to_string());
+        self.origins.keys.get(&entry).cloned().unwrap_or_else(|| self.user_origin())
+    }
+
+    pub fn host_origin(&self, name: &str) -> Origin {
+        if self.user.hosts.iter().any(|host| host.name == name) {
+            return self.user_origin();
+        }
+        self.origins.hosts.get(name).cloned().unwrap_or_else(|| self.user_origin())
+    }
+
+    pub fn custom_theme_origin(&self, name: &str) -> Origin {
+        if self.user.themes.iter().any(|theme| theme.name == name) {
+            return self.user_origin();
+        }
+        self.origins.themes.get(name).cloned().unwrap_or_else(|| self.user_origin())
+    }
+
+    /// The config file whose `[[themes]]` defines `name`, if one does; a
+    /// file in the `themes/` dir can't stand in for those.
+    pub fn inline_theme(&self, name: &str) -> Option<Origin> {
+        let defined = self.user.themes.iter().chain(&self.base.themes).any(|theme| theme.name == name);
+        let origin = self.custom_theme_origin(name);
+        (defined && !matches!(origin, Origin::Theme(_))).then_some(origin)
+    }
+
+    pub fn template_origin(&self, name: &str) -> Origin {
+        if self.user.templates.iter().any(|template| template.name == name) {
+            return self.user_origin();
+        }
+        self.origins.templates.get(name).cloned().unwrap_or_else(|| self.user_origin())
+    }
+
+    pub fn group_origin(&self, name: &str) -> Origin {
+        if self.user.groups.iter().any(|group| group.name == name) {
+            return self.user_origin();
+        }
+        self.origins.groups.get(name).cloned().unwrap_or_else(|| self.user_origin())
+    }
+
+    /// The layer below the user file that defines `name`, if any. Such a
+    /// host can be overridden from the user file but not removed from it.
+    pub fn inherited_host(&self, name: &str) -> Option<&Origin> {
+        self.origins.hosts.get(name)
+    }
+
+    /// Whether the user file has its own entry for `name`.
+    pub fn user_has_host(&self, name: &str) -> bool {
+        self.user.hosts.iter().any(|host| host.name == name)
+    }
+
+    fn user_origin(&self) -> Origin {
+        self.user_path.clone().map_or(Origin::Default, Origin::User)
+    }
+}
+
+// clears a `[defaults]` value the user file doesn't set itself and that only
+// repeats the layers below
+fn drop_inherited<T: PartialEq>(value: &mut Option<T>, own: &Option<T>, below: &Option<T>) {
+    if own.is_none() && value == below {
+        *value = None;
+    }
+}
+
+#[derive(Default)]
+struct Stack {
+    layers: Vec<Layer>,
+    base: Config,
+    origins: Origins,
+    diagnostics: Vec<Diagnostic>,
+    visited: HashSet<PathBuf>,
+    migrated: Vec<Migrated>,
+}
+
+impl Stack {
+    // reads one file, merging its includes into the base first so the file
+    // itself takes precedence over them
+    fn read(&mut self, path: &Path, origin: Origin) -> Option<Config> {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if !self.visited.insert(canonical) {
+            let message = format!("{} is included more than once; skipping it", path.display());
+            self.diagnostics.push(Diagnostic::new(Severity::Warning, path, message));
+            return None;
+        }
+
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    let message = format!("unable to read config: {err}");
+                    self.diagnostics.push(Diagnostic::new(Severity::Error, path, message));
+                } else if let Origin::Include(_) = origin {
+                    self.diagnostics.push(Diagnostic::new(Severity::Warning, path, "included file not found"));
+                }
+                self.layers.push(Layer { origin, found: false, hosts: 0, groups: 0 });
+                return None;
+            }
+        };
+
+        let source = match self.migrate(path, &origin, source) {
+            Ok(source) => source,
+            Err(err) => {
+                self.diagnostics.push(Diagnostic::new(Severity::Error, path, format!("{err:#}")));
+                self.layers.push(Layer { origin, found: true, hosts: 0, groups: 0 });
+                return None;
+            }
+        };
+
+        let (config, diagnostics) = check_source(path, &source);
+        self.diagnostics.extend(diagnostics);
+        let Some(config) = config else {
+            self.layers.push(Layer { origin, found: true, hosts: 0, groups: 0 });
+            return None;
+        };
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for include in &config.include {
+            let include = dir.join(expand_home(include));
+            if let Some(fragment) = self.read(&include, Origin::Include(include.clone())) {
+                self.merge(&fragment, Origin::Include(include));
+            }
+        }
+
+        self.layers.push(Layer {
+            origin,
+            found: true,
+            hosts: config.hosts.len(),
+            groups: config.groups.len(),
+        });
+        Some(config)
+    }
+
+    // upgrades an old file in memory; the user's own file is rewritten by
+    // the next save, the others are left for whoever manages them
+    fn migrate(&mut self, path: &Path, origin: &Origin, source: String) -> anyhow::Result<String> {
+        let (upgraded, from, changes) = migrate_source(&source)?;
+        if from == CURRENT_VERSION {
+            return Ok(source);
+        }
+        // a bare version bump is only worth mentioning for the file it will
+        // be written into
+        if changes.is_empty() && !matches!(origin, Origin::User(_)) {
+            return Ok(upgraded);
+        }
+        self.migrated.push(Migrated {
+            path: path.to_path_buf(),
+            from,
+            changes,
+        });
+        Ok(upgraded)
+    }
+
+    // one custom theme per file, named after the file unless it says
+    // otherwise; a missing dir is fine
+    fn read_themes(&mut self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        for path in paths {
+            let origin = Origin::Theme(path.clone());
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(err) => {
+                    let message = format!("unable to read theme: {err}");
+                    self.diagnostics.push(Diagnostic::new(Severity::Error, &path, message));
+                    self.layers.push(Layer { origin, found: false, hosts: 0, groups: 0 });
+                    continue;
+                }
+            };
+            let (theme, diagnostics) = check_theme_source(&path, &source);
+            self.diagnostics.extend(diagnostics);
+            self.layers.push(Layer { origin: origin.clone(), found: true, hosts: 0, groups: 0 });
+            if let Some(mut theme) = theme {
+                if theme.name.is_empty() {
+                    theme.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                }
+                let config = Config { themes: vec![theme], ..Config::default() };
+                self.merge(&config, origin);
+            }
+        }
+    }
+
+    // the `MANGSH_<KEY>` overrides that are set, each a layer of its own
+    fn read_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Config {
+        let mut read = |name: &'static str| {
+            let value = var(name)?;
+            self.layers.push(Layer { origin: Origin::Env(name), found: true, hosts: 0, groups: 0 });
+            Some(value)
+        };
+        let env = Config {
+            theme: read(THEME_ENV).map(|value| ThemeName::parse(&value)),
+            light_theme: read(LIGHT_THEME_ENV).map(|value| ThemeName::parse(&value)),
+            dark_theme: read(DARK_THEME_ENV).map(|value| ThemeName::parse(&value)),
+            prefix_key: read(PREFIX_KEY_ENV),
+            ..Config::default()
+        };
+        if let Some(key) = &env.prefix_key {
+            self.diagnostics.extend(prefix_problem(Path::new(PREFIX_KEY_ENV), key));
+        }
+        env
+    }
+
+    fn merge(&mut self, config: &Config, origin: Origin) {
+        overlay(&mut self.base, config);
+        if config.theme.is_some() {
+            self.origins.theme = Some(origin.clone());
+        }
+        if config.light_theme.is_some() {
+            self.origins.light_theme = Some(origin.clone());
+        }
+        if config.dark_theme.is_some() {
+            self.origins.dark_theme = Some(origin.clone());
+        }
+        if config.prefix_key.is_some() {
+            self.origins.prefix_key = Some(origin.clone());
+        }
+        for theme in &config.themes {
+            self.origins.themes.insert(theme.name.clone(), origin.clone());
+        }
+        for template in &config.templates {
+            self.origins.templates.insert(template.name.clone(), origin.clone());
+        }
+        for host in &config.hosts {
+            self.origins.hosts.insert(host.name.clone(), origin.clone());
+        }
+        for group in &config.groups {
+            self.origins.groups.insert(group.name.clone(), origin.clone());
+        }
+        for (scope, table) in &config.keys {
+            for key in table.keys() {
+                self.origins.keys.insert((scope.clone(), key.clone()), origin.clone());
+            }
+        }
+    }
+}
+
+// applies a higher layer: scalars it sets win, `[defaults]` is merged key by
+// key, templates, hosts and groups replace same-named entries in place and
+// new ones are appended; `[keys]` entries are merged per screen, key by key
+fn overlay(into: &mut Config, layer: &Config) {
+    if layer.theme.is_some() {
+        into.theme.clone_from(&layer.theme);
+    }
+    if layer.light_theme.is_some() {
+        into.light_theme.clone_from(&layer.light_theme);
+    }
+    if layer.dark_theme.is_some() {
+        into.dark_theme.clone_from(&layer.dark_theme);
+    }
+    if layer.prefix_key.is_some() {
+        into.prefix_key.clone_from(&layer.prefix_key);
+    }
+    for theme in &layer.themes {
+        match into.themes.iter().position(|t| t.name == theme.name) {
+            Some(index) => into.themes[index] = theme.clone(),
+            None => into.themes.push(theme.clone()),
+        }
+    }
+    into.defaults.overlay(&layer.defaults);
+    for template in &layer.templates {
+        match into.templates.iter().position(|t| t.name == template.name) {
+            Some(index) => into.templates[index] = template.clone(),
+            None => into.templates.push(template.clone()),
+        }
+    }
+    for host in &layer.hosts {
+        match into.host_index(&host.name) {
+            Some(index) => into.hosts[index] = host.clone(),
+            None => into.hosts.push(host.clone()),
+        }
+    }
+    for group in &layer.groups {
+        match into.groups.iter().position(|g| g.name == group.name) {
+            Some(index) => into.groups[index] = group.clone(),
+            None => into.groups.push(group.clone()),
+        }
+    }
+    for (scope, table) in &layer.keys {
+        into.keys.entry(scope.clone()).or_default().extend(table.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layers, Origin, DARK_THEME_ENV, LIGHT_THEME_ENV, PREFIX_KEY_ENV, SYSTEM_CONFIG_ENV, THEME_ENV};
+    use crate::{
+        config::{diagnostic::Diagnostic, temp_dir::TempDir, Config},
+        theme::{background::Brightness, Theme, ThemeName},
+    };
+
+    const SYSTEM: &str = r#"
+version = 1
+theme = "night"
+dark_theme = "matrix"
+prefix_key = "ctrl-b"
+
+[[hosts]]
+name = "shared"
+addr = "10.0.0.1"
+
+[[hosts]]
+name = "base"
+addr = "10.0.0.2"
+"#;
+
+    const WORK: &str = r#"
+version = 1
+theme = "forest"
+
+[[hosts]]
+name = "shared"
+addr = "10.0.0.3"
+
+[[hosts]]
+name = "work"
+addr = "10.0.0.5"
+"#;
+
+    const USER: &str = r#"
+version = 1
+include = ["work.toml"]
+light_theme = "light"
+
+[[hosts]]
+name = "shared"
+addr = "10.0.0.4"
+
+[[hosts]]
+name = "mine"
+addr = "10.0.0.6"
+"#;
+
+    // the three files in `dir`, with the system file pointed at by the
+    // environment and `vars` set on top
+    fn load(dir: &TempDir, vars: &[(&str, &str)]) -> (Layers, Vec<Diagnostic>) {
+        dir.file("system.toml", SYSTEM);
+        dir.file("work.toml", WORK);
+        let user = dir.file("user.toml", USER);
+        let system = dir.0.join("system.toml").display().to_string();
+        Layers::load_with(Some(&user), |name| match name {
+            SYSTEM_CONFIG_ENV => Some(system.clone()),
+            _ => vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string()),
+        })
+    }
+
+    fn addr<'a>(config: &'a Config, name: &str) -> &'a str {
+        &config.hosts.iter().find(|host| host.name == name).unwrap().addr
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let dir = TempDir::new();
+        let (layers, diagnostics) = load(&dir, &[]);
+        assert!(diagnostics.is_empty());
+        let effective = layers.effective();
+
+        // the include sits between the system and user files
+        assert_eq!(effective.theme, Some(ThemeName::Builtin(Theme::Forest)));
+        assert_eq!(layers.theme_origin(), Origin::Include(dir.0.join("work.toml")));
+        assert_eq!(effective.dark_theme, Some(ThemeName::Builtin(Theme::Matrix)));
+        assert_eq!(layers.auto_theme_origin(Brightness::Dark), Origin::System(dir.0.join("system.toml")));
+        assert_eq!(layers.auto_theme_origin(Brightness::Light), Origin::User(dir.0.join("user.toml")));
+        assert_eq!(layers.prefix_origin(), Origin::System(dir.0.join("system.toml")));
+
+        // hosts merge by name, the highest layer's entry winning
+        assert_eq!(addr(&effective, "shared"), "10.0.0.4");
+        assert_eq!(addr(&effective, "base"), "10.0.0.2");
+        assert_eq!(addr(&effective, "work"), "10.0.0.5");
+        assert_eq!(layers.host_origin("shared"), Origin::User(dir.0.join("user.toml")));
+        assert_eq!(layers.host_origin("work"), Origin::Include(dir.0.join("work.toml")));
+        assert_eq!(layers.host_origin("base"), Origin::System(dir.0.join("system.toml")));
+    }
+
+    #[test]
+    fn environment_beats_every_file() {
+        let dir = TempDir::new();
+        let vars = [
+            (THEME_ENV, "mango"),
+            (LIGHT_THEME_ENV, "gruvbox-light"),
+            (DARK_THEME_ENV, "night"),
+            (PREFIX_KEY_ENV, "ctrl-g"),
+        ];
+        let (layers, diagnostics) = load(&dir, &vars);
+        assert!(diagnostics.is_empty());
+        let effective = layers.effective();
+
+        assert_eq!(effective.theme, Some(ThemeName::Builtin(Theme::Mango)));
+        assert_eq!(effective.light_theme, Some(ThemeName::Builtin(Theme::GruvboxLight)));
+        assert_eq!(effective.dark_theme, Some(ThemeName::Builtin(Theme::Night)));
+        assert_eq!(effective.prefix_key.as_deref(), Some("ctrl-g"));
+        assert_eq!(layers.theme_origin(), Origin::Env(THEME_ENV));
+        assert_eq!(layers.auto_theme_origin(Brightness::Light), Origin::Env(LIGHT_THEME_ENV));
+        assert_eq!(layers.auto_theme_origin(Brightness::Dark), Origin::Env(DARK_THEME_ENV));
+        assert_eq!(layers.prefix_origin(), Origin::Env(PREFIX_KEY_ENV));
+
+        // each variable is listed as a layer, above the files
+        let env = layers.layers.iter().skip_while(|layer| layer.origin.path().is_some());
+        assert_eq!(env.count(), vars.len());
+    }
+
+    #[test]
+    fn bad_environment_values_are_reported_by_name() {
+        let dir = TempDir::new();
+        let (_, diagnostics) = load(&dir, &[(THEME_ENV, "nope"), (PREFIX_KEY_ENV, "x")]);
+        let locations = diagnostics.iter().map(|diagnostic| diagnostic.path.clone()).collect::<Vec<_>>();
+        assert!(locations.iter().any(|path| path.as_os_str() == THEME_ENV));
+        assert!(locations.iter().any(|path| path.as_os_str() == PREFIX_KEY_ENV));
+    }
+
+    #[test]
+    fn user_view_keeps_only_the_users_own_values() {
+        let dir = TempDir::new();
+        let (layers, _) = load(&dir, &[(THEME_ENV, "mango"), (PREFIX_KEY_ENV, "ctrl-g")]);
+        let view = layers.user_view(&layers.effective());
+
+        // neither the environment nor the layers below leak into the file
+        assert_eq!(view.theme, None);
+        assert_eq!(view.prefix_key, None);
+        assert_eq!(view.dark_theme, None);
+        assert_eq!(view.light_theme, Some(ThemeName::Builtin(Theme::Light)));
+        let hosts = view.hosts.iter().map(|host| host.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(hosts, ["shared", "mine"]);
+        assert_eq!(view.include, ["work.toml"]);
+    }
+
+    #[test]
+    fn user_view_picks_up_changes_made_in_the_app() {
+        let dir = TempDir::new();
+        let (layers, _) = load(&dir, &[]);
+        let mut effective = layers.effective();
+        effective.theme = Some(ThemeName::Builtin(Theme::Catppuccin));
+        let base = effective.hosts.iter_mut().find(|host| host.name == "base").unwrap();
+        base.addr = "10.0.0.9".to_string();
+
+        let view = layers.user_view(&effective);
+        assert_eq!(view.theme, Some(ThemeName::Builtin(Theme::Catppuccin)));
+        // an edited inherited host is copied up as an override
+        let hosts = view.hosts.iter().map(|host| host.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(hosts, ["shared", "base", "mine"]);
+    }
+
+    #[test]
+    fn picking_a_theme_takes_over_from_the_environment() {
+        let dir = TempDir::new();
+        let (mut layers, _) = load(&dir, &[(THEME_ENV, "mango")]);
+        let mut effective = layers.effective();
+
+        // saving with the environment's theme still in place keeps it
+        let view = layers.user_view(&effective);
+        layers.set_user(view, &effective);
+        assert_eq!(layers.theme_origin(), Origin::Env(THEME_ENV));
+
+        effective.theme = Some(ThemeName::Builtin(Theme::Dark));
+        let view = layers.user_view(&effective);
+        layers.set_user(view, &effective);
+        assert_eq!(layers.theme_origin(), Origin::User(dir.0.join("user.toml")));
+        assert_eq!(layers.effective().theme, Some(ThemeName::Builtin(Theme::Dark)));
+    }
+}
@@ -1,64 +1,413 @@
-use std::{fs, path::PathBuf};
+use std::{fs, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
-use super::Config;
+use toml_edit::DocumentMut;
+
+use super::{
+    diagnostic::{Diagnostic, Severity},
+    edit,
+    layers::{Layers, Origin, THEMES_DIR},
+    migrate::{migrate_source, Migrated, CURRENT_VERSION},
+    Config,
+};
+use crate::theme::CustomTheme;
 use std::fs::File;
 use std::io::Write;
 
+/// How many backups of the config file are kept.
+pub const BACKUP_COUNT: usize = 5;
+
+// saves closer together than this are one burst; a burst keeps the backup
+// from before it and one from before its latest save, so cycling themes with
+// ←/→ doesn't push every older backup out but the last good state survives
+const BACKUP_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct Backup {
+    pub path: PathBuf,
+    pub saved_at: SystemTime,
+}
+
 pub struct LoadedConfig {
     pub config: Config,
     pub path: Option<PathBuf>,
     pub error: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub layers: Layers,
+}
+
+impl LoadedConfig {
+    /// True when some layer couldn't be used.
+    pub fn failed(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
 }
 
 pub fn default_config_path() -> Option<PathBuf> {
     dirs::config_dir().map(|dir| dir.join("mangsh").join("config.toml"))
 }
 
-pub fn load_config() -> LoadedConfig {
-    let path = default_config_path();
-    let mut loaded = LoadedConfig {
-        config: Config::default(),
-        path: path.clone(),
-        error: None,
+/// Loads every config layer with the user file at `path`, leaving out
+/// layers that are broken (with an error message) and falling back to
+/// defaults when there is nothing to load. Nothing is written: an outdated
+/// user file is upgraded in memory, and on disk by the next `save_config`.
+pub fn load_config(path: Option<PathBuf>) -> LoadedConfig {
+    let (layers, diagnostics) = Layers::load(path.as_deref());
+    let user_found = layers
+        .layers
+        .iter()
+        .any(|layer| matches!(layer.origin, Origin::User(_)) && layer.found);
+
+    let error = match &path {
+        None => Some("No config directory available; using defaults.".to_string()),
+        Some(_) if diagnostics.is_empty() && !user_found => Some("Config not found; using defaults.".to_string()),
+        Some(_) => summarize(&diagnostics).or_else(|| layers.migrated.first().map(Migrated::summary)),
     };
 
-    if let Some(path) = path {
-        match fs::read_to_string(&path) {
-            Ok(contents) => match parse_config(&contents) {
-                Ok(cfg) => loaded.config = cfg,
-                Err(err) => loaded.error = Some(format!("Unable to parse config: {err}")),
-            },
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-                loaded.error = Some("Config not found; using defaults.".to_string());
-            }
-            Err(err) => {
-                loaded.error = Some(format!("Unable to read config: {err}"));
-            }
-        }
-    } else {
-        loaded.error = Some("No config directory available; using defaults.".to_string());
+    LoadedConfig {
+        config: layers.effective(),
+        path,
+        error,
+        diagnostics,
+        layers,
     }
+}
 
-    loaded
+/// One-line status for a set of diagnostics, e.g. for the Config screen.
+pub fn summarize(diagnostics: &[Diagnostic]) -> Option<String> {
+    let first = diagnostics.first()?;
+    let count = diagnostics.len();
+    let more = match count {
+        1 => String::new(),
+        n => format!(" (+{} more)", n - 1),
+    };
+    if let Some(error) = diagnostics.iter().find(|d| d.severity == Severity::Error) {
+        return Some(format!("Unable to load config: {error}{more}"));
+    }
+    Some(format!("Config loaded with {count} warning{}: {first}", if count == 1 { "" } else { "s" }))
+}
+
+/// `load_config` for the command line: any broken layer is an error. The
+/// warnings, and a note for each file read as an older version, are handed
+/// back for the caller to show.
+pub fn read_layered(path: Option<&Path>) -> Result<(Config, Vec<Diagnostic>)> {
+    let loaded = load_config(path.map(Path::to_path_buf));
+    if let Some(error) = loaded.diagnostics.iter().find(|d| d.severity == Severity::Error) {
+        bail!("{error}");
+    }
+    let mut warnings = loaded.diagnostics;
+    warnings.extend(loaded.layers.migrated.iter().map(Migrated::diagnostic));
+    Ok((loaded.config, warnings))
+}
+
+/// Strict variant of `load_config`: any read or parse failure is an error.
+/// A missing file is only accepted when `allow_missing` is set.
+pub fn read_config(path: &Path, allow_missing: bool) -> Result<Config> {
+    match fs::read_to_string(path) {
+        Ok(contents) => parse_config(&contents)
+            .with_context(|| format!("Unable to parse {}", path.display())),
+        Err(err) if allow_missing && err.kind() == std::io::ErrorKind::NotFound => {
+            Ok(Config::default())
+        }
+        Err(err) => Err(err).with_context(|| format!("Unable to read {}", path.display())),
+    }
 }
 
 fn parse_config(contents: &str) -> Result<Config> {
-    let cfg: Config = toml::from_str(contents)?;
+    let (contents, _, _) = migrate_source(contents)?;
+    let cfg: Config = toml::from_str(&contents)?;
     Ok(cfg)
 }
 
-pub fn save_config(config: &Config) -> Result<Option<PathBuf>> {
-    if let Some(path) = default_config_path() {
+/// Writes `config` to `path`. An existing file is edited in place so the
+/// user's comments and layout survive; only changed keys are rewritten. A
+/// file from an older version is upgraded first, with its original backed
+/// up whatever the backup interval says.
+pub fn save_config(config: &Config, path: Option<&Path>) -> Result<Option<PathBuf>> {
+    if let Some(path) = path {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
-        let mut file = File::create(&path)?;
-        let serialized = toml::to_string_pretty(config)?;
-        file.write_all(serialized.as_bytes())?;
-        Ok(Some(path))
+        let (contents, outdated) = match fs::read_to_string(path) {
+            Ok(contents) => {
+                let (upgraded, from, _) = migrate_source(&contents)
+                    .with_context(|| format!("Unable to upgrade {}", path.display()))?;
+                (upgraded, from < CURRENT_VERSION)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (String::new(), false),
+            Err(err) => return Err(err.into()),
+        };
+        // diffed against the upgraded text, so only real changes are written
+        let mut doc = contents.parse::<DocumentMut>().with_context(|| {
+            format!("{} has syntax errors; fix them before saving", path.display())
+        })?;
+        edit::merge_into(&mut doc, config)?;
+        backup_config(path, outdated)?;
+        write_atomic(path, doc.to_string().as_bytes())?;
+        Ok(Some(path.to_path_buf()))
     } else {
         Ok(None)
     }
 }
+
+/// Where a custom theme called `name` lives, in the `themes/` dir next to
+/// the config at `config_path`.
+pub fn theme_path(config_path: &Path, name: &str) -> PathBuf {
+    config_path.with_file_name(THEMES_DIR).join(format!("{name}.toml"))
+}
+
+/// Writes a custom theme to its `theme_path`, replacing any file there.
+pub fn save_theme(config_path: &Path, theme: &CustomTheme) -> Result<PathBuf> {
+    let path = theme_path(config_path, &theme.name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(&path, toml::to_string(theme)?.as_bytes())?;
+    Ok(path)
+}
+
+// writes next to the target and renames over it, so a crash mid-write never
+// leaves a truncated config behind. A symlinked config (dotfile managers)
+// has its target replaced rather than the link, and keeps its permissions
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "config.toml".to_string());
+    let tmp = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    let permissions = fs::metadata(&path).ok().map(|meta| meta.permissions());
+
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        // before any contents go in, so a 0600 file is never readable
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        // the rename itself only lasts once the directory is synced
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        File::open(dir)?.sync_all()?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.with_file_name("backups")
+}
+
+/// Backups of the config at `path`, newest first.
+pub fn list_backups(path: &Path) -> Vec<Backup> {
+    let Some(file_name) = path.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(backup_dir(path)) else {
+        return Vec::new();
+    };
+
+    let mut backups = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let millis = name
+                .strip_prefix(&format!("{file_name}."))?
+                .strip_suffix(".bak")?
+                .parse::<u64>()
+                .ok()?;
+            Some(Backup {
+                path: entry.path(),
+                saved_at: UNIX_EPOCH + Duration::from_millis(millis),
+            })
+        })
+        .collect::<Vec<_>>();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.saved_at));
+    backups
+}
+
+// copies the current file into the backup dir and prunes old copies,
+// returning the new backup; `force` skips the interval check
+fn backup_config(path: &Path, force: bool) -> Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let backups = list_backups(path);
+    // nothing changed since the last backup, which still covers it
+    if let Some(latest) = backups.first()
+        && fs::read(path).ok() == fs::read(&latest.path).ok()
+    {
+        return Ok(Some(latest.path.clone()));
+    }
+    // one stamped ahead of the clock (see below) is as recent as it gets
+    let recent = |backup: &Backup| {
+        !SystemTime::now()
+            .duration_since(backup.saved_at)
+            .is_ok_and(|age| age >= BACKUP_INTERVAL)
+    };
+    // mid-burst: the newest backup was from before the previous save, which
+    // this one replaces
+    if !force && backups.len() >= 2 && recent(&backups[0]) && recent(&backups[1]) {
+        let _ = fs::remove_file(&backups[0].path);
+    }
+
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    // newer than every backup there, even for saves within one millisecond
+    let after_newest = list_backups(path)
+        .first()
+        .and_then(|newest| newest.saved_at.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |at| at.as_millis() + 1);
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis().max(after_newest);
+    let backup = dir.join(format!("{file_name}.{millis}.bak"));
+    fs::copy(path, &backup).context("Unable to back up config")?;
+
+    for old in list_backups(path).iter().skip(BACKUP_COUNT) {
+        let _ = fs::remove_file(&old.path);
+    }
+    Ok(Some(backup))
+}
+
+/// Puts a backup back in place of the config. The current file is backed
+/// up first, so a restore can itself be undone.
+pub fn restore_backup(path: &Path, backup: &Backup) -> Result<()> {
+    let contents = fs::read(&backup.path)
+        .with_context(|| format!("Unable to read {}", backup.path.display()))?;
+    backup_config(path, true)?;
+    write_atomic(path, &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::{backup_config, list_backups, restore_backup, write_atomic, BACKUP_COUNT};
+    use crate::config::temp_dir::TempDir;
+
+    fn contents(backups: &[super::Backup]) -> Vec<String> {
+        backups.iter().map(|backup| fs::read_to_string(&backup.path).unwrap()).collect()
+    }
+
+    fn leftovers(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect()
+    }
+
+    #[test]
+    fn write_replaces_the_file_without_leftovers() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "old");
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(leftovers(&dir.0).is_empty());
+    }
+
+    #[test]
+    fn failed_write_leaves_the_original_alone() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "old");
+        // the temp file can't be created where a directory is in the way
+        let tmp = dir.0.join(format!(".config.toml.{}.tmp", std::process::id()));
+        fs::create_dir(&tmp).unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+    }
+
+    #[test]
+    fn failed_rename_cleans_up_the_temp_file() {
+        let dir = TempDir::new();
+        // a non-empty directory can't be renamed over
+        let path = dir.0.join("config.toml");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), "").unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert!(path.join("keep").exists());
+        assert!(leftovers(&dir.0).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_goes_through_symlinks() {
+        let dir = TempDir::new();
+        fs::create_dir(dir.0.join("dotfiles")).unwrap();
+        let target = dir.file("dotfiles/config.toml", "old");
+        let link = dir.0.join("config.toml");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_keeps_the_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "old");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn backups_are_pruned_to_the_newest() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "");
+        for n in 0..BACKUP_COUNT + 3 {
+            fs::write(&path, n.to_string()).unwrap();
+            backup_config(&path, true).unwrap();
+        }
+        let backups = list_backups(&path);
+        assert_eq!(backups.len(), BACKUP_COUNT);
+        assert_eq!(contents(&backups), ["7", "6", "5", "4", "3"]);
+    }
+
+    #[test]
+    fn a_burst_keeps_its_first_and_latest_backup() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "");
+        for theme in ["mango", "forest", "night", "nord"] {
+            fs::write(&path, theme).unwrap();
+            backup_config(&path, false).unwrap();
+        }
+        assert_eq!(contents(&list_backups(&path)), ["nord", "mango"]);
+
+        // forced backups (migrations, restores) are never folded in
+        fs::write(&path, "dawn").unwrap();
+        backup_config(&path, true).unwrap();
+        assert_eq!(contents(&list_backups(&path)), ["dawn", "nord", "mango"]);
+    }
+
+    #[test]
+    fn unchanged_file_reuses_the_latest_backup() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "same");
+        let first = backup_config(&path, true).unwrap();
+        assert_eq!(backup_config(&path, true).unwrap(), first);
+        assert_eq!(list_backups(&path).len(), 1);
+        assert_eq!(backup_config(&dir.0.join("missing.toml"), true).unwrap(), None);
+    }
+
+    #[test]
+    fn restore_can_be_undone() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "good");
+        backup_config(&path, true).unwrap();
+        fs::write(&path, "broken").unwrap();
+
+        let good = list_backups(&path).remove(0);
+        restore_backup(&path, &good).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "good");
+        assert_eq!(contents(&list_backups(&path)), ["broken", "good"]);
+    }
+}
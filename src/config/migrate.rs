@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+use toml_edit::{value, DocumentMut, Item, Value};
+
+use super::diagnostic::{Diagnostic, Severity};
+use crate::theme::Theme;
+
+/// The config format this build reads and writes.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// A config file that was brought up to the current version.
+pub struct Migrated {
+    pub path: PathBuf,
+    pub from: u32,
+    pub changes: Vec<String>,
+}
+
+impl Migrated {
+    pub fn summary(&self) -> String {
+        format!("{} is {}", self.path.display(), self.detail())
+    }
+
+    /// The summary as a warning on the file, for callers that report
+    /// diagnostics.
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, &self.path, self.detail())
+    }
+
+    fn detail(&self) -> String {
+        let changes = match self.changes.is_empty() {
+            true => String::new(),
+            false => format!(": {}", self.changes.join("; ")),
+        };
+        format!("config version {}; read as version {CURRENT_VERSION}{changes}", self.from)
+    }
+}
+
+// one step of the pipeline: upgrades a document to version `to` and says
+// what it changed
+struct Step {
+    to: u32,
+    apply: fn(&mut DocumentMut) -> Vec<String>,
+}
+
+const STEPS: &[Step] = &[Step {
+    to: 1,
+    apply: canonical_theme_names,
+}];
+
+/// Runs every step newer than the file's `version` over `source`. Returns
+/// the upgraded text, the version it started at and the changes made
+/// besides stamping the new version. Text that isn't valid TOML is passed
+/// through for the parser to report.
+pub fn migrate_source(source: &str) -> Result<(String, u32, Vec<String>)> {
+    let Ok(mut doc) = source.parse::<DocumentMut>() else {
+        return Ok((source.to_string(), CURRENT_VERSION, Vec::new()));
+    };
+    let from = match doc.get("version") {
+        None => 0,
+        Some(item) => item
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow!("`version` must be a whole number"))?,
+    };
+    if from > CURRENT_VERSION {
+        bail!("config is version {from}, but this mangsh only understands up to version {CURRENT_VERSION}");
+    }
+    if from == CURRENT_VERSION {
+        return Ok((source.to_string(), from, Vec::new()));
+    }
+
+    let mut changes = Vec::new();
+    for step in STEPS.iter().filter(|step| step.to > from) {
+        changes.extend((step.apply)(&mut doc));
+    }
+    doc["version"] = value(i64::from(CURRENT_VERSION));
+    Ok((doc.to_string(), from, changes))
+}
+
+// version 1: theme names that used to be accepted as serde aliases
+fn canonical_theme_names(doc: &mut DocumentMut) -> Vec<String> {
+    let Some(Item::Value(Value::String(theme))) = doc.get_mut("theme") else {
+        return Vec::new();
+    };
+    let Some(canonical) = Theme::from_legacy_key(theme.value()).map(|theme| theme.key()) else {
+        return Vec::new();
+    };
+    let change = format!("theme \"{}\" → \"{canonical}\"", theme.value());
+    let decor = theme.decor().clone();
+    *theme = toml_edit::Formatted::new(canonical.to_string());
+    *theme.decor_mut() = decor;
+    vec![change]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate_source, CURRENT_VERSION};
+
+    #[test]
+    fn renames_legacy_theme_names_in_place() {
+        let source = "# mine\ntheme = \"gruvbox\" # warm\n\n[[hosts]]\nname = \"box\"\naddr = \"10.0.0.1\"\n";
+        let (upgraded, from, changes) = migrate_source(source).unwrap();
+        assert_eq!(from, 0);
+        assert_eq!(changes, vec!["theme \"gruvbox\" → \"gruvbox-dark\"".to_string()]);
+        assert!(upgraded.starts_with("# mine\ntheme = \"gruvbox-dark\" # warm\n"));
+        assert!(upgraded.contains(&format!("version = {CURRENT_VERSION}")));
+        assert!(upgraded.contains("name = \"box\""));
+    }
+
+    #[test]
+    fn stamps_the_version_without_other_changes() {
+        let (upgraded, from, changes) = migrate_source("theme = \"mango\"\n").unwrap();
+        assert_eq!(from, 0);
+        assert!(changes.is_empty());
+        assert_eq!(upgraded, format!("theme = \"mango\"\nversion = {CURRENT_VERSION}\n"));
+    }
+
+    #[test]
+    fn leaves_current_files_alone() {
+        let source = format!("version = {CURRENT_VERSION}\ntheme = \"gruvbox\"\n");
+        let (upgraded, from, changes) = migrate_source(&source).unwrap();
+        assert_eq!((upgraded, from), (source, CURRENT_VERSION));
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_and_malformed_versions() {
+        let newer = format!("version = {}\n", CURRENT_VERSION + 1);
+        assert!(migrate_source(&newer).unwrap_err().to_string().contains("only understands up to"));
+        assert!(migrate_source("version = \"one\"\n").is_err());
+        assert!(migrate_source("version = -1\n").is_err());
+    }
+
+    #[test]
+    fn passes_invalid_toml_through() {
+        let (upgraded, _, changes) = migrate_source("theme = \n").unwrap();
+        assert_eq!(upgraded, "theme = \n");
+        assert!(changes.is_empty());
+    }
+}
@@ -1,16 +1,207 @@
+pub mod diagnostic;
+pub mod edit;
+pub mod inherit;
+pub mod layers;
 pub mod loader;
+pub mod migrate;
 pub mod schema;
+#[cfg(test)]
+mod temp_dir;
+pub mod watch;
 
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
-use crate::theme::Theme;
+use crate::{
+    keys::KeyTables,
+    theme::{all_themes, background::Brightness, CustomTheme, Theme, ThemeName},
+};
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+use self::{
+    inherit::ResolvedHost,
+    schema::{HostGroup, HostProfile, HostSettings, HostTemplate},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
-    pub theme: Option<Theme>,
+    /// Format version; older files are upgraded by `migrate` on load.
+    #[serde(default)]
+    pub version: u32,
+    pub theme: Option<ThemeName>,
+    /// What `theme = "auto"` uses on a light background; Gruvbox Light
+    /// when unset.
+    pub light_theme: Option<ThemeName>,
+    /// What `theme = "auto"` uses on a dark background; Gruvbox Dark when
+    /// unset.
+    pub dark_theme: Option<ThemeName>,
+    /// Extra files merged in underneath this one, relative to it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// User-defined themes, selectable by name next to the built-ins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub themes: Vec<CustomTheme>,
+    /// Settings every host inherits unless a template or the host sets them.
+    #[serde(default, skip_serializing_if = "HostSettings::is_empty")]
+    pub defaults: HostSettings,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<HostTemplate>,
+    #[serde(default)]
+    pub hosts: Vec<HostProfile>,
+    #[serde(default)]
+    pub groups: Vec<HostGroup>,
+    /// The key that hands a live session back to mangsh for one
+    /// `[keys.session]` binding; Ctrl-a when unset.
+    pub prefix_key: Option<String>,
+    /// Key bindings per screen, on top of the built-in ones.
+    #[serde(default, skip_serializing_if = "KeyTables::is_empty")]
+    pub keys: KeyTables,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: migrate::CURRENT_VERSION,
+            theme: None,
+            light_theme: None,
+            dark_theme: None,
+            include: Vec::new(),
+            themes: Vec::new(),
+            defaults: HostSettings::default(),
+            templates: Vec::new(),
+            hosts: Vec::new(),
+            groups: Vec::new(),
+            prefix_key: None,
+            keys: KeyTables::new(),
+        }
+    }
 }
 
 impl Config {
-    pub fn effective_theme(&self) -> Theme {
-        self.theme.unwrap_or_default()
+    /// The configured theme, or the default when it names a custom theme
+    /// that isn't defined.
+    pub fn effective_theme(&self) -> ThemeName {
+        match &self.theme {
+            Some(ThemeName::Custom(name)) if self.find_theme(name).is_none() => ThemeName::default(),
+            Some(theme) => theme.clone(),
+            None => ThemeName::default(),
+        }
+    }
+
+    /// The theme `auto` stands for on a background of `brightness`; one
+    /// that isn't defined (or is `auto` itself) falls back to Gruvbox.
+    pub fn auto_theme(&self, brightness: Brightness) -> ThemeName {
+        let (configured, fallback) = match brightness {
+            Brightness::Light => (&self.light_theme, Theme::GruvboxLight),
+            Brightness::Dark => (&self.dark_theme, Theme::GruvboxDark),
+        };
+        match configured {
+            Some(ThemeName::Builtin(theme)) => ThemeName::Builtin(*theme),
+            Some(ThemeName::Custom(name)) if self.find_theme(name).is_some() => ThemeName::Custom(name.clone()),
+            _ => ThemeName::Builtin(fallback),
+        }
+    }
+
+    pub fn find_theme(&self, name: &str) -> Option<&CustomTheme> {
+        self.themes.iter().find(|theme| theme.name == name)
+    }
+
+    /// Every selectable theme: `auto`, the built-ins, then custom themes.
+    pub fn theme_names(&self) -> Vec<ThemeName> {
+        let builtin = all_themes().iter().copied().map(ThemeName::Builtin);
+        let builtin = std::iter::once(ThemeName::Auto).chain(builtin);
+        let custom = self
+            .themes
+            .iter()
+            .map(|theme| ThemeName::parse(&theme.name))
+            .filter(|name| matches!(name, ThemeName::Custom(_)));
+        builtin.chain(custom).collect()
+    }
+
+    pub fn host_index(&self, name: &str) -> Option<usize> {
+        self.hosts.iter().position(|host| host.name == name)
+    }
+
+    /// Removes a host and drops it from any group that lists it by name.
+    pub fn remove_host(&mut self, index: usize) -> HostProfile {
+        let removed = self.hosts.remove(index);
+        for group in &mut self.groups {
+            group.hosts.retain(|member| *member != removed.name);
+        }
+        removed
+    }
+
+    /// Replaces a host, following a rename in the groups that list it.
+    pub fn replace_host(&mut self, index: usize, host: HostProfile) {
+        let old = std::mem::replace(&mut self.hosts[index], host);
+        let new = &self.hosts[index].name;
+        if old.name != *new {
+            for group in &mut self.groups {
+                for member in group.hosts.iter_mut().filter(|m| **m == old.name) {
+                    member.clone_from(new);
+                }
+            }
+        }
+    }
+
+    pub fn find_group(&self, name: &str) -> Option<&HostGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// Every tag used by a host, sorted.
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags = self
+            .hosts
+            .iter()
+            .flat_map(|host| host.tags.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
+    /// Indices of the hosts in a group, in config order.
+    pub fn group_members(&self, group: &HostGroup) -> Vec<usize> {
+        self.hosts
+            .iter()
+            .enumerate()
+            .filter(|(_, host)| {
+                group.hosts.contains(&host.name) || group.tags.iter().any(|tag| host.has_tag(tag))
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Resolves command-line or TUI targets (host names, group names or
+    /// `tag:<tag>`) into resolved hosts, keeping the order they were given in
+    /// and dropping duplicates.
+    pub fn resolve_targets(&self, targets: &[String]) -> Result<Vec<ResolvedHost>> {
+        let mut indices: Vec<usize> = Vec::new();
+        for target in targets {
+            let matched = if let Some(index) = self.host_index(target) {
+                vec![index]
+            } else if let Some(group) = self.find_group(target) {
+                if let Some(missing) = group.hosts.iter().find(|name| self.host_index(name).is_none()) {
+                    bail!("Group {} references unknown host {missing}", group.name);
+                }
+                self.group_members(group)
+            } else if let Some(tag) = target.strip_prefix("tag:") {
+                self.hosts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, host)| host.has_tag(tag))
+                    .map(|(index, _)| index)
+                    .collect()
+            } else {
+                bail!("Unknown host or group: {target}");
+            };
+            if matched.is_empty() {
+                bail!("{target} matches no hosts");
+            }
+            for index in matched {
+                if !indices.contains(&index) {
+                    indices.push(index);
+                }
+            }
+        }
+        Ok(indices.into_iter().map(|index| self.resolve(&self.hosts[index])).collect())
     }
 }
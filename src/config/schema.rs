@@ -1,7 +1,122 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+// a host as written in the config; unset settings are inherited (see
+// `Config::resolve`)
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct HostProfile {
     pub name: String,
     pub addr: String,
-    pub port: u16,
-    pub user: String,
-    pub tor: bool,
+    /// Template to inherit from, on top of any whose patterns match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tor: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl HostProfile {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn settings(&self) -> HostSettings {
+        HostSettings {
+            user: self.user.clone(),
+            port: self.port,
+            identity: self.identity.clone(),
+            jump: self.jump.clone(),
+            tor: self.tor,
+            keepalive: self.keepalive,
+        }
+    }
+}
+
+/// The inheritable part of a host: the `[defaults]` block, and the body of
+/// a template.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct HostSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// Host to hop through: a configured host name or `[user@]addr[:port]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tor: Option<bool>,
+    /// Seconds between ssh keepalive probes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<u32>,
+}
+
+impl HostSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == HostSettings::default()
+    }
+
+    /// Takes every setting `other` sets.
+    pub fn overlay(&mut self, other: &HostSettings) {
+        if other.user.is_some() {
+            self.user.clone_from(&other.user);
+        }
+        if other.port.is_some() {
+            self.port = other.port;
+        }
+        if other.identity.is_some() {
+            self.identity.clone_from(&other.identity);
+        }
+        if other.jump.is_some() {
+            self.jump.clone_from(&other.jump);
+        }
+        if other.tor.is_some() {
+            self.tor = other.tor;
+        }
+        if other.keepalive.is_some() {
+            self.keepalive = other.keepalive;
+        }
+    }
+}
+
+// named settings shared by several hosts; applied to hosts that name it and
+// to hosts whose name or addr matches one of its wildcard patterns
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct HostTemplate {
+    pub name: String,
+    /// Patterns like `*.prod.example.com` or `10.0.?.*`.
+    #[serde(rename = "match", default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+    #[serde(flatten)]
+    pub settings: HostSettings,
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// a named set of hosts, listed explicitly and/or picked up by tag
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct HostGroup {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+// how often the config file's metadata is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// what a file looked like on the last check; `None` when it didn't exist
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Notices edits made to the config files from outside mangsh by polling
+/// their modification time and size.
+pub struct ConfigWatcher {
+    files: Vec<(PathBuf, Stamp)>,
+    checked: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let mut watcher = Self {
+            files: Vec::new(),
+            checked: Instant::now(),
+        };
+        watcher.sync(paths);
+        watcher
+    }
+
+    /// Records the files as they are now, e.g. after mangsh wrote one
+    /// itself, so that write isn't reported as an outside change.
+    pub fn sync(&mut self, paths: Vec<PathBuf>) {
+        self.files = paths
+            .into_iter()
+            .map(|path| {
+                let stamp = stamp(&path);
+                (path, stamp)
+            })
+            .collect();
+        self.checked = Instant::now();
+    }
+
+    /// True once per change to any of the watched files.
+    pub fn poll(&mut self) -> bool {
+        if self.checked.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.checked = Instant::now();
+        let mut changed = false;
+        for (path, last) in &mut self.files {
+            let current = stamp(path);
+            if current != *last {
+                *last = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        time::{Duration, Instant, SystemTime},
+    };
+
+    use super::{ConfigWatcher, POLL_INTERVAL};
+    use crate::config::temp_dir::TempDir;
+
+    // polls as if the interval had passed
+    fn poll(watcher: &mut ConfigWatcher) -> bool {
+        watcher.checked = Instant::now().checked_sub(POLL_INTERVAL).unwrap();
+        watcher.poll()
+    }
+
+    #[test]
+    fn reports_a_size_change_once() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "theme = \"mango\"\n");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+        assert!(!poll(&mut watcher));
+
+        fs::write(&path, "theme = \"forest\"\n").unwrap();
+        assert!(poll(&mut watcher));
+        assert!(!poll(&mut watcher));
+    }
+
+    #[test]
+    fn reports_a_touch_with_the_same_size() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "theme = \"mango\"\n");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+
+        let later = SystemTime::now() + Duration::from_secs(5);
+        File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert!(poll(&mut watcher));
+        assert!(!poll(&mut watcher));
+    }
+
+    #[test]
+    fn waits_out_the_poll_interval() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "a");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+        fs::write(&path, "ab").unwrap();
+        assert!(!watcher.poll());
+        assert!(poll(&mut watcher));
+    }
+
+    #[test]
+    fn reports_files_appearing_and_disappearing() {
+        let dir = TempDir::new();
+        let path = dir.0.join("config.toml");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+        assert!(!poll(&mut watcher));
+
+        fs::write(&path, "").unwrap();
+        assert!(poll(&mut watcher));
+        fs::remove_file(&path).unwrap();
+        assert!(poll(&mut watcher));
+        assert!(!poll(&mut watcher));
+    }
+
+    #[test]
+    fn reports_a_file_replaced_by_rename() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "theme = \"mango\"\n");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+
+        // how editors and mangsh itself save: write elsewhere, rename over
+        let tmp = dir.file(".config.toml.swp", "theme = \"night\"\n");
+        File::options()
+            .write(true)
+            .open(&tmp)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        fs::rename(&tmp, &path).unwrap();
+        assert!(poll(&mut watcher));
+        assert!(!poll(&mut watcher));
+    }
+
+    #[test]
+    fn sync_swallows_our_own_writes() {
+        let dir = TempDir::new();
+        let path = dir.file("config.toml", "a");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+        fs::write(&path, "ab").unwrap();
+        watcher.sync(vec![path.clone()]);
+        assert!(!poll(&mut watcher));
+    }
+}
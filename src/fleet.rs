@@ -0,0 +1,408 @@
+use anyhow::{bail, Result};
+use std::{
+    collections::HashSet,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    ssh::exec::{spawn_exec, ExecEvent, ExecOutcome, Stream, DEFAULT_CONCURRENCY},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FleetFocus {
+    Hosts,
+    Command,
+    Results,
+}
+
+pub enum RowStatus {
+    Queued,
+    Running(Instant),
+    Done(ExecOutcome),
+}
+
+pub struct FleetRow {
+    pub host: String,
+    pub status: RowStatus,
+    pub output: Vec<(Stream, String)>,
+}
+
+impl FleetRow {
+    pub fn duration(&self) -> Option<Duration> {
+        match &self.status {
+            RowStatus::Queued => None,
+            RowStatus::Running(started) => Some(started.elapsed()),
+            RowStatus::Done(outcome) => Some(outcome.duration),
+        }
+    }
+
+    pub fn first_line(&self) -> &str {
+        self.output.first().map(|(_, line)| line.as_str()).unwrap_or("")
+    }
+}
+
+pub struct FleetRun {
+    pub command: String,
+    pub rows: Vec<FleetRow>,
+    events: Option<Receiver<ExecEvent>>,
+}
+
+impl FleetRun {
+    pub fn is_running(&self) -> bool {
+        self.events.is_some()
+    }
+}
+
+// state for the fleet command runner screen
+pub struct Fleet {
+    /// Host names in config order, as of the last `sync_hosts`.
+    pub hosts: Vec<String>,
+    /// Picked hosts by name, so removing or reordering hosts doesn't move
+    /// the selection onto others.
+    pub selected: HashSet<String>,
+    pub cursor: usize,
+    pub command: String,
+    pub focus: FleetFocus,
+    pub run: Option<FleetRun>,
+    pub result_index: usize,
+    pub detail: bool,
+    pub detail_scroll: u16,
+    pub notice: Option<String>,
+    pub group: Option<usize>,
+}
+
+impl Fleet {
+    pub fn new() -> Self {
+        Self {
+            hosts: Vec::new(),
+            selected: HashSet::new(),
+            cursor: 0,
+            command: String::new(),
+            focus: FleetFocus::Hosts,
+            run: None,
+            result_index: 0,
+            detail: false,
+            detail_scroll: 0,
+            notice: None,
+            group: None,
+        }
+    }
+
+    /// Keeps the selection in step with the host list; hosts that are gone
+    /// drop out of it and are returned, sorted.
+    pub fn sync_hosts(&mut self, config: &Config) -> Vec<String> {
+        self.hosts = config.hosts.iter().map(|host| host.name.clone()).collect();
+        let mut dropped = self
+            .selected
+            .iter()
+            .filter(|name| !self.hosts.contains(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        dropped.sort();
+        for name in &dropped {
+            self.selected.remove(name);
+        }
+        self.cursor = self.cursor.min(self.hosts.len().saturating_sub(1));
+        dropped
+    }
+
+    /// Carries the selection over to a host's new name.
+    pub fn rename(&mut self, old: &str, new: &str) {
+        if old != new && self.selected.remove(old) {
+            self.selected.insert(new.to_string());
+        }
+    }
+
+    pub fn is_selected(&self, name: &str) -> bool {
+        self.selected.contains(name)
+    }
+
+    pub fn next_host(&mut self) {
+        if !self.hosts.is_empty() {
+            self.cursor = (self.cursor + 1) % self.hosts.len();
+        }
+    }
+
+    pub fn previous_host(&mut self) {
+        self.cursor = match self.cursor {
+            0 => self.hosts.len().saturating_sub(1),
+            n => n - 1,
+        };
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(name) = self.hosts.get(self.cursor) {
+            if !self.selected.remove(name) {
+                self.selected.insert(name.clone());
+            }
+            self.group = None;
+        }
+    }
+
+    pub fn toggle_all(&mut self) {
+        match self.hosts.iter().all(|name| self.selected.contains(name)) {
+            true => self.selected.clear(),
+            false => self.selected.extend(self.hosts.iter().cloned()),
+        }
+        self.group = None;
+    }
+
+    /// Steps through the configured groups, selecting exactly the members
+    /// of each in turn.
+    pub fn next_group(&mut self, config: &Config) {
+        self.group = match self.group {
+            None if !config.groups.is_empty() => Some(0),
+            Some(index) if index + 1 < config.groups.len() => Some(index + 1),
+            _ => None,
+        };
+        let members = self
+            .group
+            .map(|index| config.group_members(&config.groups[index]))
+            .unwrap_or_default();
+        self.selected = members.into_iter().map(|index| config.hosts[index].name.clone()).collect();
+    }
+
+    pub fn next_focus(&mut self) {
+        self.focus = match self.focus {
+            FleetFocus::Hosts => FleetFocus::Command,
+            FleetFocus::Command if self.run.is_some() => FleetFocus::Results,
+            FleetFocus::Command | FleetFocus::Results => FleetFocus::Hosts,
+        };
+    }
+
+    pub fn start(&mut self, config: &Config) -> Result<()> {
+        let command = self.command.trim().to_string();
+        if command.is_empty() {
+            bail!("Type a command to run.");
+        }
+        let targets = config
+            .hosts
+            .iter()
+            .filter(|host| self.is_selected(&host.name))
+            .map(|host| config.resolve(host))
+            .collect::<Vec<_>>();
+        if targets.is_empty() {
+            bail!("Select at least one host (Space).");
+        }
+        if self.run.as_ref().is_some_and(FleetRun::is_running) {
+            bail!("A command is still running.");
+        }
+
+        let rows = targets
+            .iter()
+            .map(|host| FleetRow {
+                host: host.name.clone(),
+                status: RowStatus::Queued,
+                output: Vec::new(),
+            })
+            .collect();
+        let events = spawn_exec(targets, vec![command.clone()], DEFAULT_CONCURRENCY);
+        self.run = Some(FleetRun {
+            command,
+            rows,
+            events: Some(events),
+        });
+        self.result_index = 0;
+        self.detail = false;
+        self.focus = FleetFocus::Results;
+        Ok(())
+    }
+
+    /// Applies exec progress that arrived since the last frame.
+    pub fn pump(&mut self) {
+        let Some(run) = self.run.as_mut() else {
+            return;
+        };
+        let Some(events) = run.events.as_ref() else {
+            return;
+        };
+        loop {
+            match events.try_recv() {
+                Ok(ExecEvent::Started { index }) => {
+                    run.rows[index].status = RowStatus::Running(Instant::now());
+                }
+                Ok(ExecEvent::Output { index, stream, line }) => {
+                    run.rows[index].output.push((stream, line));
+                }
+                Ok(ExecEvent::Finished { index, outcome }) => {
+                    run.rows[index].status = RowStatus::Done(outcome);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    run.events = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn next_result(&mut self) {
+        if let Some(run) = &self.run
+            && !run.rows.is_empty()
+        {
+            self.result_index = (self.result_index + 1) % run.rows.len();
+            self.detail_scroll = 0;
+        }
+    }
+
+    pub fn previous_result(&mut self) {
+        if let Some(run) = &self.run {
+            self.result_index = if self.result_index == 0 {
+                run.rows.len().saturating_sub(1)
+            } else {
+                self.result_index - 1
+            };
+            self.detail_scroll = 0;
+        }
+    }
+
+    pub fn open_detail(&mut self) {
+        if self.selected_row().is_some() {
+            self.detail = true;
+            self.detail_scroll = 0;
+        }
+    }
+
+    pub fn selected_row(&self) -> Option<&FleetRow> {
+        self.run.as_ref().and_then(|run| run.rows.get(self.result_index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fleet;
+    use crate::config::Config;
+
+    fn config(source: &str) -> Config {
+        toml::from_str(source).unwrap()
+    }
+
+    const HOSTS: &str = r#"
+        [[hosts]]
+        name = "alpha"
+        addr = "10.0.0.1"
+        tags = ["web"]
+
+        [[hosts]]
+        name = "bravo"
+        addr = "10.0.0.2"
+
+        [[hosts]]
+        name = "charlie"
+        addr = "10.0.0.3"
+        tags = ["web"]
+
+        [[groups]]
+        name = "front"
+        tags = ["web"]
+
+        [[groups]]
+        name = "solo"
+        hosts = ["bravo"]
+    "#;
+
+    fn selected(fleet: &Fleet) -> Vec<&str> {
+        let mut names = fleet.selected.iter().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn selection_follows_names_across_reordering() {
+        let mut fleet = Fleet::new();
+        fleet.sync_hosts(&config(HOSTS));
+        fleet.cursor = 1;
+        fleet.toggle();
+        assert_eq!(selected(&fleet), ["bravo"]);
+
+        let reordered = config(r#"
+            [[hosts]]
+            name = "charlie"
+            addr = "10.0.0.3"
+
+            [[hosts]]
+            name = "bravo"
+            addr = "10.0.0.2"
+        "#);
+        assert_eq!(fleet.sync_hosts(&reordered), Vec::<String>::new());
+        assert!(fleet.is_selected("bravo"));
+        assert!(!fleet.is_selected("charlie"));
+    }
+
+    #[test]
+    fn sync_drops_missing_hosts_and_clamps_the_cursor() {
+        let mut fleet = Fleet::new();
+        fleet.sync_hosts(&config(HOSTS));
+        fleet.toggle_all();
+        fleet.cursor = 2;
+        let dropped = fleet.sync_hosts(&config("[[hosts]]\nname = \"bravo\"\naddr = \"10.0.0.2\"\n"));
+        assert_eq!(dropped, ["alpha", "charlie"]);
+        assert_eq!(selected(&fleet), ["bravo"]);
+        assert_eq!(fleet.cursor, 0);
+    }
+
+    #[test]
+    fn rename_keeps_the_host_selected() {
+        let mut fleet = Fleet::new();
+        fleet.sync_hosts(&config(HOSTS));
+        fleet.toggle();
+        fleet.rename("alpha", "able");
+        assert_eq!(selected(&fleet), ["able"]);
+        fleet.rename("bravo", "baker");
+        assert_eq!(selected(&fleet), ["able"]);
+    }
+
+    #[test]
+    fn group_cycling_wraps_to_no_group() {
+        let config = config(HOSTS);
+        let mut fleet = Fleet::new();
+        fleet.sync_hosts(&config);
+
+        fleet.next_group(&config);
+        assert_eq!(fleet.group, Some(0));
+        assert_eq!(selected(&fleet), ["alpha", "charlie"]);
+        fleet.next_group(&config);
+        assert_eq!(fleet.group, Some(1));
+        assert_eq!(selected(&fleet), ["bravo"]);
+        fleet.next_group(&config);
+        assert_eq!(fleet.group, None);
+        assert!(fleet.selected.is_empty());
+        fleet.next_group(&config);
+        assert_eq!(fleet.group, Some(0));
+    }
+
+    #[test]
+    fn picking_by_hand_leaves_the_group() {
+        let config = config(HOSTS);
+        let mut fleet = Fleet::new();
+        fleet.sync_hosts(&config);
+        fleet.next_group(&config);
+        fleet.cursor = 1;
+        fleet.toggle();
+        assert_eq!(fleet.group, None);
+        assert_eq!(selected(&fleet), ["alpha", "bravo", "charlie"]);
+    }
+
+    #[test]
+    fn no_groups_selects_nothing() {
+        let config = config("[[hosts]]\nname = \"alpha\"\naddr = \"10.0.0.1\"\n");
+        let mut fleet = Fleet::new();
+        fleet.sync_hosts(&config);
+        fleet.toggle();
+        fleet.next_group(&config);
+        assert_eq!(fleet.group, None);
+        assert!(fleet.selected.is_empty());
+    }
+
+    #[test]
+    fn cursor_wraps_both_ways() {
+        let mut fleet = Fleet::new();
+        fleet.sync_hosts(&config(HOSTS));
+        fleet.previous_host();
+        assert_eq!(fleet.cursor, 2);
+        fleet.next_host();
+        assert_eq!(fleet.cursor, 0);
+    }
+}
@@ -0,0 +1,144 @@
+// small fzf-style subsequence matcher used by the search boxes
+
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Char indices of the matched characters in the candidate.
+    pub positions: Vec<usize>,
+}
+
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 8;
+const BOUNDARY: i64 = 10;
+const PREFIX: i64 = 12;
+
+/// Matches `query` as a case-insensitive subsequence of `candidate`,
+/// preferring tight matches that start on word boundaries.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let query = query.chars().map(fold).collect::<Vec<_>>();
+    if query.is_empty() {
+        return Some(FuzzyMatch { score: 0, positions: Vec::new() });
+    }
+    let chars = candidate.chars().collect::<Vec<_>>();
+    let lower = chars.iter().copied().map(fold).collect::<Vec<_>>();
+
+    // forward: find where the earliest full match ends
+    let mut qi = 0;
+    let mut end = 0;
+    for (i, c) in lower.iter().enumerate() {
+        if *c == query[qi] {
+            qi += 1;
+            if qi == query.len() {
+                end = i;
+                break;
+            }
+        }
+    }
+    if qi < query.len() {
+        return None;
+    }
+
+    // backward: pull the start as close to the end as possible
+    let mut qi = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if lower[i] == query[qi - 1] {
+            qi -= 1;
+            if qi == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut qi = 0;
+    for (i, c) in lower.iter().enumerate().skip(start) {
+        if qi < query.len() && *c == query[qi] {
+            positions.push(i);
+            qi += 1;
+        }
+    }
+
+    let mut score = 0;
+    for (n, &pos) in positions.iter().enumerate() {
+        score += MATCH;
+        if pos == 0 {
+            score += PREFIX;
+        }
+        if is_boundary(&chars, pos) {
+            score += BOUNDARY;
+        }
+        if n > 0 && positions[n - 1] + 1 == pos {
+            score += CONSECUTIVE;
+        }
+    }
+    let span = positions[positions.len() - 1] - positions[0] + 1;
+    score -= (span - positions.len()) as i64;
+    score -= chars.len() as i64 / 8;
+
+    Some(FuzzyMatch { score, positions })
+}
+
+// one char in, one char out, so query and candidate fold the same way and
+// positions stay char indices; `İ` lowercases to two chars, `i` and a dot
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_boundary(chars: &[char], pos: usize) -> bool {
+    if pos == 0 {
+        return true;
+    }
+    let prev = chars[pos - 1];
+    let cur = chars[pos];
+    !prev.is_alphanumeric() || (prev.is_lowercase() && cur.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_match;
+
+    fn score(query: &str, candidate: &str) -> i64 {
+        fuzzy_match(query, candidate).unwrap().score
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        assert_eq!(fuzzy_match("WEB", "web-01").unwrap().positions, vec![0, 1, 2]);
+        assert_eq!(fuzzy_match("wb1", "web-01").unwrap().positions, vec![0, 2, 5]);
+        assert!(fuzzy_match("bew", "web-01").is_none());
+        assert!(fuzzy_match("web", "we").is_none());
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let found = fuzzy_match("", "anything").unwrap();
+        assert_eq!(found.score, 0);
+        assert!(found.positions.is_empty());
+    }
+
+    #[test]
+    fn picks_the_tightest_match() {
+        // the earliest `d` is passed over for the run of `db`
+        assert_eq!(fuzzy_match("db", "dev-db").unwrap().positions, vec![4, 5]);
+    }
+
+    #[test]
+    fn positions_are_char_indices() {
+        assert_eq!(fuzzy_match("db", "café-db").unwrap().positions, vec![5, 6]);
+        assert_eq!(fuzzy_match("i", "İstanbul").unwrap().positions, vec![0]);
+    }
+
+    #[test]
+    fn prefers_prefixes_boundaries_and_runs() {
+        assert!(score("prod", "prod-web") > score("prod", "web-prod"));
+        assert!(score("wp", "web-prod") > score("wp", "wasp"));
+        assert!(score("db", "dbx") > score("db", "dxb"));
+        assert!(score("ms", "mailServer") > score("ms", "mailserver"));
+    }
+
+    #[test]
+    fn shorter_candidates_win_ties() {
+        assert!(score("web", "web") > score("web", "web-frontend-eu-west-1"));
+    }
+}
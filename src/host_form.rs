@@ -0,0 +1,498 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config::{
+    schema::{expand_home, HostProfile},
+    Config,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FormField {
+    Name,
+    Addr,
+    Template,
+    User,
+    Port,
+    Tags,
+    Identity,
+    Jump,
+    Keepalive,
+    Tor,
+}
+
+pub const FIELDS: [FormField; 10] = [
+    FormField::Name,
+    FormField::Addr,
+    FormField::Template,
+    FormField::User,
+    FormField::Port,
+    FormField::Tags,
+    FormField::Identity,
+    FormField::Jump,
+    FormField::Keepalive,
+    FormField::Tor,
+];
+
+impl FormField {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FormField::Name => "Name",
+            FormField::Addr => "Address",
+            FormField::Template => "Template",
+            FormField::User => "User",
+            FormField::Port => "Port",
+            FormField::Tags => "Tags",
+            FormField::Identity => "Identity",
+            FormField::Jump => "Jump",
+            FormField::Keepalive => "Keepalive",
+            FormField::Tor => "Tor",
+        }
+    }
+
+    /// The setting name `Config::resolve` reports a source under, for
+    /// fields that can be inherited.
+    pub fn setting(&self) -> Option<&'static str> {
+        match self {
+            FormField::User => Some("user"),
+            FormField::Port => Some("port"),
+            FormField::Identity => Some("identity"),
+            FormField::Jump => Some("jump"),
+            FormField::Keepalive => Some("keepalive"),
+            FormField::Tor => Some("tor"),
+            FormField::Name | FormField::Addr | FormField::Template | FormField::Tags => None,
+        }
+    }
+}
+
+// state for the add/edit host screen; blank fields are inherited
+pub struct HostForm {
+    /// Index of the host being edited; `None` when adding.
+    pub editing: Option<usize>,
+    pub name: String,
+    pub addr: String,
+    pub template: String,
+    pub user: String,
+    pub port: String,
+    pub tags: String,
+    pub identity: String,
+    pub jump: String,
+    pub keepalive: String,
+    /// `None` inherits; Space cycles on, off, inherit.
+    pub tor: Option<bool>,
+    pub focus: usize,
+    pub errors: Vec<(FormField, String)>,
+    pub notice: Option<String>,
+    keys: Vec<String>,
+}
+
+impl HostForm {
+    pub fn new_host() -> Self {
+        Self {
+            editing: None,
+            name: String::new(),
+            addr: String::new(),
+            template: String::new(),
+            user: String::new(),
+            port: String::new(),
+            tags: String::new(),
+            identity: String::new(),
+            jump: String::new(),
+            keepalive: String::new(),
+            tor: None,
+            focus: 0,
+            errors: Vec::new(),
+            notice: None,
+            keys: ssh_keys(),
+        }
+    }
+
+    pub fn edit(index: usize, host: &HostProfile) -> Self {
+        Self {
+            editing: Some(index),
+            name: host.name.clone(),
+            addr: host.addr.clone(),
+            template: host.template.clone().unwrap_or_default(),
+            user: host.user.clone().unwrap_or_default(),
+            port: host.port.map(|port| port.to_string()).unwrap_or_default(),
+            tags: host.tags.join(", "),
+            identity: host.identity.clone().unwrap_or_default(),
+            jump: host.jump.clone().unwrap_or_default(),
+            keepalive: host.keepalive.map(|secs| secs.to_string()).unwrap_or_default(),
+            tor: host.tor,
+            ..Self::new_host()
+        }
+    }
+
+    pub fn field(&self) -> FormField {
+        FIELDS[self.focus]
+    }
+
+    pub fn next_field(&mut self) {
+        self.focus = (self.focus + 1) % FIELDS.len();
+    }
+
+    pub fn previous_field(&mut self) {
+        self.focus = self.focus.checked_sub(1).unwrap_or(FIELDS.len() - 1);
+    }
+
+    pub fn value(&self, field: FormField) -> &str {
+        match field {
+            FormField::Name => &self.name,
+            FormField::Addr => &self.addr,
+            FormField::Template => &self.template,
+            FormField::User => &self.user,
+            FormField::Port => &self.port,
+            FormField::Tags => &self.tags,
+            FormField::Identity => &self.identity,
+            FormField::Jump => &self.jump,
+            FormField::Keepalive => &self.keepalive,
+            FormField::Tor => match self.tor {
+                Some(true) => "[x]",
+                Some(false) => "[ ]",
+                None => "",
+            },
+        }
+    }
+
+    fn text_mut(&mut self) -> Option<&mut String> {
+        match self.field() {
+            FormField::Name => Some(&mut self.name),
+            FormField::Addr => Some(&mut self.addr),
+            FormField::Template => Some(&mut self.template),
+            FormField::User => Some(&mut self.user),
+            FormField::Port => Some(&mut self.port),
+            FormField::Tags => Some(&mut self.tags),
+            FormField::Identity => Some(&mut self.identity),
+            FormField::Jump => Some(&mut self.jump),
+            FormField::Keepalive => Some(&mut self.keepalive),
+            FormField::Tor => None,
+        }
+    }
+
+    pub fn input(&mut self, c: char) {
+        match self.field() {
+            FormField::Tor if c == ' ' => {
+                self.tor = match self.tor {
+                    None => Some(true),
+                    Some(true) => Some(false),
+                    Some(false) => None,
+                }
+            }
+            FormField::Port | FormField::Keepalive if !c.is_ascii_digit() => {}
+            _ => {
+                if let Some(text) = self.text_mut() {
+                    text.push(c);
+                }
+            }
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(text) = self.text_mut() {
+            text.pop();
+        }
+    }
+
+    /// Steps the identity field through the private keys found in ~/.ssh.
+    pub fn cycle_identity(&mut self, forward: bool) {
+        let mut choices = vec![String::new()];
+        choices.extend(self.keys.iter().cloned());
+        let current = choices.iter().position(|k| *k == self.identity);
+        let next = match (current, forward) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1) % choices.len(),
+            (Some(0), false) => choices.len() - 1,
+            (Some(i), false) => i - 1,
+        };
+        self.identity = choices.swap_remove(next);
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn error(&self, field: FormField) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|(f, _)| *f == field)
+            .map(|(_, msg)| msg.as_str())
+    }
+
+    /// The profile as currently typed, with unparsable numbers left unset;
+    /// used to preview what blank fields inherit.
+    pub fn draft(&self) -> HostProfile {
+        let text = |value: &str| Some(value.trim()).filter(|v| !v.is_empty()).map(str::to_string);
+        let mut tags = Vec::new();
+        for tag in self.tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        HostProfile {
+            name: self.name.trim().to_string(),
+            addr: self.addr.trim().to_string(),
+            template: text(&self.template),
+            port: self.port.parse().ok(),
+            user: text(&self.user),
+            tor: self.tor,
+            identity: text(&self.identity),
+            jump: text(&self.jump),
+            keepalive: self.keepalive.parse().ok(),
+            tags,
+        }
+    }
+
+    /// Checks every field and builds the profile, recording per-field errors.
+    pub fn validate(&mut self, config: &Config) -> Option<HostProfile> {
+        let host = self.draft();
+        self.errors = check_host(&host, self.editing, config);
+        // text that didn't parse is left unset by `draft`
+        if !self.port.is_empty() && host.port.is_none() {
+            self.errors.push((FormField::Port, "must be 1-65535".to_string()));
+        }
+        if !self.keepalive.is_empty() && host.keepalive.is_none() {
+            self.errors.push((FormField::Keepalive, "must be a positive number of seconds".to_string()));
+        }
+        self.errors.is_empty().then_some(host)
+    }
+}
+
+/// What's wrong with `host` as an entry in `config`, per field; shared by
+/// the form and `mangsh add`. `editing` is the index of the host it
+/// replaces, if any.
+pub fn check_host(host: &HostProfile, editing: Option<usize>, config: &Config) -> Vec<(FormField, String)> {
+    let mut errors = Vec::new();
+    let name = host.name.as_str();
+    let addr = host.addr.as_str();
+
+    if name.is_empty() {
+        errors.push((FormField::Name, "required".to_string()));
+    } else if name.contains(char::is_whitespace) {
+        errors.push((FormField::Name, "no spaces allowed".to_string()));
+    } else if config.host_index(name).is_some_and(|index| Some(index) != editing) {
+        errors.push((FormField::Name, "another host already uses this name".to_string()));
+    } else if config.find_group(name).is_some() {
+        errors.push((FormField::Name, "a group already uses this name".to_string()));
+    }
+    if addr.is_empty() {
+        errors.push((FormField::Addr, "required".to_string()));
+    } else if addr.contains(char::is_whitespace) {
+        errors.push((FormField::Addr, "no spaces allowed".to_string()));
+    }
+    if let Some(template) = &host.template
+        && config.find_template(template).is_none()
+    {
+        errors.push((FormField::Template, "no template with this name".to_string()));
+    }
+    if host.port == Some(0) {
+        errors.push((FormField::Port, "must be 1-65535".to_string()));
+    }
+    if host.identity.as_deref().is_some_and(|identity| !expand_home(identity).is_file()) {
+        errors.push((FormField::Identity, "file not found".to_string()));
+    }
+    if let Some(jump) = host.jump.as_deref() {
+        let problem = if jump.contains(char::is_whitespace) {
+            Some("no spaces allowed".to_string())
+        } else if jump == name {
+            Some("a host can't jump through itself".to_string())
+        } else if config.host_index(jump).is_some() {
+            None
+        } else {
+            jump.split(',').find_map(jump_problem)
+        };
+        if let Some(problem) = problem {
+            errors.push((FormField::Jump, problem));
+        }
+    }
+    if host.keepalive == Some(0) {
+        errors.push((FormField::Keepalive, "must be a positive number of seconds".to_string()));
+    }
+    errors
+}
+
+// one `[user@]addr[:port]` hop of a `-J` list that isn't a configured host
+fn jump_problem(hop: &str) -> Option<String> {
+    let (user, rest) = match hop.rsplit_once('@') {
+        Some((user, rest)) => (Some(user), rest),
+        None => (None, hop),
+    };
+    if user == Some("") {
+        return Some("nothing before the @".to_string());
+    }
+    let (addr, port) = match rest.strip_prefix('[') {
+        Some(bracketed) => match bracketed.split_once(']') {
+            Some((addr, "")) => (addr, None),
+            Some((addr, port)) => match port.strip_prefix(':') {
+                Some(port) => (addr, Some(port)),
+                None => return Some(format!("unexpected `{port}` after ]")),
+            },
+            None => return Some("missing ]".to_string()),
+        },
+        None if rest.matches(':').count() > 1 => {
+            return Some(format!("put an IPv6 address in brackets, e.g. [{rest}]:22"));
+        }
+        None => match rest.split_once(':') {
+            Some((addr, port)) => (addr, Some(port)),
+            None => (rest, None),
+        },
+    };
+    if addr.is_empty() {
+        return Some("no host or address given".to_string());
+    }
+    if port.is_some_and(|port| !matches!(port.parse::<u16>(), Ok(1..))) {
+        return Some("port must be 1-65535".to_string());
+    }
+    None
+}
+
+// private keys in ~/.ssh, shown as ~/ paths
+fn ssh_keys() -> Vec<String> {
+    let Some(dir) = dirs::home_dir().map(|home| home.join(".ssh")) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let mut keys = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && has_public_half(path))
+        .filter_map(|path| path.file_name().map(|name| format!("~/.ssh/{}", name.to_string_lossy())))
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+fn has_public_half(path: &Path) -> bool {
+    let mut public = path.as_os_str().to_owned();
+    public.push(".pub");
+    PathBuf::from(public).is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_host, FormField, HostForm};
+    use crate::config::{schema::HostProfile, Config};
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [[templates]]
+            name = "prod"
+
+            [[hosts]]
+            name = "gate"
+            addr = "10.0.0.1"
+
+            [[hosts]]
+            name = "web"
+            addr = "10.0.0.2"
+
+            [[groups]]
+            name = "edge"
+            hosts = ["gate"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn host(name: &str) -> HostProfile {
+        HostProfile {
+            name: name.to_string(),
+            addr: "10.0.0.9".to_string(),
+            template: None,
+            port: None,
+            user: None,
+            tor: None,
+            identity: None,
+            jump: None,
+            keepalive: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn errors(host: &HostProfile, editing: Option<usize>) -> Vec<(FormField, String)> {
+        check_host(host, editing, &config())
+    }
+
+    fn error(host: &HostProfile, field: FormField) -> Option<String> {
+        errors(host, None).into_iter().find(|(f, _)| *f == field).map(|(_, message)| message)
+    }
+
+    #[test]
+    fn a_plain_host_passes() {
+        assert!(errors(&host("db"), None).is_empty());
+    }
+
+    #[test]
+    fn rejects_empty_and_spaced_names() {
+        assert_eq!(error(&host(""), FormField::Name).as_deref(), Some("required"));
+        assert_eq!(error(&host("my db"), FormField::Name).as_deref(), Some("no spaces allowed"));
+        let no_addr = HostProfile { addr: String::new(), ..host("db") };
+        assert_eq!(error(&no_addr, FormField::Addr).as_deref(), Some("required"));
+    }
+
+    #[test]
+    fn rejects_names_already_in_use() {
+        assert_eq!(error(&host("web"), FormField::Name).as_deref(), Some("another host already uses this name"));
+        assert_eq!(error(&host("edge"), FormField::Name).as_deref(), Some("a group already uses this name"));
+        // a host keeps its own name when edited
+        assert!(errors(&host("web"), Some(1)).is_empty());
+        assert!(!errors(&host("web"), Some(0)).is_empty());
+    }
+
+    #[test]
+    fn rejects_out_of_range_numbers() {
+        let zero = HostProfile { port: Some(0), keepalive: Some(0), ..host("db") };
+        assert_eq!(error(&zero, FormField::Port).as_deref(), Some("must be 1-65535"));
+        assert_eq!(error(&zero, FormField::Keepalive).as_deref(), Some("must be a positive number of seconds"));
+
+        let mut form = HostForm::new_host();
+        form.name = "db".to_string();
+        form.addr = "10.0.0.9".to_string();
+        for port in ["70000", "-1", "22x"] {
+            form.port = port.to_string();
+            assert!(form.validate(&config()).is_none(), "{port}");
+            assert_eq!(form.error(FormField::Port), Some("must be 1-65535"));
+        }
+        form.port = "2222".to_string();
+        assert_eq!(form.validate(&config()).and_then(|host| host.port), Some(2222));
+    }
+
+    #[test]
+    fn rejects_unknown_templates_and_missing_keys() {
+        let typo = HostProfile { template: Some("prdo".to_string()), ..host("db") };
+        assert_eq!(error(&typo, FormField::Template).as_deref(), Some("no template with this name"));
+        let known = HostProfile { template: Some("prod".to_string()), ..host("db") };
+        assert!(errors(&known, None).is_empty());
+        let identity = HostProfile { identity: Some("/nonexistent/id_ed25519".to_string()), ..host("db") };
+        assert_eq!(error(&identity, FormField::Identity).as_deref(), Some("file not found"));
+    }
+
+    fn jump(spec: &str) -> Option<String> {
+        error(&HostProfile { jump: Some(spec.to_string()), ..host("db") }, FormField::Jump)
+    }
+
+    #[test]
+    fn accepts_jump_hosts_ssh_takes() {
+        for spec in ["gate", "bastion.example.com", "ops@10.0.0.1:2222", "[fe80::1]", "me@[fe80::1]:22", "a:22,b"] {
+            assert_eq!(jump(spec), None, "{spec}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_jump_hosts() {
+        assert_eq!(jump("db").as_deref(), Some("a host can't jump through itself"));
+        assert_eq!(jump("gate web").as_deref(), Some("no spaces allowed"));
+        assert_eq!(jump("@gate").as_deref(), Some("nothing before the @"));
+        assert_eq!(jump("ops@").as_deref(), Some("no host or address given"));
+        assert_eq!(jump("gate:0").as_deref(), Some("port must be 1-65535"));
+        assert_eq!(jump("gate:ssh").as_deref(), Some("port must be 1-65535"));
+        assert_eq!(jump("[fe80::1").as_deref(), Some("missing ]"));
+        assert_eq!(jump("[fe80::1]22").as_deref(), Some("unexpected `22` after ]"));
+        assert_eq!(jump("fe80::1").as_deref(), Some("put an IPv6 address in brackets, e.g. [fe80::1]:22"));
+        assert_eq!(jump("a,,b").as_deref(), Some("no host or address given"));
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashSet;
+
+use crate::{
+    config::{inherit::ResolvedHost, schema::HostProfile, Config},
+    fuzzy::fuzzy_match,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Grouping {
+    Flat,
+    Tag,
+    Group,
+}
+
+impl Grouping {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Grouping::Flat => "all",
+            Grouping::Tag => "by tag",
+            Grouping::Group => "by group",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Grouping::Flat => Grouping::Tag,
+            Grouping::Tag => Grouping::Group,
+            Grouping::Group => Grouping::Flat,
+        }
+    }
+}
+
+pub enum HostRow {
+    Section { label: String, count: usize, collapsed: bool },
+    Host { index: usize },
+}
+
+/// A search result, with the matched char positions in each field.
+pub struct HostHit {
+    pub index: usize,
+    pub score: i64,
+    pub name: Vec<usize>,
+    pub user: Vec<usize>,
+    pub addr: Vec<usize>,
+    pub tags: Vec<Vec<usize>>,
+}
+
+// how the Hosts panel lays out the configured hosts
+pub struct HostView {
+    pub grouping: Grouping,
+    pub tag_filter: Option<String>,
+    pub cursor: usize,
+    /// `Some` while the `/` search box is open.
+    pub search: Option<String>,
+    collapsed: HashSet<(Grouping, String)>,
+}
+
+impl HostView {
+    pub fn new() -> Self {
+        Self {
+            grouping: Grouping::Flat,
+            tag_filter: None,
+            cursor: 0,
+            search: None,
+            collapsed: HashSet::new(),
+        }
+    }
+
+    fn visible(&self, host: &HostProfile) -> bool {
+        self.tag_filter.as_deref().is_none_or(|tag| host.has_tag(tag))
+    }
+
+    /// Hosts matching the search query, best first. Every whitespace
+    /// separated term has to match one of name, addr, user, tags or the
+    /// name of a group the host is in.
+    pub fn search_hits(&self, config: &Config) -> Vec<HostHit> {
+        let query = self.search.as_deref().unwrap_or("");
+        let members = config
+            .groups
+            .iter()
+            .map(|group| (group.name.as_str(), config.group_members(group)))
+            .collect::<Vec<_>>();
+        let mut hits = config
+            .hosts
+            .iter()
+            .enumerate()
+            .filter(|(_, host)| self.visible(host))
+            .filter_map(|(index, host)| {
+                let groups = members
+                    .iter()
+                    .filter(|(_, members)| members.contains(&index))
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>();
+                match_host(index, &config.resolve(host), &groups, query)
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| b.score.cmp(&a.score).then(a.index.cmp(&b.index)));
+        hits
+    }
+
+    pub fn start_search(&mut self) {
+        self.search = Some(String::new());
+        self.cursor = 0;
+    }
+
+    pub fn stop_search(&mut self) {
+        self.search = None;
+        self.cursor = 0;
+    }
+
+    pub fn search_push(&mut self, c: char) {
+        if let Some(query) = self.search.as_mut() {
+            query.push(c);
+            self.cursor = 0;
+        }
+    }
+
+    pub fn search_pop(&mut self) {
+        if let Some(query) = self.search.as_mut() {
+            query.pop();
+            self.cursor = 0;
+        }
+    }
+
+    /// The visible rows, section headers included.
+    pub fn rows(&self, config: &Config) -> Vec<HostRow> {
+        if self.search.is_some() {
+            return self
+                .search_hits(config)
+                .into_iter()
+                .map(|hit| HostRow::Host { index: hit.index })
+                .collect();
+        }
+
+        let visible = |index: &usize| self.visible(&config.hosts[*index]);
+
+        let sections: Vec<(String, Vec<usize>)> = match self.grouping {
+            Grouping::Flat => {
+                return (0..config.hosts.len())
+                    .filter(visible)
+                    .map(|index| HostRow::Host { index })
+                    .collect();
+            }
+            Grouping::Tag => {
+                let mut sections = config
+                    .tags()
+                    .into_iter()
+                    .map(|tag| {
+                        let members = (0..config.hosts.len())
+                            .filter(|index| config.hosts[*index].has_tag(tag))
+                            .collect();
+                        (tag.to_string(), members)
+                    })
+                    .collect::<Vec<_>>();
+                let untagged = (0..config.hosts.len())
+                    .filter(|index| config.hosts[*index].tags.is_empty())
+                    .collect::<Vec<_>>();
+                sections.push(("untagged".to_string(), untagged));
+                sections
+            }
+            Grouping::Group => {
+                let mut grouped = HashSet::new();
+                let mut sections = config
+                    .groups
+                    .iter()
+                    .map(|group| {
+                        let members = config.group_members(group);
+                        grouped.extend(members.iter().copied());
+                        (group.name.clone(), members)
+                    })
+                    .collect::<Vec<_>>();
+                let ungrouped = (0..config.hosts.len())
+                    .filter(|index| !grouped.contains(index))
+                    .collect::<Vec<_>>();
+                sections.push(("ungrouped".to_string(), ungrouped));
+                sections
+            }
+        };
+
+        let mut rows = Vec::new();
+        for (label, members) in sections {
+            let members = members.into_iter().filter(visible).collect::<Vec<_>>();
+            if members.is_empty() {
+                continue;
+            }
+            let collapsed = self.collapsed.contains(&(self.grouping, label.clone()));
+            rows.push(HostRow::Section {
+                label,
+                count: members.len(),
+                collapsed,
+            });
+            if !collapsed {
+                rows.extend(members.into_iter().map(|index| HostRow::Host { index }));
+            }
+        }
+        rows
+    }
+
+    /// The host under the cursor, if the cursor isn't on a section header.
+    pub fn selected_host(&self, config: &Config) -> Option<usize> {
+        match self.rows(config).get(self.cursor) {
+            Some(HostRow::Host { index }) => Some(*index),
+            _ => None,
+        }
+    }
+
+    pub fn next(&mut self, config: &Config) {
+        let len = self.rows(config).len();
+        if len > 0 {
+            self.cursor = (self.cursor + 1) % len;
+        }
+    }
+
+    pub fn previous(&mut self, config: &Config) {
+        let len = self.rows(config).len();
+        self.cursor = match self.cursor {
+            0 => len.saturating_sub(1),
+            n => n - 1,
+        };
+    }
+
+    /// Collapses or expands the section under the cursor; returns false when
+    /// the cursor is on a host instead.
+    pub fn toggle_section(&mut self, config: &Config) -> bool {
+        let Some(HostRow::Section { label, .. }) = self.rows(config).into_iter().nth(self.cursor) else {
+            return false;
+        };
+        let key = (self.grouping, label);
+        if !self.collapsed.remove(&key) {
+            self.collapsed.insert(key);
+        }
+        true
+    }
+
+    pub fn cycle_grouping(&mut self) {
+        self.grouping = self.grouping.next();
+        self.cursor = 0;
+    }
+
+    pub fn cycle_tag_filter(&mut self, config: &Config) {
+        let tags = config.tags();
+        self.tag_filter = match &self.tag_filter {
+            None => tags.first().map(|tag| tag.to_string()),
+            Some(current) => tags
+                .iter()
+                .position(|tag| tag == current)
+                .and_then(|pos| tags.get(pos + 1))
+                .map(|tag| tag.to_string()),
+        };
+        self.cursor = 0;
+    }
+
+    /// Moves the cursor onto a host, e.g. after connecting by name.
+    pub fn focus_host(&mut self, config: &Config, index: usize) {
+        if let Some(pos) = self
+            .rows(config)
+            .iter()
+            .position(|row| matches!(row, HostRow::Host { index: i } if *i == index))
+        {
+            self.cursor = pos;
+        }
+    }
+}
+
+fn match_host(index: usize, host: &ResolvedHost, groups: &[&str], query: &str) -> Option<HostHit> {
+    let mut hit = HostHit {
+        index,
+        score: 0,
+        name: Vec::new(),
+        user: Vec::new(),
+        addr: Vec::new(),
+        tags: vec![Vec::new(); host.tags.len()],
+    };
+
+    for term in query.split_whitespace() {
+        let mut best: Option<i64> = None;
+        let mut record = |score: i64, positions: Vec<usize>, into: &mut Vec<usize>| {
+            best = Some(best.map_or(score, |b| b.max(score)));
+            into.extend(positions);
+        };
+
+        if let Some(m) = fuzzy_match(term, &host.name) {
+            // a hit on the name outranks the same hit elsewhere
+            record(m.score * 2, m.positions, &mut hit.name);
+        }
+        if let Some(m) = host.user.as_deref().and_then(|user| fuzzy_match(term, user)) {
+            record(m.score, m.positions, &mut hit.user);
+        }
+        if let Some(m) = fuzzy_match(term, &host.addr) {
+            record(m.score, m.positions, &mut hit.addr);
+        }
+        for (tag, positions) in host.tags.iter().zip(hit.tags.iter_mut()) {
+            if let Some(m) = fuzzy_match(term, tag) {
+                record(m.score, m.positions, positions);
+            }
+        }
+        // groups aren't shown in the row, so there's nothing to highlight
+        for group in groups {
+            if let Some(m) = fuzzy_match(term, group) {
+                record(m.score, Vec::new(), &mut Vec::new());
+            }
+        }
+
+        hit.score += best?;
+    }
+    Some(hit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grouping, HostRow, HostView};
+    use crate::config::Config;
+
+    const HOSTS: &str = r#"
+        [[hosts]]
+        name = "web-01"
+        addr = "10.0.0.1"
+        user = "deploy"
+        tags = ["prod"]
+
+        [[hosts]]
+        name = "db"
+        addr = "web.internal"
+
+        [[hosts]]
+        name = "café-db"
+        addr = "10.0.0.9"
+        tags = ["staging"]
+
+        [[groups]]
+        name = "payments"
+        hosts = ["db"]
+    "#;
+
+    fn search(query: &str) -> Vec<(String, Vec<usize>, Vec<usize>)> {
+        let config = toml::from_str::<Config>(HOSTS).unwrap();
+        let mut view = HostView::new();
+        view.search = Some(query.to_string());
+        view.search_hits(&config)
+            .into_iter()
+            .map(|hit| (config.hosts[hit.index].name.clone(), hit.name, hit.addr))
+            .collect()
+    }
+
+    fn names(query: &str) -> Vec<String> {
+        search(query).into_iter().map(|(name, _, _)| name).collect()
+    }
+
+    #[test]
+    fn name_hits_outrank_addr_hits() {
+        let hits = search("web");
+        assert_eq!(hits[0], ("web-01".to_string(), vec![0, 1, 2], vec![]));
+        assert_eq!(hits[1], ("db".to_string(), vec![], vec![0, 1, 2]));
+        assert_eq!(hits.len(), 2);
+    }
+
+    #[test]
+    fn matches_user_tags_and_groups() {
+        assert_eq!(names("deploy"), ["web-01"]);
+        assert_eq!(names("staging"), ["café-db"]);
+        assert_eq!(names("payments"), ["db"]);
+    }
+
+    #[test]
+    fn every_term_has_to_match() {
+        assert_eq!(names("db 10.0"), ["café-db"]);
+        assert!(names("web staging").is_empty());
+        assert_eq!(names("").len(), 3);
+    }
+
+    #[test]
+    fn positions_are_chars_in_multibyte_names() {
+        let hits = search("édb");
+        assert_eq!(hits[0].0, "café-db");
+        assert_eq!(hits[0].1, [3, 5, 6]);
+    }
+
+    #[test]
+    fn tag_filter_limits_the_search() {
+        let config = toml::from_str::<Config>(HOSTS).unwrap();
+        let mut view = HostView::new();
+        view.tag_filter = Some("staging".to_string());
+        view.search = Some("db".to_string());
+        let hits = view.search_hits(&config);
+        assert_eq!(hits.iter().map(|hit| hit.index).collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn group_rows_list_the_ungrouped_last() {
+        let config = toml::from_str::<Config>(HOSTS).unwrap();
+        let mut view = HostView::new();
+        view.grouping = Grouping::Group;
+        let rows = view
+            .rows(&config)
+            .into_iter()
+            .map(|row| match row {
+                HostRow::Section { label, count, .. } => format!("{label} ({count})"),
+                HostRow::Host { index } => config.hosts[index].name.clone(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rows, ["payments (1)", "db", "ungrouped (2)", "web-01", "café-db"]);
+    }
+}
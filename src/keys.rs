@@ -0,0 +1,837 @@
+use std::{collections::BTreeMap, fmt};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// The `[keys]` config section: per screen, key chord → action name. Values
+/// are kept as read so one that isn't a string is a warning from the config
+/// check rather than a file that doesn't load.
+pub type KeyTables = BTreeMap<String, BTreeMap<String, toml::Value>>;
+
+/// What `[keys]` can bind a key to in place of an action, dropping the
+/// built-in binding.
+pub const UNBOUND: &str = "none";
+
+/// The key that hands a live session's keyboard back to mangsh for one
+/// `[keys.session]` binding, unless `prefix_key` says otherwise.
+pub const DEFAULT_PREFIX: KeyChord = KeyChord {
+    code: KeyCode::Char('a'),
+    modifiers: KeyModifiers::CONTROL,
+};
+
+/// Something a key can do.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    OpenHosts,
+    OpenConfig,
+    OpenMenu,
+    OpenFleet,
+    NextTheme,
+    PreviousTheme,
+    Back,
+    NextTab,
+    PreviousTab,
+    Up,
+    Down,
+    Select,
+    Search,
+    CycleGrouping,
+    CycleTagFilter,
+    AddHost,
+    EditHost,
+    DeleteHost,
+    Backups,
+    ImportTheme,
+    EditTheme,
+    NextField,
+    PreviousField,
+    Toggle,
+    SelectAll,
+    NextGroup,
+    Increase,
+    Decrease,
+    IncreaseMore,
+    DecreaseMore,
+    ToggleColorModel,
+    EnterColor,
+    ResetColor,
+    Save,
+    CommandPalette,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 37] = [
+        Action::Quit,
+        Action::OpenHosts,
+        Action::OpenConfig,
+        Action::OpenMenu,
+        Action::OpenFleet,
+        Action::NextTheme,
+        Action::PreviousTheme,
+        Action::Back,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::Up,
+        Action::Down,
+        Action::Select,
+        Action::Search,
+        Action::CycleGrouping,
+        Action::CycleTagFilter,
+        Action::AddHost,
+        Action::EditHost,
+        Action::DeleteHost,
+        Action::Backups,
+        Action::ImportTheme,
+        Action::EditTheme,
+        Action::NextField,
+        Action::PreviousField,
+        Action::Toggle,
+        Action::SelectAll,
+        Action::NextGroup,
+        Action::Increase,
+        Action::Decrease,
+        Action::IncreaseMore,
+        Action::DecreaseMore,
+        Action::ToggleColorModel,
+        Action::EnterColor,
+        Action::ResetColor,
+        Action::Save,
+        Action::CommandPalette,
+        Action::Help,
+    ];
+
+    /// The name used in `[keys]`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::OpenHosts => "open-hosts",
+            Action::OpenConfig => "open-config",
+            Action::OpenMenu => "open-menu",
+            Action::OpenFleet => "open-fleet",
+            Action::NextTheme => "next-theme",
+            Action::PreviousTheme => "previous-theme",
+            Action::Back => "back",
+            Action::NextTab => "next-tab",
+            Action::PreviousTab => "previous-tab",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Select => "select",
+            Action::Search => "search",
+            Action::CycleGrouping => "cycle-grouping",
+            Action::CycleTagFilter => "cycle-tag-filter",
+            Action::AddHost => "add-host",
+            Action::EditHost => "edit-host",
+            Action::DeleteHost => "delete-host",
+            Action::Backups => "backups",
+            Action::ImportTheme => "import-theme",
+            Action::EditTheme => "edit-theme",
+            Action::NextField => "next-field",
+            Action::PreviousField => "previous-field",
+            Action::Toggle => "toggle",
+            Action::SelectAll => "select-all",
+            Action::NextGroup => "next-group",
+            Action::Increase => "increase",
+            Action::Decrease => "decrease",
+            Action::IncreaseMore => "increase-more",
+            Action::DecreaseMore => "decrease-more",
+            Action::ToggleColorModel => "toggle-color-model",
+            Action::EnterColor => "enter-color",
+            Action::ResetColor => "reset-color",
+            Action::Save => "save",
+            Action::CommandPalette => "command-palette",
+            Action::Help => "help",
+        }
+    }
+
+    /// What the command palette calls it.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit mangsh",
+            Action::OpenHosts => "Open hosts",
+            Action::OpenConfig => "Open config",
+            Action::OpenMenu => "Open menu",
+            Action::OpenFleet => "Open fleet runner",
+            Action::NextTheme => "Next theme",
+            Action::PreviousTheme => "Previous theme",
+            Action::Back => "Back",
+            Action::NextTab => "Next tab",
+            Action::PreviousTab => "Previous tab",
+            Action::Up => "Move up",
+            Action::Down => "Move down",
+            Action::Select => "Select",
+            Action::Search => "Search hosts",
+            Action::CycleGrouping => "Cycle host grouping",
+            Action::CycleTagFilter => "Filter hosts by tag",
+            Action::AddHost => "Add host",
+            Action::EditHost => "Edit selected host",
+            Action::DeleteHost => "Delete selected host",
+            Action::Backups => "Restore a config backup",
+            Action::ImportTheme => "Import a color scheme",
+            Action::EditTheme => "Edit theme colors",
+            Action::NextField => "Next field",
+            Action::PreviousField => "Previous field",
+            Action::Toggle => "Toggle selected host",
+            Action::SelectAll => "Select all hosts",
+            Action::NextGroup => "Select next group",
+            Action::Increase => "Increase slider",
+            Action::Decrease => "Decrease slider",
+            Action::IncreaseMore => "Increase slider by 10",
+            Action::DecreaseMore => "Decrease slider by 10",
+            Action::ToggleColorModel => "Switch between RGB and HSL",
+            Action::EnterColor => "Type a color",
+            Action::ResetColor => "Reset color",
+            Action::Save => "Save theme as…",
+            Action::CommandPalette => "Command palette",
+            Action::Help => "Show key bindings",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    // actions that mean the same thing on every screen, so they can be bound
+    // under `[keys.global]` or on any screen
+    fn is_global(&self) -> bool {
+        matches!(
+            self,
+            Action::Quit
+                | Action::OpenHosts
+                | Action::OpenConfig
+                | Action::OpenMenu
+                | Action::OpenFleet
+                | Action::NextTheme
+                | Action::PreviousTheme
+                | Action::CommandPalette
+                | Action::Help
+        )
+    }
+}
+
+/// Where a binding applies: a screen, a live session (after the prefix
+/// key), or everywhere.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scope {
+    Global,
+    Title,
+    Main,
+    Session,
+    Config,
+    Menu,
+    Fleet,
+    Editor,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 8] = [
+        Scope::Global,
+        Scope::Title,
+        Scope::Main,
+        Scope::Session,
+        Scope::Config,
+        Scope::Menu,
+        Scope::Fleet,
+        Scope::Editor,
+    ];
+
+    /// The table name under `[keys]`.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Scope::Global => "global",
+            Scope::Title => "title",
+            Scope::Main => "main",
+            Scope::Session => "session",
+            Scope::Config => "config",
+            Scope::Menu => "menu",
+            Scope::Fleet => "fleet",
+            Scope::Editor => "editor",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Scope::Global => "every screen",
+            Scope::Title => "the title screen",
+            Scope::Main => "the main screen",
+            Scope::Session => "live sessions",
+            Scope::Config => "the config screen",
+            Scope::Menu => "the menu",
+            Scope::Fleet => "the fleet runner",
+            Scope::Editor => "the theme editor",
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|scope| scope.key() == key)
+    }
+
+    /// Whether `action` does anything here.
+    pub fn has(&self, action: Action) -> bool {
+        action.is_global() || DEFAULTS.iter().any(|(scope, _, default)| scope == self && *default == action)
+    }
+}
+
+// the built-in bindings; `[keys]` entries replace them chord by chord
+const DEFAULTS: &[(Scope, &str, Action)] = &[
+    (Scope::Global, "q", Action::Quit),
+    (Scope::Global, "ctrl-p", Action::CommandPalette),
+    (Scope::Global, "?", Action::Help),
+    (Scope::Title, "enter", Action::OpenHosts),
+    (Scope::Title, "m", Action::OpenMenu),
+    (Scope::Title, "M", Action::OpenMenu),
+    (Scope::Main, "c", Action::OpenConfig),
+    (Scope::Main, "m", Action::OpenMenu),
+    (Scope::Main, "M", Action::OpenMenu),
+    (Scope::Main, "f", Action::OpenFleet),
+    (Scope::Main, "right", Action::NextTab),
+    (Scope::Main, "l", Action::NextTab),
+    (Scope::Main, "left", Action::PreviousTab),
+    (Scope::Main, "h", Action::PreviousTab),
+    (Scope::Main, "down", Action::Down),
+    (Scope::Main, "j", Action::Down),
+    (Scope::Main, "up", Action::Up),
+    (Scope::Main, "k", Action::Up),
+    (Scope::Main, "enter", Action::Select),
+    (Scope::Main, "g", Action::CycleGrouping),
+    (Scope::Main, "t", Action::CycleTagFilter),
+    (Scope::Main, "/", Action::Search),
+    (Scope::Main, "a", Action::AddHost),
+    (Scope::Main, "e", Action::EditHost),
+    (Scope::Main, "d", Action::DeleteHost),
+    (Scope::Session, "right", Action::NextTab),
+    (Scope::Session, "l", Action::NextTab),
+    (Scope::Session, "left", Action::PreviousTab),
+    (Scope::Session, "h", Action::PreviousTab),
+    (Scope::Session, "c", Action::OpenConfig),
+    (Scope::Session, "m", Action::OpenMenu),
+    (Scope::Session, "f", Action::OpenFleet),
+    (Scope::Session, "q", Action::Quit),
+    (Scope::Session, "p", Action::CommandPalette),
+    (Scope::Session, "?", Action::Help),
+    (Scope::Config, "b", Action::Backups),
+    (Scope::Config, "i", Action::ImportTheme),
+    (Scope::Config, "e", Action::EditTheme),
+    (Scope::Config, "m", Action::OpenMenu),
+    (Scope::Config, "M", Action::OpenMenu),
+    (Scope::Config, "right", Action::NextTheme),
+    (Scope::Config, "l", Action::NextTheme),
+    (Scope::Config, "left", Action::PreviousTheme),
+    (Scope::Config, "h", Action::PreviousTheme),
+    (Scope::Config, "down", Action::Down),
+    (Scope::Config, "j", Action::Down),
+    (Scope::Config, "up", Action::Up),
+    (Scope::Config, "k", Action::Up),
+    (Scope::Menu, "1", Action::OpenHosts),
+    (Scope::Menu, "enter", Action::OpenHosts),
+    (Scope::Menu, "2", Action::OpenConfig),
+    (Scope::Menu, "c", Action::OpenConfig),
+    (Scope::Menu, "3", Action::OpenFleet),
+    (Scope::Menu, "f", Action::OpenFleet),
+    (Scope::Menu, "esc", Action::Back),
+    (Scope::Fleet, "esc", Action::Back),
+    (Scope::Fleet, "tab", Action::NextField),
+    (Scope::Fleet, "down", Action::Down),
+    (Scope::Fleet, "j", Action::Down),
+    (Scope::Fleet, "up", Action::Up),
+    (Scope::Fleet, "k", Action::Up),
+    (Scope::Fleet, "enter", Action::Select),
+    (Scope::Fleet, "space", Action::Toggle),
+    (Scope::Fleet, "a", Action::SelectAll),
+    (Scope::Fleet, "g", Action::NextGroup),
+    (Scope::Fleet, "m", Action::OpenMenu),
+    (Scope::Fleet, "M", Action::OpenMenu),
+    (Scope::Editor, "esc", Action::Back),
+    (Scope::Editor, "down", Action::Down),
+    (Scope::Editor, "j", Action::Down),
+    (Scope::Editor, "up", Action::Up),
+    (Scope::Editor, "k", Action::Up),
+    (Scope::Editor, "tab", Action::NextField),
+    (Scope::Editor, "shift-tab", Action::PreviousField),
+    (Scope::Editor, "right", Action::Increase),
+    (Scope::Editor, "l", Action::Increase),
+    (Scope::Editor, "left", Action::Decrease),
+    (Scope::Editor, "h", Action::Decrease),
+    (Scope::Editor, "shift-right", Action::IncreaseMore),
+    (Scope::Editor, "L", Action::IncreaseMore),
+    (Scope::Editor, "shift-left", Action::DecreaseMore),
+    (Scope::Editor, "H", Action::DecreaseMore),
+    (Scope::Editor, "m", Action::ToggleColorModel),
+    (Scope::Editor, "#", Action::EnterColor),
+    (Scope::Editor, "x", Action::EnterColor),
+    (Scope::Editor, "r", Action::ResetColor),
+    (Scope::Editor, "s", Action::Save),
+    (Scope::Editor, "enter", Action::Save),
+];
+
+// names for the keys that aren't a single character
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("space", KeyCode::Char(' ')),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("insert", KeyCode::Insert),
+    ("delete", KeyCode::Delete),
+];
+
+const ALIASES: &[(&str, &str)] = &[
+    ("return", "enter"),
+    ("escape", "esc"),
+    ("pgup", "pageup"),
+    ("pgdn", "pagedown"),
+    ("del", "delete"),
+];
+
+/// A key plus the Ctrl/Alt/Shift held with it, e.g. `ctrl-a` or `shift-tab`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        // terminals fold Shift into the character (and into BackTab), so
+        // `shift-a` and `A` are the same key
+        let code = match code {
+            // and Ctrl+letter is one byte either way
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) && c.is_ascii_alphabetic() => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) || c.is_uppercase() => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    pub fn from_event(key: &KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+
+    /// Reads `q`, `M`, `enter`, `ctrl-a`, `alt+left`, `shift-tab`, `f5`...
+    /// Modifier and key names are case-insensitive; single characters
+    /// aren't.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let trimmed = text.trim();
+        if trimmed.chars().count() == 1 {
+            let c = trimmed.chars().next().unwrap_or_default();
+            return Ok(Self::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        // the key itself may be `-` or `+`, as in `ctrl--`
+        let split = match trimmed.char_indices().rev().nth(1) {
+            Some((index, '-' | '+')) if trimmed.len() > 2 => index,
+            _ => trimmed.rfind(['-', '+']).unwrap_or(0),
+        };
+        let (prefix, key) = match split {
+            0 => ("", trimmed),
+            index => (&trimmed[..index], &trimmed[index + 1..]),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for name in prefix.split(['-', '+']).filter(|name| !name.is_empty() || !prefix.is_empty()) {
+            modifiers |= match name.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier `{name}` in `{text}`; use ctrl, alt or shift")),
+            };
+        }
+        if key.chars().count() == 1 {
+            let c = key.chars().next().unwrap_or_default();
+            return Ok(Self::new(KeyCode::Char(c), modifiers));
+        }
+
+        let name = key.to_lowercase();
+        let name = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map_or(name.as_str(), |(_, name)| name);
+        if let Some((_, code)) = NAMED_KEYS.iter().find(|(known, _)| *known == name) {
+            return Ok(Self::new(*code, modifiers));
+        }
+        if name == "backtab" {
+            return Ok(Self::new(KeyCode::BackTab, modifiers));
+        }
+        if let Some(number) = name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok())
+            && (1..=12).contains(&number)
+        {
+            return Ok(Self::new(KeyCode::F(number), modifiers));
+        }
+        Err(format!("`{text}` isn't a key"))
+    }
+
+    /// Whether a remote shell would get something from this key; those
+    /// are better left alone in sessions.
+    pub fn is_plain(&self) -> bool {
+        !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+
+    /// Short form for hints, e.g. `Ctrl+a` or `Ctrl+←`.
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                label.push_str(name);
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => label.push_str("Space"),
+            KeyCode::Char(c) => label.push(c),
+            KeyCode::Enter => label.push_str("Enter"),
+            KeyCode::Esc => label.push_str("Esc"),
+            KeyCode::Tab => label.push_str("Tab"),
+            KeyCode::BackTab => label.push_str("Shift+Tab"),
+            KeyCode::Backspace => label.push_str("Backspace"),
+            KeyCode::Up => label.push('↑'),
+            KeyCode::Down => label.push('↓'),
+            KeyCode::Left => label.push('←'),
+            KeyCode::Right => label.push('→'),
+            KeyCode::Home => label.push_str("Home"),
+            KeyCode::End => label.push_str("End"),
+            KeyCode::PageUp => label.push_str("PgUp"),
+            KeyCode::PageDown => label.push_str("PgDn"),
+            KeyCode::Insert => label.push_str("Ins"),
+            KeyCode::Delete => label.push_str("Del"),
+            KeyCode::F(number) => label.push_str(&format!("F{number}")),
+            _ => label.push('?'),
+        }
+        label
+    }
+}
+
+/// The `[keys]` spelling, e.g. `ctrl-left`.
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl-"),
+            (KeyModifiers::ALT, "alt-"),
+            (KeyModifiers::SHIFT, "shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::BackTab => f.write_str("shift-tab"),
+            KeyCode::F(number) => write!(f, "f{number}"),
+            code => {
+                let name = NAMED_KEYS.iter().find(|(_, known)| *known == code).map_or("?", |(name, _)| name);
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+struct Binding {
+    scope: Scope,
+    chord: KeyChord,
+    action: Action,
+    /// The `[keys]` entry as written; `None` for a built-in binding.
+    configured: Option<String>,
+}
+
+/// Two bindings that can't both hold, found while building a keymap.
+pub struct KeyConflict {
+    pub scope: Scope,
+    /// The `[keys]` entry to point at, as written.
+    pub key: String,
+    pub message: String,
+}
+
+/// Every key binding in effect: the built-ins with `[keys]` applied.
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    /// Arms `[keys.session]` in a live session; pressed twice, it's sent
+    /// through.
+    pub prefix: KeyChord,
+}
+
+impl Keymap {
+    /// `prefix` is the configured `prefix_key`; a bad one falls back to the
+    /// default.
+    pub fn new(tables: &KeyTables, prefix: Option<&str>) -> Self {
+        Self::build(tables, prefix).0
+    }
+
+    /// Problems with `tables` that only show once they're combined, e.g.
+    /// two spellings of one key bound to different things, or a screen
+    /// binding hiding a global one. Bad names are left to the config check.
+    pub fn conflicts(tables: &KeyTables, prefix: Option<&str>) -> Vec<KeyConflict> {
+        Self::build(tables, prefix).1
+    }
+
+    fn build(tables: &KeyTables, prefix: Option<&str>) -> (Self, Vec<KeyConflict>) {
+        let prefix = prefix.and_then(|key| KeyChord::parse(key).ok()).unwrap_or(DEFAULT_PREFIX);
+        let mut bindings = DEFAULTS
+            .iter()
+            .filter_map(|(scope, key, action)| {
+                let chord = KeyChord::parse(key).ok()?;
+                Some(Binding { scope: *scope, chord, action: *action, configured: None })
+            })
+            .collect::<Vec<_>>();
+        let mut conflicts = Vec::new();
+
+        for (scope_key, table) in tables {
+            let Some(scope) = Scope::parse(scope_key) else {
+                continue;
+            };
+            let mut seen: Vec<(KeyChord, &str, &str)> = Vec::new();
+            for (key, value) in table {
+                let (Ok(chord), Some(name)) = (KeyChord::parse(key), value.as_str()) else {
+                    continue;
+                };
+                if let Some((_, other, other_name)) = seen.iter().find(|(seen, _, _)| *seen == chord)
+                    && *other_name != name
+                {
+                    conflicts.push(KeyConflict {
+                        scope,
+                        key: key.clone(),
+                        message: format!(
+                            "`{key}` and `{other}` are the same key in [keys.{}], bound to `{name}` and `{other_name}`",
+                            scope.key()
+                        ),
+                    });
+                }
+                seen.push((chord, key, name));
+
+                let action = match name {
+                    UNBOUND => None,
+                    name => match Action::parse(name) {
+                        Some(action) if scope.has(action) => Some(action),
+                        _ => continue,
+                    },
+                };
+                if scope == Scope::Session && chord == prefix && name != UNBOUND {
+                    conflicts.push(KeyConflict {
+                        scope,
+                        key: key.clone(),
+                        message: format!("`{key}` is the prefix key; pressed after itself it goes to the session"),
+                    });
+                }
+                bindings.retain(|binding| binding.scope != scope || binding.chord != chord);
+                if let Some(action) = action {
+                    bindings.push(Binding { scope, chord, action, configured: Some(key.clone()) });
+                }
+            }
+        }
+
+        // a screen's own binding wins over a global one, which is fine for
+        // the built-ins but a surprise when either side was configured
+        for global in bindings.iter().filter(|binding| binding.scope == Scope::Global) {
+            for local in &bindings {
+                if local.scope == Scope::Global
+                    || local.scope == Scope::Session
+                    || local.chord != global.chord
+                    || local.action == global.action
+                {
+                    continue;
+                }
+                let (scope, key) = match (&local.configured, &global.configured) {
+                    (Some(key), _) => (local.scope, key.clone()),
+                    (None, Some(key)) => (Scope::Global, key.clone()),
+                    (None, None) => continue,
+                };
+                conflicts.push(KeyConflict {
+                    scope,
+                    key,
+                    message: format!(
+                        "`{}` is `{}` on every screen but `{}` on {}, which wins there",
+                        global.chord,
+                        global.action.name(),
+                        local.action.name(),
+                        local.scope.label()
+                    ),
+                });
+            }
+        }
+        (Self { bindings, prefix }, conflicts)
+    }
+
+    /// The keys that do `action` in `scope`, its own bindings first, then
+    /// global ones it doesn't override.
+    pub fn keys(&self, scope: Scope, action: Action) -> Vec<KeyChord> {
+        let own = self.bindings.iter().filter(|binding| binding.scope == scope);
+        let mut keys = own
+            .clone()
+            .filter(|binding| binding.action == action)
+            .map(|binding| binding.chord)
+            .collect::<Vec<_>>();
+        if scope != Scope::Session && scope != Scope::Global {
+            let global = self
+                .bindings
+                .iter()
+                .filter(|binding| binding.scope == Scope::Global && binding.action == action)
+                .filter(|binding| !own.clone().any(|local| local.chord == binding.chord))
+                .map(|binding| binding.chord);
+            keys.extend(global);
+        }
+        keys
+    }
+
+    /// What `key` does in `scope`. Global bindings apply on every screen
+    /// but not in live sessions, which only look at `[keys.session]` once
+    /// the prefix is pressed.
+    pub fn action(&self, scope: Scope, key: &KeyEvent) -> Option<Action> {
+        let chord = KeyChord::from_event(key);
+        let find = |scope: Scope| {
+            self.bindings
+                .iter()
+                .find(|binding| binding.scope == scope && binding.chord == chord)
+                .map(|binding| binding.action)
+        };
+        match scope {
+            Scope::Session | Scope::Global => find(scope),
+            scope => find(scope).or_else(|| find(Scope::Global)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Action, KeyChord, KeyTables, Keymap, Scope};
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord { code, modifiers }
+    }
+
+    fn tables(source: &str) -> KeyTables {
+        toml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn parses_single_characters_as_written() {
+        assert_eq!(KeyChord::parse("q"), Ok(chord(KeyCode::Char('q'), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("M"), Ok(chord(KeyCode::Char('M'), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("-"), Ok(chord(KeyCode::Char('-'), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse(" ? "), Ok(chord(KeyCode::Char('?'), KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn parses_modifiers_and_named_keys() {
+        assert_eq!(KeyChord::parse("ctrl-a"), Ok(chord(KeyCode::Char('a'), KeyModifiers::CONTROL)));
+        assert_eq!(KeyChord::parse("Alt+Left"), Ok(chord(KeyCode::Left, KeyModifiers::ALT)));
+        assert_eq!(
+            KeyChord::parse("ctrl-shift-pgup"),
+            Ok(chord(KeyCode::PageUp, KeyModifiers::CONTROL | KeyModifiers::SHIFT))
+        );
+        assert_eq!(KeyChord::parse("return"), Ok(chord(KeyCode::Enter, KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("f12"), Ok(chord(KeyCode::F(12), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("ctrl--"), Ok(chord(KeyCode::Char('-'), KeyModifiers::CONTROL)));
+        assert_eq!(KeyChord::parse("alt++"), Ok(chord(KeyCode::Char('+'), KeyModifiers::ALT)));
+    }
+
+    #[test]
+    fn folds_shift_into_the_key() {
+        let upper = chord(KeyCode::Char('A'), KeyModifiers::NONE);
+        assert_eq!(KeyChord::parse("shift-a"), Ok(upper));
+        assert_eq!(KeyChord::parse("A"), Ok(upper));
+        assert_eq!(KeyChord::parse("ctrl-A"), KeyChord::parse("ctrl-a"));
+        assert_eq!(KeyChord::parse("ctrl-shift-a"), KeyChord::parse("ctrl-a"));
+        assert_eq!(KeyChord::parse("shift-tab"), Ok(chord(KeyCode::BackTab, KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("backtab"), KeyChord::parse("shift-tab"));
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!(KeyChord::parse("hyper-a").unwrap_err().contains("unknown modifier `hyper`"));
+        assert!(KeyChord::parse("ctrl-").is_err());
+        assert!(KeyChord::parse("f13").is_err());
+        assert!(KeyChord::parse("enterr").is_err());
+        assert!(KeyChord::parse("").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for text in ["q", "ctrl-a", "alt-left", "shift-tab", "f5", "space", "ctrl-shift-pageup"] {
+            let parsed = KeyChord::parse(text).unwrap();
+            assert_eq!(parsed.to_string(), text);
+            assert_eq!(KeyChord::parse(&parsed.to_string()), Ok(parsed));
+        }
+    }
+
+    #[test]
+    fn configured_keys_replace_the_built_ins() {
+        let keymap = Keymap::new(&tables("[main]\nn = \"add-host\"\na = \"none\"\n"), None);
+        let event = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(keymap.action(Scope::Main, &event('n')), Some(Action::AddHost));
+        assert_eq!(keymap.action(Scope::Main, &event('a')), None);
+        assert_eq!(keymap.keys(Scope::Main, Action::AddHost), vec![KeyChord::parse("n").unwrap()]);
+        // global bindings reach screens but not sessions
+        assert_eq!(keymap.action(Scope::Main, &event('q')), Some(Action::Quit));
+        assert_eq!(keymap.action(Scope::Session, &event('n')), None);
+    }
+
+    #[test]
+    fn built_ins_do_not_conflict() {
+        assert!(Keymap::conflicts(&KeyTables::new(), None).is_empty());
+    }
+
+    #[test]
+    fn finds_two_spellings_of_one_key() {
+        let conflicts = Keymap::conflicts(&tables("[main]\n\"ctrl-x\" = \"search\"\n\"ctrl-X\" = \"add-host\"\n"), None);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].scope, Scope::Main);
+        assert!(conflicts[0].message.contains("are the same key in [keys.main]"));
+
+        let same_action = tables("[main]\n\"ctrl-x\" = \"search\"\n\"ctrl-X\" = \"search\"\n");
+        assert!(Keymap::conflicts(&same_action, None).is_empty());
+    }
+
+    #[test]
+    fn finds_screen_bindings_hiding_global_ones() {
+        let conflicts = Keymap::conflicts(&tables("[global]\nz = \"open-fleet\"\n[main]\nz = \"search\"\n"), None);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].scope, conflicts[0].key.as_str()), (Scope::Main, "z"));
+        assert!(conflicts[0].message.contains("`z` is `open-fleet` on every screen but `search` on the main screen"));
+
+        // a built-in screen key hiding a configured global one points at the global entry
+        let conflicts = Keymap::conflicts(&tables("[global]\nj = \"open-fleet\"\n"), None);
+        assert!(conflicts.iter().all(|conflict| conflict.scope == Scope::Global && conflict.key == "j"));
+        assert!(!conflicts.is_empty());
+    }
+
+    #[test]
+    fn finds_session_bindings_on_the_prefix() {
+        let conflicts = Keymap::conflicts(&tables("[session]\n\"ctrl-b\" = \"quit\"\n"), Some("ctrl-b"));
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].message.contains("is the prefix key"));
+        assert!(Keymap::conflicts(&tables("[session]\n\"ctrl-b\" = \"quit\"\n"), None).is_empty());
+    }
+
+    #[test]
+    fn skips_values_that_are_not_names() {
+        let keymap = Keymap::new(&tables("[main]\nx = 5\n"), None);
+        let x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(Scope::Main, &x), None);
+    }
+}
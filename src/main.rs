@@ -1,9 +1,22 @@
 mod app;
+mod cli;
+mod clipboard;
+mod command_palette;
 mod config;
+mod fleet;
+mod fuzzy;
+mod host_form;
+mod hosts;
+mod keys;
+mod ssh;
 mod theme;
+mod theme_editor;
 mod ui;
 
-fn main() -> anyhow::Result<()> {
-    let mut app = app::App::new();
-    app.run()
+use std::process::ExitCode;
+
+use clap::Parser;
+
+fn main() -> anyhow::Result<ExitCode> {
+    cli::run(cli::Cli::parse())
 }
@@ -0,0 +1,133 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    process::{Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::config::inherit::ResolvedHost;
+
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Progress of a multi-host exec; `index` refers to the host list it was
+/// started with.
+pub enum ExecEvent {
+    Started { index: usize },
+    Output { index: usize, stream: Stream, line: String },
+    Finished { index: usize, outcome: ExecOutcome },
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecOutcome {
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+impl ExecOutcome {
+    pub fn success(&self) -> bool {
+        self.error.is_none() && self.exit_code == Some(0)
+    }
+}
+
+/// Runs `command` on every host over a non-interactive ssh channel, at most
+/// `concurrency` at a time. The receiver closes once every host finished.
+pub fn spawn_exec(hosts: Vec<ResolvedHost>, command: Vec<String>, concurrency: usize) -> Receiver<ExecEvent> {
+    let (tx, rx) = mpsc::channel();
+    let workers = concurrency.clamp(1, hosts.len().max(1));
+    let queue = Arc::new(Mutex::new(hosts.into_iter().enumerate().collect::<VecDeque<_>>()));
+    let command = Arc::new(command);
+
+    for _ in 0..workers {
+        let queue = Arc::clone(&queue);
+        let command = Arc::clone(&command);
+        let tx = tx.clone();
+        thread::spawn(move || {
+            while let Some((index, host)) = queue.lock().ok().and_then(|mut q| q.pop_front()) {
+                if tx.send(ExecEvent::Started { index }).is_err() {
+                    break;
+                }
+                let outcome = run_one(index, &host, &command, &tx);
+                if tx.send(ExecEvent::Finished { index, outcome }).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    rx
+}
+
+fn run_one(index: usize, host: &ResolvedHost, command: &[String], tx: &Sender<ExecEvent>) -> ExecOutcome {
+    let started = Instant::now();
+    let spawned = Command::new("ssh")
+        .arg("-T")
+        .args(["-o", "BatchMode=yes"])
+        // the `--` before the destination already ends option parsing, so
+        // the command goes through as is
+        .args(super::ssh_args(host))
+        .args(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(err) => {
+            return ExecOutcome {
+                exit_code: None,
+                duration: started.elapsed(),
+                error: Some(format!("failed to start ssh: {err}")),
+            };
+        }
+    };
+
+    let readers = [
+        child.stdout.take().map(|out| forward_lines(index, Stream::Stdout, out, tx.clone())),
+        child.stderr.take().map(|err| forward_lines(index, Stream::Stderr, err, tx.clone())),
+    ];
+    let status = child.wait();
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+
+    match status {
+        Ok(status) => ExecOutcome {
+            exit_code: status.code(),
+            duration: started.elapsed(),
+            error: status.code().is_none().then(|| "terminated by signal".to_string()),
+        },
+        Err(err) => ExecOutcome {
+            exit_code: None,
+            duration: started.elapsed(),
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+fn forward_lines(
+    index: usize,
+    stream: Stream,
+    source: impl Read + Send + 'static,
+    tx: Sender<ExecEvent>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(source).lines().map_while(Result::ok) {
+            if tx.send(ExecEvent::Output { index, stream, line }).is_err() {
+                break;
+            }
+        }
+    })
+}
@@ -1,2 +1,15 @@
+pub mod exec;
 pub mod normal;
+pub mod session;
 pub mod tor;
+
+use crate::config::inherit::ResolvedHost;
+
+/// Arguments passed to the system `ssh` binary for a resolved host.
+pub fn ssh_args(profile: &ResolvedHost) -> Vec<String> {
+    if profile.tor {
+        tor::ssh_args(profile)
+    } else {
+        normal::ssh_args(profile)
+    }
+}
@@ -1,3 +1,23 @@
-pub fn connect_normal(host: &str) {
-    println!("[SSH] connecting normally to {}", host);
+use crate::config::inherit::ResolvedHost;
+
+pub fn ssh_args(profile: &ResolvedHost) -> Vec<String> {
+    let mut args = vec!["-p".to_string(), profile.port.to_string()];
+    if let Some(identity) = profile.identity_path() {
+        args.push("-i".to_string());
+        args.push(identity.display().to_string());
+    }
+    // tor's ProxyCommand would win over -J anyway; the config check warns
+    // about hosts that set both
+    if let Some(jump) = profile.jump.as_ref().filter(|_| !profile.tor) {
+        args.push("-J".to_string());
+        args.push(jump.clone());
+    }
+    if let Some(interval) = profile.keepalive {
+        args.push("-o".to_string());
+        args.push(format!("ServerAliveInterval={interval}"));
+    }
+    // an addr starting with `-` mustn't be read as an option
+    args.push("--".to_string());
+    args.push(profile.destination());
+    args
 }
@@ -0,0 +1,496 @@
+use std::{
+    io::{Read, Write},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use vt100::{MouseProtocolEncoding, MouseProtocolMode};
+
+use crate::config::inherit::ResolvedHost;
+
+const SCROLLBACK_LINES: usize = 1000;
+// an echo slower than this is something else answering, e.g. the shell
+// after a password prompt that never echoed
+const MAX_ECHO: Duration = Duration::from_secs(2);
+// what ssh and the remote side ask before there's a session to speak of
+const LOGIN_PROMPTS: &[&str] = &["password", "passphrase", "(yes/no", "verification code", "one-time"];
+
+// an interactive ssh process running inside a local pty
+pub struct Session {
+    pub host: String,
+    pub parser: vt100::Parser,
+    pub status: Option<String>,
+    /// Text picked out with the mouse, in screen cells.
+    pub selection: Option<Selection>,
+    pub tor: bool,
+    pub jump: Option<String>,
+    /// How long the last typed character took to be echoed back, which is
+    /// about one round trip to the remote host.
+    pub latency: Option<Duration>,
+    // any output yet, and output past the host key and password prompts
+    replied: bool,
+    connected: bool,
+    // a typed character being timed: when it went out and the bytes its
+    // echo has to contain
+    pending_echo: Option<(Instant, Vec<u8>)>,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    // stamped as it's read, so latency doesn't depend on when it's pumped
+    output: Receiver<(Instant, Vec<u8>)>,
+}
+
+impl Session {
+    pub fn spawn(profile: &ResolvedHost, rows: u16, cols: u16) -> Result<Self> {
+        let pair = native_pty_system().openpty(pty_size(rows, cols))?;
+
+        let mut cmd = CommandBuilder::new("ssh");
+        cmd.arg("-tt");
+        cmd.args(super::ssh_args(profile));
+        let child = pair.slave.spawn_command(cmd)?;
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let (tx, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if tx.send((Instant::now(), buf[..n].to_vec())).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            host: profile.name.clone(),
+            parser: vt100::Parser::new(rows, cols, SCROLLBACK_LINES),
+            status: None,
+            selection: None,
+            tor: profile.tor,
+            jump: profile.jump.clone(),
+            latency: None,
+            replied: false,
+            connected: false,
+            pending_echo: None,
+            master: pair.master,
+            writer,
+            child,
+            output,
+        })
+    }
+
+    /// Feeds pending remote output into the terminal emulator.
+    pub fn pump(&mut self) {
+        let mut received = false;
+        while let Ok((at, bytes)) = self.output.try_recv() {
+            received = true;
+            // only output read after the key went out and carrying its echo
+            // counts; a redraw that happens to come next doesn't
+            if let Some((sent, echo)) = &self.pending_echo
+                && at >= *sent
+                && bytes.windows(echo.len()).any(|window| window == echo.as_slice())
+            {
+                let took = at - *sent;
+                if took <= MAX_ECHO {
+                    self.latency = Some(took);
+                }
+                self.pending_echo = None;
+            }
+            self.parser.process(&bytes);
+        }
+        if self.pending_echo.as_ref().is_some_and(|(sent, _)| sent.elapsed() > MAX_ECHO) {
+            self.pending_echo = None;
+        }
+        if received {
+            self.replied = true;
+            self.connected = self.connected || !self.at_login_prompt();
+        }
+        if self.status.is_none()
+            && let Ok(Some(exit)) = self.child.try_wait()
+        {
+            self.status = Some(format!("closed (exit {})", exit.exit_code()));
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status.is_none()
+    }
+
+    /// How the connection is doing, for the status bar.
+    pub fn state(&self) -> &str {
+        match &self.status {
+            Some(status) => status,
+            None if self.connected => "connected",
+            None if self.replied => "logging in",
+            None => "connecting",
+        }
+    }
+
+    // whether the line the cursor sits on is ssh asking about the host key
+    // or for a password, passphrase or code
+    fn at_login_prompt(&self) -> bool {
+        let screen = self.parser.screen();
+        let (row, _) = screen.cursor_position();
+        let (_, cols) = screen.size();
+        let line = screen.contents_between(row, 0, row, cols).to_lowercase();
+        LOGIN_PROMPTS.iter().any(|prompt| line.contains(prompt))
+    }
+
+    pub fn send_key(&mut self, key: KeyEvent) -> Result<()> {
+        // typing jumps back to the live screen
+        self.parser.set_scrollback(0);
+        self.selection = None;
+        let application_cursor = self.parser.screen().application_cursor();
+        if let Some(bytes) = key_bytes(key, application_cursor) {
+            self.writer.write_all(&bytes)?;
+            self.writer.flush()?;
+            // plain characters typed at a shell come straight back; full
+            // screen apps redraw on their own schedule, so they aren't timed
+            let echoed = matches!(key.code, KeyCode::Char(_))
+                && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                && !self.parser.screen().alternate_screen();
+            if echoed && self.connected && self.pending_echo.is_none() {
+                self.pending_echo = Some((Instant::now(), bytes));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the remote application asked for mouse reporting (htop, vim
+    /// with `mouse=a`, ...).
+    pub fn wants_mouse(&self) -> bool {
+        self.parser.screen().mouse_protocol_mode() != MouseProtocolMode::None
+    }
+
+    /// Reports a mouse event at `row`/`col` of the remote screen, in the
+    /// mode and encoding the remote application asked for.
+    pub fn send_mouse(&mut self, mouse: MouseEvent, row: u16, col: u16) -> Result<()> {
+        let screen = self.parser.screen();
+        if let Some(bytes) = mouse_bytes(mouse, row, col, screen.mouse_protocol_mode(), screen.mouse_protocol_encoding()) {
+            self.writer.write_all(&bytes)?;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Moves the view `lines` further into the scrollback, or back towards
+    /// the live screen.
+    pub fn scroll(&mut self, up: bool, lines: usize) {
+        let offset = self.parser.screen().scrollback();
+        self.parser.set_scrollback(match up {
+            true => offset + lines,
+            false => offset.saturating_sub(lines),
+        });
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        let ((start_row, start_col), (end_row, end_col)) = self.selection?.bounds();
+        let text = self.parser.screen().contents_between(start_row, start_col, end_row, end_col + 1);
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Resizes the local pty; ssh forwards this to the remote side as a
+    /// `window-change` request.
+    pub fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
+        if self.parser.screen().size() == (rows, cols) {
+            return Ok(());
+        }
+        self.master.resize(pty_size(rows, cols))?;
+        self.parser.set_size(rows, cols);
+        Ok(())
+    }
+}
+
+/// A stretch of the screen dragged over with the mouse, from where the
+/// drag started to where it is now, as (row, col).
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub anchor: (u16, u16),
+    pub head: (u16, u16),
+}
+
+impl Selection {
+    /// The first and last selected cells, in reading order.
+    pub fn bounds(&self) -> ((u16, u16), (u16, u16)) {
+        (self.anchor.min(self.head), self.anchor.max(self.head))
+    }
+
+    pub fn contains(&self, row: u16, col: u16) -> bool {
+        let (start, end) = self.bounds();
+        (start..=end).contains(&(row, col))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        // reap it, or every closed session leaves a zombie behind
+        let _ = self.child.wait();
+    }
+}
+
+fn pty_size(rows: u16, cols: u16) -> PtySize {
+    PtySize {
+        rows: rows.max(1),
+        cols: cols.max(1),
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+// what xterm sends for a key. Modified cursor, edit and function keys carry
+// xterm's modifier parameter (1, plus 1 for shift, 2 for alt, 4 for ctrl);
+// keys that send plain bytes get alt as an ESC in front
+fn key_bytes(key: KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let modifier = 1 + u8::from(shift) + 2 * u8::from(alt) + 4 * u8::from(ctrl);
+
+    // `CSI 1;m X` when modified, otherwise SS3 (`ESC O X`) or `CSI X`
+    let letter = |code: u8, ss3: bool| match (modifier, ss3) {
+        (1, true) => vec![0x1b, b'O', code],
+        (1, false) => vec![0x1b, b'[', code],
+        _ => format!("\x1b[1;{modifier}{}", char::from(code)).into_bytes(),
+    };
+    let tilde = |n: u8| match modifier {
+        1 => format!("\x1b[{n}~").into_bytes(),
+        _ => format!("\x1b[{n};{modifier}~").into_bytes(),
+    };
+    let meta = |mut bytes: Vec<u8>| {
+        if alt {
+            bytes.insert(0, 0x1b);
+        }
+        bytes
+    };
+
+    let bytes = match key.code {
+        KeyCode::Char(c) => match control_byte(c).filter(|_| ctrl) {
+            Some(byte) => meta(vec![byte]),
+            None => meta(c.encode_utf8(&mut [0u8; 4]).as_bytes().to_vec()),
+        },
+        KeyCode::Enter => meta(vec![b'\r']),
+        KeyCode::Backspace if ctrl => meta(vec![0x08]),
+        KeyCode::Backspace => meta(vec![0x7f]),
+        KeyCode::Tab => meta(vec![b'\t']),
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Esc => meta(vec![0x1b]),
+        KeyCode::Up => letter(b'A', application_cursor),
+        KeyCode::Down => letter(b'B', application_cursor),
+        KeyCode::Right => letter(b'C', application_cursor),
+        KeyCode::Left => letter(b'D', application_cursor),
+        KeyCode::Home => letter(b'H', application_cursor),
+        KeyCode::End => letter(b'F', application_cursor),
+        KeyCode::Insert => tilde(2),
+        KeyCode::Delete => tilde(3),
+        KeyCode::PageUp => tilde(5),
+        KeyCode::PageDown => tilde(6),
+        KeyCode::F(n @ 1..=4) => letter(b'P' + n - 1, true),
+        // 16 and 22 were skipped by the VT220 this numbering comes from
+        KeyCode::F(n @ 5..=12) => tilde([15, 17, 18, 19, 20, 21, 23, 24][usize::from(n - 5)]),
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+// the control code Ctrl plus `c` sends, with xterm's digit and punctuation
+// aliases; keys without one (Ctrl+1, Ctrl+é) send themselves
+fn control_byte(c: char) -> Option<u8> {
+    let byte = match c.to_ascii_lowercase() {
+        c @ 'a'..='z' => c as u8 - b'a' + 1,
+        '@' | ' ' | '2' => 0,
+        '[' | '3' => 0x1b,
+        '\\' | '4' => 0x1c,
+        ']' | '5' => 0x1d,
+        '^' | '~' | '6' => 0x1e,
+        '_' | '/' | '-' | '7' => 0x1f,
+        '?' | '8' => 0x7f,
+        _ => return None,
+    };
+    Some(byte)
+}
+
+// xterm mouse reports: the button (plus 32 while dragging, 64 for the
+// wheel, and the modifier bits) with 1-based coordinates, as `CSI < .. M/m`
+// under SGR or as offset bytes in the older encodings
+fn mouse_bytes(mouse: MouseEvent, row: u16, col: u16, mode: MouseProtocolMode, encoding: MouseProtocolEncoding) -> Option<Vec<u8>> {
+    let button = |button: MouseButton| match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    };
+    let (pressed, release) = match mouse.kind {
+        MouseEventKind::Down(b) => (button(b), false),
+        MouseEventKind::Up(b) if mode != MouseProtocolMode::Press => (button(b), true),
+        MouseEventKind::Drag(b) if matches!(mode, MouseProtocolMode::ButtonMotion | MouseProtocolMode::AnyMotion) => {
+            (button(b) + 32, false)
+        }
+        MouseEventKind::Moved if mode == MouseProtocolMode::AnyMotion => (3 + 32, false),
+        MouseEventKind::ScrollUp => (64, false),
+        MouseEventKind::ScrollDown => (65, false),
+        _ => return None,
+    };
+    let sgr = encoding == MouseProtocolEncoding::Sgr;
+    // the older encodings can't say which button went up
+    let mut code = if release && !sgr { 3 } else { pressed };
+    if mouse.modifiers.contains(KeyModifiers::SHIFT) {
+        code += 4;
+    }
+    if mouse.modifiers.contains(KeyModifiers::ALT) {
+        code += 8;
+    }
+    if mouse.modifiers.contains(KeyModifiers::CONTROL) {
+        code += 16;
+    }
+    let (x, y) = (u32::from(col) + 1, u32::from(row) + 1);
+
+    if sgr {
+        let end = if release { 'm' } else { 'M' };
+        return Some(format!("\x1b[<{code};{x};{y}{end}").into_bytes());
+    }
+    let mut bytes = b"\x1b[M".to_vec();
+    for value in [code + 32, x + 32, y + 32] {
+        match encoding {
+            MouseProtocolEncoding::Utf8 => {
+                let c = char::from_u32(value)?;
+                let mut buf = [0u8; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            _ => bytes.push(u8::try_from(value).ok()?),
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+    use vt100::{MouseProtocolEncoding, MouseProtocolMode};
+
+    use super::{key_bytes, mouse_bytes};
+
+    fn bytes(code: KeyCode, modifiers: KeyModifiers) -> Vec<u8> {
+        key_bytes(KeyEvent::new(code, modifiers), false).unwrap()
+    }
+
+    #[test]
+    fn plain_keys_send_themselves() {
+        assert_eq!(bytes(KeyCode::Char('a'), KeyModifiers::NONE), b"a");
+        assert_eq!(bytes(KeyCode::Char('é'), KeyModifiers::NONE), "é".as_bytes());
+        assert_eq!(bytes(KeyCode::Enter, KeyModifiers::NONE), b"\r");
+        assert_eq!(bytes(KeyCode::Backspace, KeyModifiers::NONE), b"\x7f");
+        assert_eq!(bytes(KeyCode::BackTab, KeyModifiers::SHIFT), b"\x1b[Z");
+        assert_eq!(key_bytes(KeyEvent::new(KeyCode::Null, KeyModifiers::NONE), false), None);
+    }
+
+    #[test]
+    fn ctrl_sends_control_codes() {
+        assert_eq!(bytes(KeyCode::Char('c'), KeyModifiers::CONTROL), b"\x03");
+        assert_eq!(bytes(KeyCode::Char('C'), KeyModifiers::CONTROL | KeyModifiers::SHIFT), b"\x03");
+        assert_eq!(bytes(KeyCode::Char(' '), KeyModifiers::CONTROL), b"\x00");
+        assert_eq!(bytes(KeyCode::Char('['), KeyModifiers::CONTROL), b"\x1b");
+        assert_eq!(bytes(KeyCode::Char('_'), KeyModifiers::CONTROL), b"\x1f");
+        assert_eq!(bytes(KeyCode::Char('/'), KeyModifiers::CONTROL), b"\x1f");
+        assert_eq!(bytes(KeyCode::Char('6'), KeyModifiers::CONTROL), b"\x1e");
+        assert_eq!(bytes(KeyCode::Char('1'), KeyModifiers::CONTROL), b"1");
+        assert_eq!(bytes(KeyCode::Backspace, KeyModifiers::CONTROL), b"\x08");
+    }
+
+    #[test]
+    fn alt_prefixes_escape() {
+        assert_eq!(bytes(KeyCode::Char('b'), KeyModifiers::ALT), b"\x1bb");
+        assert_eq!(bytes(KeyCode::Char('x'), KeyModifiers::ALT | KeyModifiers::CONTROL), b"\x1b\x18");
+        assert_eq!(bytes(KeyCode::Enter, KeyModifiers::ALT), b"\x1b\r");
+    }
+
+    #[test]
+    fn cursor_keys_follow_the_cursor_mode() {
+        assert_eq!(bytes(KeyCode::Up, KeyModifiers::NONE), b"\x1b[A");
+        assert_eq!(key_bytes(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE), true).unwrap(), b"\x1bOA");
+        assert_eq!(bytes(KeyCode::Home, KeyModifiers::NONE), b"\x1b[H");
+        assert_eq!(bytes(KeyCode::End, KeyModifiers::NONE), b"\x1b[F");
+    }
+
+    #[test]
+    fn modified_keys_carry_the_xterm_parameter() {
+        assert_eq!(bytes(KeyCode::Up, KeyModifiers::CONTROL), b"\x1b[1;5A");
+        assert_eq!(key_bytes(KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT), true).unwrap(), b"\x1b[1;2D");
+        assert_eq!(bytes(KeyCode::Right, KeyModifiers::ALT | KeyModifiers::SHIFT), b"\x1b[1;4C");
+        assert_eq!(bytes(KeyCode::PageUp, KeyModifiers::ALT), b"\x1b[5;3~");
+        assert_eq!(bytes(KeyCode::Delete, KeyModifiers::NONE), b"\x1b[3~");
+    }
+
+    #[test]
+    fn function_keys() {
+        assert_eq!(bytes(KeyCode::F(1), KeyModifiers::NONE), b"\x1bOP");
+        assert_eq!(bytes(KeyCode::F(4), KeyModifiers::NONE), b"\x1bOS");
+        assert_eq!(bytes(KeyCode::F(1), KeyModifiers::CONTROL), b"\x1b[1;5P");
+        assert_eq!(bytes(KeyCode::F(5), KeyModifiers::NONE), b"\x1b[15~");
+        assert_eq!(bytes(KeyCode::F(6), KeyModifiers::NONE), b"\x1b[17~");
+        assert_eq!(bytes(KeyCode::F(12), KeyModifiers::SHIFT), b"\x1b[24;2~");
+        assert_eq!(key_bytes(KeyEvent::new(KeyCode::F(13), KeyModifiers::NONE), false), None);
+    }
+
+    fn mouse(kind: MouseEventKind, modifiers: KeyModifiers) -> MouseEvent {
+        MouseEvent { kind, column: 0, row: 0, modifiers }
+    }
+
+    fn report(kind: MouseEventKind, mode: MouseProtocolMode, encoding: MouseProtocolEncoding) -> Option<Vec<u8>> {
+        mouse_bytes(mouse(kind, KeyModifiers::NONE), 4, 9, mode, encoding)
+    }
+
+    #[test]
+    fn sgr_reports_press_and_release() {
+        let down = MouseEventKind::Down(MouseButton::Left);
+        let up = MouseEventKind::Up(MouseButton::Right);
+        let (mode, sgr) = (MouseProtocolMode::PressRelease, MouseProtocolEncoding::Sgr);
+        assert_eq!(report(down, mode, sgr).unwrap(), b"\x1b[<0;10;5M");
+        assert_eq!(report(up, mode, sgr).unwrap(), b"\x1b[<2;10;5m");
+        assert_eq!(report(MouseEventKind::ScrollDown, mode, sgr).unwrap(), b"\x1b[<65;10;5M");
+    }
+
+    #[test]
+    fn modes_filter_events() {
+        let sgr = MouseProtocolEncoding::Sgr;
+        let up = MouseEventKind::Up(MouseButton::Left);
+        let drag = MouseEventKind::Drag(MouseButton::Left);
+        assert_eq!(report(up, MouseProtocolMode::Press, sgr), None);
+        assert_eq!(report(drag, MouseProtocolMode::PressRelease, sgr), None);
+        assert_eq!(report(drag, MouseProtocolMode::ButtonMotion, sgr).unwrap(), b"\x1b[<32;10;5M");
+        assert_eq!(report(MouseEventKind::Moved, MouseProtocolMode::ButtonMotion, sgr), None);
+        assert_eq!(report(MouseEventKind::Moved, MouseProtocolMode::AnyMotion, sgr).unwrap(), b"\x1b[<35;10;5M");
+    }
+
+    #[test]
+    fn modifiers_add_to_the_button() {
+        let event = mouse(MouseEventKind::Down(MouseButton::Middle), KeyModifiers::SHIFT | KeyModifiers::CONTROL);
+        let bytes = mouse_bytes(event, 0, 0, MouseProtocolMode::Press, MouseProtocolEncoding::Sgr).unwrap();
+        assert_eq!(bytes, b"\x1b[<21;1;1M");
+    }
+
+    #[test]
+    fn legacy_encodings_offset_by_32() {
+        let (mode, default) = (MouseProtocolMode::PressRelease, MouseProtocolEncoding::Default);
+        let down = MouseEventKind::Down(MouseButton::Left);
+        assert_eq!(report(down, mode, default).unwrap(), b"\x1b[M \x2a\x25");
+        // the release doesn't say which button
+        assert_eq!(report(MouseEventKind::Up(MouseButton::Right), mode, default).unwrap(), b"\x1b[M#\x2a\x25");
+        // past column 223 a byte can't hold it; UTF-8 can
+        let far = mouse(down, KeyModifiers::NONE);
+        assert_eq!(mouse_bytes(far, 0, 300, mode, default), None);
+        assert_eq!(
+            mouse_bytes(far, 0, 300, mode, MouseProtocolEncoding::Utf8).unwrap(),
+            [b"\x1b[M ".as_slice(), "ō".as_bytes(), b"!"].concat()
+        );
+    }
+}
@@ -1,3 +1,12 @@
-pub fn connect_tor(host: &str) {
-    println!("[SSH] connecting to {} over Tor", host);
+use crate::config::inherit::ResolvedHost;
+
+pub const TOR_SOCKS_ADDR: &str = "127.0.0.1:9050";
+
+pub fn ssh_args(profile: &ResolvedHost) -> Vec<String> {
+    let mut args = vec![
+        "-o".to_string(),
+        format!("ProxyCommand=nc -X 5 -x {TOR_SOCKS_ADDR} %h %p"),
+    ];
+    args.extend(super::normal::ssh_args(profile));
+    args
 }
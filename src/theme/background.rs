@@ -0,0 +1,191 @@
+use std::env;
+
+use super::convert::luminance;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Brightness {
+    Light,
+    Dark,
+}
+
+impl Brightness {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Brightness::Light => "light",
+            Brightness::Dark => "dark",
+        }
+    }
+}
+
+/// Whether the terminal's background is light or dark, and how that was
+/// found out; what `theme = "auto"` goes by.
+#[derive(Clone, Debug)]
+pub struct Background {
+    pub brightness: Brightness,
+    /// e.g. `OSC 11` or `COLORFGBG=15;0`.
+    pub reason: String,
+}
+
+impl Background {
+    /// Asks the terminal for its background color (OSC 11), then falls
+    /// back to `COLORFGBG`, then assumes dark. Needs raw mode, and nothing
+    /// else may be reading input meanwhile: it reads stdin itself, and a
+    /// reply that reached another reader would be taken for keys.
+    pub fn detect() -> Background {
+        if let Some(rgb) = query::background_color() {
+            let brightness = match luminance(rgb) > 0.5 {
+                true => Brightness::Light,
+                false => Brightness::Dark,
+            };
+            return Background { brightness, reason: "OSC 11".to_string() };
+        }
+        if let Ok(value) = env::var("COLORFGBG")
+            && let Some(brightness) = colorfgbg(&value)
+        {
+            return Background { brightness, reason: format!("COLORFGBG={value}") };
+        }
+        Background {
+            brightness: Brightness::Dark,
+            reason: "no answer from the terminal; assumed".to_string(),
+        }
+    }
+}
+
+// `fg;bg` or `fg;default;bg` with ANSI color numbers; 7 (white) and the
+// bright colors other than 8 (dark grey) are light backgrounds
+fn colorfgbg(value: &str) -> Option<Brightness> {
+    let background = value.rsplit(';').next()?.trim().parse::<u8>().ok()?;
+    match background {
+        7 | 9..=15 => Some(Brightness::Light),
+        0..=8 => Some(Brightness::Dark),
+        _ => None,
+    }
+}
+
+// `rgb:RRRR/GGGG/BBBB` (1-4 hex digits per channel, sometimes `rgba:`)
+// after `ESC ] 11 ;`, ended by BEL or ST
+fn parse_reply(reply: &[u8]) -> Option<(u8, u8, u8)> {
+    let reply = String::from_utf8_lossy(reply);
+    let start = reply.find("]11;")? + 4;
+    let value = reply[start..].split(['\x07', '\x1b']).next()?;
+    let channels = value.strip_prefix("rgb:").or_else(|| value.strip_prefix("rgba:"))?;
+    let mut channels = channels.split('/').map(|digits| {
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        let max = 16u32.checked_pow(u32::try_from(digits.len()).ok()?)? - 1;
+        Some((value * 255 / max.max(1)) as u8)
+    });
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+#[cfg(unix)]
+mod query {
+    use std::{
+        io::{self, Write},
+        os::fd::AsRawFd,
+        time::{Duration, Instant},
+    };
+
+    // how long a terminal that answers nothing at all holds things up
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    pub fn background_color() -> Option<(u8, u8, u8)> {
+        let fd = io::stdin().as_raw_fd();
+        // SAFETY: isatty only looks at the descriptor
+        if unsafe { libc::isatty(fd) } != 1 {
+            return None;
+        }
+        // every terminal answers the device attributes request (DA1) that
+        // follows; getting that answer first means OSC 11 isn't supported
+        // and there's no point waiting out the timeout
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b]11;?\x1b\\\x1b[c").ok()?;
+        stdout.flush().ok()?;
+
+        // read straight from the descriptor: anything left in a buffer
+        // here would never reach crossterm's reader
+        let deadline = Instant::now() + TIMEOUT;
+        let mut reply = Vec::new();
+        while !answered(&reply) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let mut poll = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+            // SAFETY: one valid pollfd
+            let ready = unsafe { libc::poll(&mut poll, 1, left.as_millis() as libc::c_int) };
+            if ready <= 0 {
+                break;
+            }
+            let mut buf = [0u8; 256];
+            // SAFETY: reads at most buf.len() bytes into buf
+            let read = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+            let Ok(read) = usize::try_from(read) else {
+                break;
+            };
+            if read == 0 {
+                break;
+            }
+            reply.extend_from_slice(&buf[..read]);
+        }
+        super::parse_reply(&reply)
+    }
+
+    // the DA1 answer, `ESC [ ? ... c`, comes last
+    fn answered(reply: &[u8]) -> bool {
+        reply
+            .windows(3)
+            .position(|window| window == b"\x1b[?")
+            .is_some_and(|start| reply[start..].contains(&b'c'))
+    }
+}
+
+#[cfg(not(unix))]
+mod query {
+    pub fn background_color() -> Option<(u8, u8, u8)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{colorfgbg, parse_reply, Brightness};
+
+    #[test]
+    fn parses_four_digit_replies() {
+        assert_eq!(parse_reply(b"\x1b]11;rgb:ffff/8080/0000\x07"), Some((255, 128, 0)));
+        assert_eq!(parse_reply(b"\x1b]11;rgb:1d1d/1f1f/2121\x1b\\"), Some((29, 31, 33)));
+    }
+
+    #[test]
+    fn scales_shorter_channels() {
+        assert_eq!(parse_reply(b"\x1b]11;rgb:ff/80/00\x07"), Some((255, 128, 0)));
+        assert_eq!(parse_reply(b"\x1b]11;rgb:f/8/0\x07"), Some((255, 136, 0)));
+        assert_eq!(parse_reply(b"\x1b]11;rgba:ffff/ffff/ffff/ffff\x07"), Some((255, 255, 255)));
+    }
+
+    #[test]
+    fn skips_input_before_the_reply() {
+        assert_eq!(parse_reply(b"abc\x1b]11;rgb:0000/0000/0000\x07"), Some((0, 0, 0)));
+    }
+
+    #[test]
+    fn rejects_malformed_replies() {
+        assert_eq!(parse_reply(b""), None);
+        assert_eq!(parse_reply(b"\x1b]10;rgb:ffff/ffff/ffff\x07"), None);
+        assert_eq!(parse_reply(b"\x1b]11;#ffffff\x07"), None);
+        assert_eq!(parse_reply(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+        assert_eq!(parse_reply(b"\x1b]11;rgb:zz/00/00\x07"), None);
+    }
+
+    #[test]
+    fn colorfgbg_reads_the_last_field() {
+        assert_eq!(colorfgbg("15;0"), Some(Brightness::Dark));
+        assert_eq!(colorfgbg("0;15"), Some(Brightness::Light));
+        assert_eq!(colorfgbg("0;default;7"), Some(Brightness::Light));
+        assert_eq!(colorfgbg("7;8"), Some(Brightness::Dark));
+    }
+
+    #[test]
+    fn colorfgbg_ignores_unknown_values() {
+        assert_eq!(colorfgbg("15;default"), None);
+        assert_eq!(colorfgbg("0;16"), None);
+        assert_eq!(colorfgbg(""), None);
+    }
+}
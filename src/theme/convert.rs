@@ -0,0 +1,211 @@
+use ratatui::style::Color;
+
+pub type Rgb = (u8, u8, u8);
+
+/// Hue in degrees (0-360), saturation and lightness in percent (0-100).
+pub type Hsl = (f64, f64, f64);
+
+// xterm's defaults for the 16 named colors, black through bright white
+const ANSI: [Rgb; 16] = [
+    (0, 0, 0),
+    (128, 0, 0),
+    (0, 128, 0),
+    (128, 128, 0),
+    (0, 0, 128),
+    (128, 0, 128),
+    (0, 128, 128),
+    (192, 192, 192),
+    (128, 128, 128),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// the six levels of each channel in the 256-color cube
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The RGB a terminal would most likely show for `color`; named and
+/// indexed colors use xterm's defaults.
+pub fn to_rgb(color: Color) -> Rgb {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index) => indexed(index),
+        Color::Reset | Color::Black => ANSI[0],
+        Color::Red => ANSI[1],
+        Color::Green => ANSI[2],
+        Color::Yellow => ANSI[3],
+        Color::Blue => ANSI[4],
+        Color::Magenta => ANSI[5],
+        Color::Cyan => ANSI[6],
+        Color::Gray => ANSI[7],
+        Color::DarkGray => ANSI[8],
+        Color::LightRed => ANSI[9],
+        Color::LightGreen => ANSI[10],
+        Color::LightYellow => ANSI[11],
+        Color::LightBlue => ANSI[12],
+        Color::LightMagenta => ANSI[13],
+        Color::LightCyan => ANSI[14],
+        Color::White => ANSI[15],
+    }
+}
+
+fn indexed(index: u8) -> Rgb {
+    match index {
+        0..16 => ANSI[usize::from(index)],
+        16..232 => {
+            let cube = index - 16;
+            (
+                CUBE[usize::from(cube / 36)],
+                CUBE[usize::from(cube / 6 % 6)],
+                CUBE[usize::from(cube % 6)],
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+pub fn to_hsl((r, g, b): Rgb) -> Hsl {
+    let (r, g, b) = (f64::from(r) / 255.0, f64::from(g) / 255.0, f64::from(b) / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+    if delta == 0.0 {
+        return (0.0, 0.0, lightness * 100.0);
+    }
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    (hue, saturation * 100.0, lightness * 100.0)
+}
+
+pub fn from_hsl((hue, saturation, lightness): Hsl) -> Rgb {
+    let (saturation, lightness) = (saturation / 100.0, lightness / 100.0);
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let offset = lightness - chroma / 2.0;
+    let channel = |value: f64| ((value + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+/// Relative brightness from 0 to 1, for picking readable text over a color.
+pub fn luminance((r, g, b): Rgb) -> f64 {
+    (0.299 * f64::from(r) + 0.587 * f64::from(g) + 0.114 * f64::from(b)) / 255.0
+}
+
+/// The closest color in the 256-color cube or grey ramp (indexes 16-255;
+/// the first 16 are left alone since terminals redefine them).
+pub fn nearest_256(rgb: Rgb) -> u8 {
+    let level = |channel: u8| match channel {
+        0..48 => 0,
+        48..115 => 1,
+        channel => (channel - 35) / 40,
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = 16 + 36 * r + 6 * g + b;
+
+    let average = (u16::from(rgb.0) + u16::from(rgb.1) + u16::from(rgb.2)) / 3;
+    let grey = if average > 238 { 23 } else { average.saturating_sub(3) / 10 } as u8;
+    let grey = 232 + grey.min(23);
+
+    match distance(rgb, indexed(grey)) < distance(rgb, indexed(cube)) {
+        true => grey,
+        false => cube,
+    }
+}
+
+/// The closest of the 16 named colors. Plain distance turns muted accents
+/// grey, so anything with some color to it keeps its hue instead.
+pub fn nearest_16(rgb: Rgb) -> Color {
+    let (hue, _, lightness) = to_hsl(rgb);
+    let chroma = rgb.0.max(rgb.1).max(rgb.2) - rgb.0.min(rgb.1).min(rgb.2);
+    if chroma < 60 {
+        return match lightness {
+            l if l < 20.0 => Color::Black,
+            l if l < 50.0 => Color::DarkGray,
+            l if l < 80.0 => Color::Gray,
+            _ => Color::White,
+        };
+    }
+    let bright = lightness > 55.0;
+    match ((hue + 30.0) % 360.0 / 60.0) as u8 {
+        0 if bright => Color::LightRed,
+        0 => Color::Red,
+        1 if bright => Color::LightYellow,
+        1 => Color::Yellow,
+        2 if bright => Color::LightGreen,
+        2 => Color::Green,
+        3 if bright => Color::LightCyan,
+        3 => Color::Cyan,
+        4 if bright => Color::LightBlue,
+        4 => Color::Blue,
+        _ if bright => Color::LightMagenta,
+        _ => Color::Magenta,
+    }
+}
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Color;
+
+    use super::{nearest_16, nearest_256};
+
+    #[test]
+    fn nearest_256_hits_cube_corners() {
+        assert_eq!(nearest_256((0, 0, 0)), 16);
+        assert_eq!(nearest_256((255, 0, 0)), 196);
+        assert_eq!(nearest_256((0, 255, 0)), 46);
+        assert_eq!(nearest_256((0, 0, 255)), 21);
+        assert_eq!(nearest_256((255, 255, 255)), 231);
+    }
+
+    #[test]
+    fn nearest_256_prefers_the_grey_ramp_for_greys() {
+        assert_eq!(nearest_256((128, 128, 128)), 244);
+        assert_eq!(nearest_256((8, 8, 8)), 232);
+        assert!((232..=255).contains(&nearest_256((30, 30, 30))));
+    }
+
+    #[test]
+    fn nearest_16_keeps_the_hue_of_muted_colors() {
+        assert_eq!(nearest_16((0xcc, 0x24, 0x1d)), Color::Red);
+        assert_eq!(nearest_16((0xfb, 0x49, 0x34)), Color::LightRed);
+        assert_eq!(nearest_16((0x45, 0x85, 0x88)), Color::Cyan);
+        assert_eq!(nearest_16((0x83, 0xa5, 0x98)), Color::Gray);
+        assert_eq!(nearest_16((0x20, 0x40, 0xc0)), Color::Blue);
+    }
+
+    #[test]
+    fn nearest_16_sorts_greys_by_lightness() {
+        assert_eq!(nearest_16((10, 10, 10)), Color::Black);
+        assert_eq!(nearest_16((80, 80, 80)), Color::DarkGray);
+        assert_eq!(nearest_16((170, 170, 170)), Color::Gray);
+        assert_eq!(nearest_16((240, 240, 240)), Color::White);
+    }
+}
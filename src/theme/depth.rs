@@ -0,0 +1,239 @@
+use std::{env, fs, path::PathBuf};
+
+use ratatui::style::Color;
+
+use super::convert::{nearest_16, nearest_256, to_rgb};
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    /// No colors at all (`NO_COLOR`, dumb terminals); emphasis comes from
+    /// bold, dim and reverse video.
+    Mono,
+}
+
+impl ColorDepth {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorDepth::TrueColor => "truecolor",
+            ColorDepth::Ansi256 => "256 colors",
+            ColorDepth::Ansi16 => "16 colors",
+            ColorDepth::Mono => "monochrome",
+        }
+    }
+
+    fn parse(value: &str) -> Option<ColorDepth> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            "256" => Some(ColorDepth::Ansi256),
+            "16" | "8" => Some(ColorDepth::Ansi16),
+            "none" | "mono" | "0" => Some(ColorDepth::Mono),
+            _ => None,
+        }
+    }
+
+    /// The nearest color this depth can show; `Mono` drops colors
+    /// altogether.
+    pub fn fit(&self, color: Color) -> Color {
+        match (self, color) {
+            (_, Color::Reset) | (ColorDepth::TrueColor, _) => color,
+            (ColorDepth::Mono, _) => Color::Reset,
+            (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(nearest_256((r, g, b))),
+            (ColorDepth::Ansi256, _) => color,
+            (ColorDepth::Ansi16, Color::Rgb(..) | Color::Indexed(16..)) => nearest_16(to_rgb(color)),
+            (ColorDepth::Ansi16, Color::Indexed(index)) => NAMED[usize::from(index)],
+            (ColorDepth::Ansi16, _) => color,
+        }
+    }
+}
+
+// ANSI 0-15 by name, so 16-color output never uses 256-color escapes
+const NAMED: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+/// The terminal's color depth and what gave it away.
+#[derive(Clone, Debug)]
+pub struct ColorSupport {
+    pub depth: ColorDepth,
+    /// e.g. `COLORTERM=truecolor` or `terminfo xterm-256color`.
+    pub reason: String,
+}
+
+impl ColorSupport {
+    /// Checks, in order: `MANGSH_COLOR` (truecolor, 256, 16 or none),
+    /// `NO_COLOR`, `COLORTERM`, then the `colors` capability in `TERM`'s
+    /// terminfo entry, falling back to guessing from the name.
+    pub fn detect() -> ColorSupport {
+        let found = |depth, reason: String| ColorSupport { depth, reason };
+        let var = |name| env::var(name).ok().filter(|value: &String| !value.is_empty());
+
+        if let Some(value) = var("MANGSH_COLOR")
+            && let Some(depth) = ColorDepth::parse(&value)
+        {
+            return found(depth, format!("MANGSH_COLOR={value}"));
+        }
+        if var("NO_COLOR").is_some() {
+            return found(ColorDepth::Mono, "NO_COLOR".to_string());
+        }
+        if let Some(value) = var("COLORTERM")
+            && matches!(value.as_str(), "truecolor" | "24bit")
+        {
+            return found(ColorDepth::TrueColor, format!("COLORTERM={value}"));
+        }
+        let Some(term) = var("TERM") else {
+            // Windows consoles don't set TERM and handle RGB fine
+            return found(ColorDepth::TrueColor, "TERM not set".to_string());
+        };
+        if term == "dumb" {
+            return found(ColorDepth::Mono, "TERM=dumb".to_string());
+        }
+        if term.ends_with("-direct") || term.ends_with("-truecolor") {
+            return found(ColorDepth::TrueColor, format!("TERM={term}"));
+        }
+        if let Some(colors) = terminfo_colors(&term) {
+            let depth = match colors {
+                0x100_0000.. => ColorDepth::TrueColor,
+                256.. => ColorDepth::Ansi256,
+                8.. => ColorDepth::Ansi16,
+                _ => ColorDepth::Mono,
+            };
+            return found(depth, format!("terminfo {term}"));
+        }
+        let depth = match term.contains("256color") {
+            true => ColorDepth::Ansi256,
+            false => ColorDepth::Ansi16,
+        };
+        found(depth, format!("TERM={term}"))
+    }
+}
+
+// the `colors` number from a compiled terminfo entry; -1 when the entry
+// doesn't have one
+fn terminfo_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".terminfo"));
+    }
+    if let Some(list) = env::var_os("TERMINFO_DIRS") {
+        dirs.extend(env::split_paths(&list));
+    }
+    dirs.extend(["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo", "/usr/lib/terminfo"].map(PathBuf::from));
+
+    // ncurses files entries under their first letter; macOS under its hex
+    let entry = dirs.iter().find_map(|dir| {
+        [first.to_string(), format!("{:x}", first as u32)]
+            .iter()
+            .find_map(|sub| fs::read(dir.join(sub).join(term)).ok())
+    })?;
+    read_colors(&entry)
+}
+
+// the compiled format: a header of six little-endian shorts, the names,
+// the booleans, then the numbers (16-bit, or 32-bit in the newer format);
+// `colors` is number 13
+fn read_colors(data: &[u8]) -> Option<i32> {
+    const COLORS: usize = 13;
+    let short = |at: usize| Some(i16::from_le_bytes([*data.get(at)?, *data.get(at + 1)?]));
+    let width = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let names = usize::try_from(short(2)?).ok()?;
+    let booleans = usize::try_from(short(4)?).ok()?;
+    let numbers = usize::try_from(short(6)?).ok()?;
+    if COLORS >= numbers {
+        return Some(-1);
+    }
+    let mut start = 12 + names + booleans;
+    // numbers start on an even byte
+    start += start % 2;
+    let at = start + COLORS * width;
+    let bytes = data.get(at..at + width)?;
+    Some(match width {
+        2 => i32::from(i16::from_le_bytes([bytes[0], bytes[1]])),
+        _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_colors;
+
+    // a header, `names` bytes of names, no booleans, then the numbers
+    fn terminfo(magic: i16, names: usize, numbers: &[i32]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for short in [magic, names as i16, 0, numbers.len() as i16, 0, 0] {
+            data.extend(short.to_le_bytes());
+        }
+        data.extend(std::iter::repeat_n(b'x', names));
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        for number in numbers {
+            match magic {
+                0o432 => data.extend((*number as i16).to_le_bytes()),
+                _ => data.extend(number.to_le_bytes()),
+            }
+        }
+        data
+    }
+
+    fn numbers(colors: i32) -> Vec<i32> {
+        let mut numbers = vec![-1; 15];
+        numbers[13] = colors;
+        numbers
+    }
+
+    #[test]
+    fn reads_the_legacy_format() {
+        assert_eq!(read_colors(&terminfo(0o432, 10, &numbers(256))), Some(256));
+    }
+
+    #[test]
+    fn aligns_numbers_after_odd_names() {
+        assert_eq!(read_colors(&terminfo(0o432, 11, &numbers(8))), Some(8));
+    }
+
+    #[test]
+    fn reads_the_extended_number_format() {
+        assert_eq!(read_colors(&terminfo(0o1036, 10, &numbers(0x1000000))), Some(0x1000000));
+    }
+
+    #[test]
+    fn missing_colors_is_absent() {
+        assert_eq!(read_colors(&terminfo(0o432, 10, &[-1; 5])), Some(-1));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(read_colors(b"#!/bin/sh\n"), None);
+        assert_eq!(read_colors(&[0x1a, 0x01]), None);
+        let mut truncated = terminfo(0o432, 10, &numbers(256));
+        truncated.truncate(30);
+        assert_eq!(read_colors(&truncated), None);
+    }
+}
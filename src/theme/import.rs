@@ -0,0 +1,405 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use ratatui::style::Color;
+
+use super::{convert::Rgb, CustomTheme};
+
+/// Scheme formats mangsh can read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SchemeFormat {
+    Base16,
+    Alacritty,
+    Iterm,
+}
+
+impl SchemeFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SchemeFormat::Base16 => "base16",
+            SchemeFormat::Alacritty => "Alacritty",
+            SchemeFormat::Iterm => "iTerm",
+        }
+    }
+
+    // by extension, then by a look at the contents
+    fn detect(path: &Path, source: &str) -> Option<SchemeFormat> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => return Some(SchemeFormat::Base16),
+            Some("toml") => return Some(SchemeFormat::Alacritty),
+            Some("itermcolors") => return Some(SchemeFormat::Iterm),
+            _ => {}
+        }
+        if source.contains("<plist") {
+            Some(SchemeFormat::Iterm)
+        } else if source.contains("base00") {
+            Some(SchemeFormat::Base16)
+        } else if source.contains("[colors") {
+            Some(SchemeFormat::Alacritty)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads a base16 YAML, Alacritty TOML or iTerm `.itermcolors` scheme into a
+/// custom theme with all 16 ANSI colors. The theme is named after the file
+/// unless `name` is given.
+pub fn import_scheme(path: &Path, name: Option<&str>) -> Result<(CustomTheme, SchemeFormat)> {
+    let source = fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let format = SchemeFormat::detect(path, &source)
+        .ok_or_else(|| anyhow!("{} isn't a base16, Alacritty or iTerm scheme", path.display()))?;
+    let mut theme = match format {
+        SchemeFormat::Base16 => base16(&source),
+        SchemeFormat::Alacritty => alacritty(&source),
+        SchemeFormat::Iterm => iterm(&source),
+    }
+    .with_context(|| format!("Unable to import {} as a {} scheme", path.display(), format.label()))?;
+
+    theme.name = match name {
+        Some(name) => name.to_string(),
+        None => slug(&path.file_stem().unwrap_or_default().to_string_lossy()),
+    };
+    Ok((theme, format))
+}
+
+// theme names end up in `theme = "..."`, so keep them plain
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+// `base0D: "7cafc2"`-style lines, at any indentation so both the classic
+// layout and the newer `palette:` block work
+fn base16(source: &str) -> Result<CustomTheme> {
+    let mut base = [None; 16];
+    for line in source.lines() {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let Some(index) = key
+            .trim()
+            .strip_prefix("base0")
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+        else {
+            continue;
+        };
+        let value = value.trim();
+        let value = match value.strip_prefix(['"', '\'']) {
+            Some(quoted) => quoted.split(['"', '\'']).next().unwrap_or_default(),
+            None => value.split(" #").next().unwrap_or_default(),
+        };
+        base[usize::from(index)] = Some(hex(value).with_context(|| format!("bad color for {}", key.trim()))?);
+    }
+    let mut colors = [(0, 0, 0); 16];
+    for (index, color) in base.iter().enumerate() {
+        colors[index] = color.ok_or_else(|| anyhow!("missing base{index:02X}"))?;
+    }
+    let [b00, b01, b02, b03, b04, b05, _b06, b07, b08, b09, b0a, b0b, b0c, b0d, b0e, _b0f] = colors;
+
+    // the usual base16-shell terminal mapping
+    let ansi = [
+        b00, b08, b0b, b0a, b0d, b0e, b0c, b05, b03, b08, b0b, b0a, b0d, b0e, b0c, b07,
+    ];
+    Ok(CustomTheme {
+        name: String::new(),
+        base: rgb(b00),
+        base_alt: rgb(b01),
+        surface: rgb(b01),
+        overlay: rgb(b02),
+        accent: rgb(b0d),
+        accent_soft: rgb(b09),
+        text: rgb(b05),
+        text_muted: rgb(b04),
+        ansi: ansi.into_iter().map(rgb).collect(),
+    })
+}
+
+fn alacritty(source: &str) -> Result<CustomTheme> {
+    let doc = source.parse::<toml::Table>()?;
+    let colors = doc.get("colors").and_then(toml::Value::as_table).ok_or_else(|| anyhow!("no [colors] table"))?;
+    let get = |table: &str, key: &str| -> Result<Option<Rgb>> {
+        let Some(value) = colors.get(table).and_then(|t| t.get(key)) else {
+            return Ok(None);
+        };
+        let value = value.as_str().ok_or_else(|| anyhow!("colors.{table}.{key} isn't a string"))?;
+        hex(value).map(Some).with_context(|| format!("bad color for colors.{table}.{key}"))
+    };
+    let require = |table: &str, key: &str| get(table, key)?.ok_or_else(|| anyhow!("missing colors.{table}.{key}"));
+
+    let names = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    let mut ansi = [(0, 0, 0); 16];
+    for (index, name) in names.iter().enumerate() {
+        ansi[index] = require("normal", name)?;
+        // schemes often leave the bright row out; fall back to normal
+        ansi[index + 8] = get("bright", name)?.unwrap_or(ansi[index]);
+    }
+    let background = require("primary", "background")?;
+    let foreground = require("primary", "foreground")?;
+    let selection = get("selection", "background")?;
+    Ok(from_terminal(background, foreground, selection, ansi))
+}
+
+// an Apple plist: a dict of `<key>Ansi 4 Color</key><dict>...</dict>`
+// entries, each holding `Red/Green/Blue Component` reals from 0 to 1
+fn iterm(source: &str) -> Result<CustomTheme> {
+    let tokens = plist_tokens(source);
+    let mut entries: Vec<(String, Rgb)> = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let (tag, name) = tokens[index];
+        if tag == "key" && tokens.get(index + 1).is_some_and(|(next, _)| *next == "dict") {
+            let mut components = [None; 3];
+            index += 2;
+            while index < tokens.len() && tokens[index].0 != "/dict" {
+                if let ("key", component) = tokens[index]
+                    && let Some(("real" | "integer", value)) = tokens.get(index + 1)
+                {
+                    let slot = match component {
+                        "Red Component" => Some(0),
+                        "Green Component" => Some(1),
+                        "Blue Component" => Some(2),
+                        _ => None,
+                    };
+                    if let Some(slot) = slot {
+                        let value = value.trim().parse::<f64>().with_context(|| format!("bad {component} in {name}"))?;
+                        components[slot] = Some((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+                    }
+                }
+                index += 1;
+            }
+            if let [Some(r), Some(g), Some(b)] = components {
+                entries.push((name.to_string(), (r, g, b)));
+            }
+        }
+        index += 1;
+    }
+
+    let find = |key: &str| entries.iter().find(|(name, _)| name == key).map(|(_, rgb)| *rgb);
+    let require = |key: &str| find(key).ok_or_else(|| anyhow!("missing {key}"));
+    let mut ansi = [(0, 0, 0); 16];
+    for (index, color) in ansi.iter_mut().enumerate() {
+        *color = require(&format!("Ansi {index} Color"))?;
+    }
+    Ok(from_terminal(
+        require("Background Color")?,
+        require("Foreground Color")?,
+        find("Selection Color"),
+        ansi,
+    ))
+}
+
+// the tags and text of a plist, e.g. ("key", "Ansi 0 Color"), ("dict", "");
+// of the closing tags only `/dict` matters
+fn plist_tokens(source: &str) -> Vec<(&str, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(open) = rest.find('<') {
+        let Some(close) = rest[open..].find('>') else {
+            break;
+        };
+        let tag = rest[open + 1..open + close].trim_end_matches('/').trim();
+        let tag = tag.split_whitespace().next().unwrap_or_default();
+        rest = &rest[open + close + 1..];
+        let text = rest.split('<').next().unwrap_or_default();
+        if !tag.starts_with(['?', '!']) && (!tag.starts_with('/') || tag == "/dict") {
+            tokens.push((tag, text.trim()));
+        }
+    }
+    tokens
+}
+
+// terminal schemes only give a background, foreground and the ANSI colors;
+// the in-between panel shades are blended from them
+fn from_terminal(background: Rgb, foreground: Rgb, selection: Option<Rgb>, ansi: [Rgb; 16]) -> CustomTheme {
+    CustomTheme {
+        name: String::new(),
+        base: rgb(background),
+        base_alt: rgb(mix(background, foreground, 0.04)),
+        surface: rgb(mix(background, foreground, 0.08)),
+        overlay: rgb(selection.unwrap_or_else(|| mix(background, foreground, 0.16))),
+        accent: rgb(ansi[4]),
+        accent_soft: rgb(ansi[5]),
+        text: rgb(foreground),
+        text_muted: rgb(mix(foreground, background, 0.35)),
+        ansi: ansi.into_iter().map(rgb).collect(),
+    }
+}
+
+fn mix(from: Rgb, to: Rgb, amount: f64) -> Rgb {
+    let channel = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * amount).round() as u8;
+    (channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2))
+}
+
+fn rgb((r, g, b): Rgb) -> Color {
+    Color::Rgb(r, g, b)
+}
+
+// `#1d1f21`, `0x1d1f21` or a bare `1d1f21`
+fn hex(value: &str) -> Result<Rgb> {
+    let digits = value.trim();
+    let digits = digits
+        .strip_prefix('#')
+        .or_else(|| digits.strip_prefix("0x"))
+        .unwrap_or(digits);
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("`{value}` isn't a hex color");
+    }
+    let channel = |range| u8::from_str_radix(&digits[range], 16);
+    Ok((channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Color;
+
+    use super::{alacritty, base16, hex, iterm};
+
+    #[test]
+    fn hex_takes_the_usual_spellings() {
+        assert_eq!(hex("#1d1f21").unwrap(), (0x1d, 0x1f, 0x21));
+        assert_eq!(hex("0x1D1F21").unwrap(), (0x1d, 0x1f, 0x21));
+        assert_eq!(hex(" 1d1f21 ").unwrap(), (0x1d, 0x1f, 0x21));
+    }
+
+    #[test]
+    fn hex_rejects_other_lengths_and_digits() {
+        assert!(hex("#fff").is_err());
+        assert!(hex("#1d1f2g").is_err());
+        assert!(hex("").is_err());
+        assert!(hex("1d1f21ff").is_err());
+    }
+
+    const BASE16: &str = r##"
+scheme: "Test"
+base00: "000000"
+base01: '010101'
+base02: "020202"
+base03: 030303
+base04: "040404" # comment
+base05: "050505"
+base06: "060606"
+base07: "070707"
+base08: "080808"
+base09: "090909"
+base0A: "0a0a0a"
+base0B: "0b0b0b"
+base0C: "0c0c0c"
+base0D: "0d0d0d"
+base0E: "0e0e0e"
+base0F: "0f0f0f"
+"##;
+
+    #[test]
+    fn base16_maps_the_terminal_colors() {
+        let theme = base16(BASE16).unwrap();
+        let grey = |n| Color::Rgb(n, n, n);
+        assert_eq!(theme.base, grey(0x00));
+        assert_eq!(theme.overlay, grey(0x02));
+        assert_eq!(theme.text, grey(0x05));
+        assert_eq!(theme.text_muted, grey(0x04));
+        assert_eq!(theme.accent, grey(0x0d));
+        let ansi: Vec<_> = [0x00, 0x08, 0x0b, 0x0a, 0x0d, 0x0e, 0x0c, 0x05, 0x03, 0x08, 0x0b, 0x0a, 0x0d, 0x0e, 0x0c, 0x07]
+            .into_iter()
+            .map(grey)
+            .collect();
+        assert_eq!(theme.ansi, ansi);
+    }
+
+    #[test]
+    fn base16_reads_the_palette_block() {
+        let palette: String = BASE16.lines().filter(|line| line.starts_with("base")).map(|line| format!("  {line}\n")).collect();
+        let nested = format!("system: \"base16\"\npalette:\n{palette}");
+        assert_eq!(base16(&nested).unwrap().accent, Color::Rgb(0x0d, 0x0d, 0x0d));
+    }
+
+    #[test]
+    fn base16_names_a_missing_color() {
+        let error = base16(&BASE16.replace("base0C: \"0c0c0c\"\n", "")).unwrap_err();
+        assert_eq!(error.to_string(), "missing base0C");
+    }
+
+    const ALACRITTY: &str = r##"
+[colors.primary]
+background = "#000000"
+foreground = "#ffffff"
+
+[colors.normal]
+black = "#000000"
+red = "#aa0000"
+green = "#00aa00"
+yellow = "#aaaa00"
+blue = "#0000aa"
+magenta = "#aa00aa"
+cyan = "#00aaaa"
+white = "#aaaaaa"
+
+[colors.bright]
+red = "#ff5555"
+"##;
+
+    #[test]
+    fn alacritty_falls_back_to_normal_for_bright() {
+        let theme = alacritty(ALACRITTY).unwrap();
+        assert_eq!(theme.base, Color::Rgb(0, 0, 0));
+        assert_eq!(theme.text, Color::Rgb(255, 255, 255));
+        assert_eq!(theme.accent, Color::Rgb(0, 0, 0xaa));
+        assert_eq!(theme.ansi[9], Color::Rgb(0xff, 0x55, 0x55));
+        assert_eq!(theme.ansi[12], theme.ansi[4]);
+        // no selection color, so the overlay is blended
+        assert_eq!(theme.overlay, Color::Rgb(41, 41, 41));
+    }
+
+    #[test]
+    fn alacritty_needs_the_primary_colors() {
+        let error = alacritty(&ALACRITTY.replace("foreground = \"#ffffff\"\n", "")).unwrap_err();
+        assert_eq!(error.to_string(), "missing colors.primary.foreground");
+        assert!(alacritty("[font]\nsize = 11\n").is_err());
+    }
+
+    fn iterm_color(name: &str, (r, g, b): (f64, f64, f64)) -> String {
+        format!(
+            "<key>{name}</key>\n<dict>\n<key>Color Space</key>\n<string>sRGB</string>\n\
+             <key>Blue Component</key>\n<real>{b}</real>\n<key>Green Component</key>\n<real>{g}</real>\n\
+             <key>Red Component</key>\n<real>{r}</real>\n</dict>\n"
+        )
+    }
+
+    fn iterm_scheme(skip: Option<usize>) -> String {
+        let mut body = String::new();
+        for index in (0..16).filter(|index| Some(*index) != skip) {
+            body += &iterm_color(&format!("Ansi {index} Color"), (f64::from(index as u8) / 15.0, 0.0, 0.0));
+        }
+        body += &iterm_color("Background Color", (0.0, 0.0, 0.0));
+        body += &iterm_color("Foreground Color", (1.0, 1.0, 1.0));
+        body += &iterm_color("Selection Color", (0.2, 0.4, 0.6));
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE plist>\n\
+             <plist version=\"1.0\">\n<dict>\n{body}</dict>\n</plist>\n"
+        )
+    }
+
+    #[test]
+    fn iterm_reads_the_plist_components() {
+        let theme = iterm(&iterm_scheme(None)).unwrap();
+        assert_eq!(theme.base, Color::Rgb(0, 0, 0));
+        assert_eq!(theme.text, Color::Rgb(255, 255, 255));
+        assert_eq!(theme.overlay, Color::Rgb(51, 102, 153));
+        assert_eq!(theme.ansi[0], Color::Rgb(0, 0, 0));
+        assert_eq!(theme.ansi[5], Color::Rgb(85, 0, 0));
+        assert_eq!(theme.ansi[15], Color::Rgb(255, 0, 0));
+    }
+
+    #[test]
+    fn iterm_names_a_missing_color() {
+        let error = iterm(&iterm_scheme(Some(3))).unwrap_err();
+        assert_eq!(error.to_string(), "missing Ansi 3 Color");
+    }
+}
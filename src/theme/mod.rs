@@ -0,0 +1,290 @@
+pub mod background;
+pub mod convert;
+pub mod depth;
+pub mod import;
+
+use std::{fmt, str::FromStr};
+
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Mango,
+    #[serde(alias = "gruvbox")]
+    GruvboxDark,
+    GruvboxLight,
+    #[serde(alias = "catpuccin")]
+    Catppuccin,
+    Light,
+    Dark,
+    Matrix,
+    Forest,
+    Night,
+}
+
+// names older versions used; still read (e.g. from `MANGSH_THEME`) but
+// never written
+const LEGACY_KEYS: &[(&str, Theme)] = &[("gruvbox", Theme::GruvboxDark), ("catpuccin", Theme::Catppuccin)];
+
+pub fn all_themes() -> &'static [Theme] {
+    &[
+        Theme::Mango,
+        Theme::Forest,
+        Theme::Night,
+        Theme::GruvboxDark,
+        Theme::GruvboxLight,
+        Theme::Catppuccin,
+        Theme::Light,
+        Theme::Dark,
+        Theme::Matrix,
+    ]
+}
+
+impl Theme {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Mango => "Mango",
+            Theme::Forest => "Forest",
+            Theme::Night => "Night",
+            Theme::GruvboxDark => "Gruvbox Dark",
+            Theme::GruvboxLight => "Gruvbox Light",
+            Theme::Catppuccin => "Catppuccin",
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+            Theme::Matrix => "Matrix",
+        }
+    }
+
+    /// The name used for the theme in the config file.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Theme::Mango => "mango",
+            Theme::Forest => "forest",
+            Theme::Night => "night",
+            Theme::GruvboxDark => "gruvbox-dark",
+            Theme::GruvboxLight => "gruvbox-light",
+            Theme::Catppuccin => "catppuccin",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::Matrix => "matrix",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Theme> {
+        all_themes()
+            .iter()
+            .copied()
+            .find(|theme| theme.key() == key)
+            .or_else(|| Theme::from_legacy_key(key))
+    }
+
+    /// The theme an old name like `gruvbox` stands for.
+    pub fn from_legacy_key(key: &str) -> Option<Theme> {
+        LEGACY_KEYS.iter().find(|(legacy, _)| *legacy == key).map(|(_, theme)| *theme)
+    }
+}
+
+/// A theme as picked in the config: a built-in, the name of a custom
+/// theme defined under `[[themes]]` or in the `themes/` dir, or `auto`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThemeName {
+    Builtin(Theme),
+    Custom(String),
+    /// `light_theme` or `dark_theme`, whichever suits the terminal's
+    /// background.
+    Auto,
+}
+
+const AUTO: &str = "auto";
+
+impl Default for ThemeName {
+    fn default() -> Self {
+        ThemeName::Builtin(Theme::default())
+    }
+}
+
+impl ThemeName {
+    pub fn parse(name: &str) -> Self {
+        if name == AUTO {
+            return ThemeName::Auto;
+        }
+        Theme::from_key(name).map_or_else(|| ThemeName::Custom(name.to_string()), ThemeName::Builtin)
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            ThemeName::Builtin(theme) => theme.label(),
+            ThemeName::Custom(name) => name,
+            ThemeName::Auto => "Auto",
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            ThemeName::Builtin(theme) => theme.key(),
+            ThemeName::Custom(name) => name,
+            ThemeName::Auto => AUTO,
+        }
+    }
+}
+
+/// Checks that `name` works for a new custom theme: plain enough for a file
+/// name and `theme = "..."`, and not taken by a built-in.
+pub fn check_theme_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Give the theme a name.".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Theme names can only use letters, digits, - and _.".to_string());
+    }
+    if !matches!(ThemeName::parse(name), ThemeName::Custom(_)) {
+        return Err(format!("{name} is a built-in theme; pick another name."));
+    }
+    Ok(())
+}
+
+impl fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+impl Serialize for ThemeName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.key())
+    }
+}
+
+impl<'de> Deserialize<'de> for ThemeName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(ThemeName::parse(&name))
+    }
+}
+
+// a user-defined theme; every slot takes `#rrggbb`, a color name like
+// `light-blue`, or a 256-color index
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CustomTheme {
+    /// Defaults to the file name for themes in the `themes/` dir.
+    #[serde(default)]
+    pub name: String,
+    #[serde(with = "color")]
+    pub base: Color,
+    #[serde(with = "color")]
+    pub base_alt: Color,
+    #[serde(with = "color")]
+    pub surface: Color,
+    #[serde(with = "color")]
+    pub overlay: Color,
+    #[serde(with = "color")]
+    pub accent: Color,
+    #[serde(with = "color")]
+    pub accent_soft: Color,
+    #[serde(with = "color")]
+    pub text: Color,
+    #[serde(with = "color")]
+    pub text_muted: Color,
+    /// The 16 ANSI colors sessions render with; when left out the
+    /// terminal's own are used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "ansi")]
+    pub ansi: Vec<Color>,
+}
+
+/// The palette slots every theme sets, as named in the config.
+pub const SLOTS: [&str; 8] = [
+    "base",
+    "base_alt",
+    "surface",
+    "overlay",
+    "accent",
+    "accent_soft",
+    "text",
+    "text_muted",
+];
+
+impl CustomTheme {
+    pub fn ansi(&self) -> Option<[Color; 16]> {
+        self.ansi.as_slice().try_into().ok()
+    }
+
+    /// The color in one of the `SLOTS`.
+    pub fn slot(&self, slot: &str) -> Option<Color> {
+        match slot {
+            "base" => Some(self.base),
+            "base_alt" => Some(self.base_alt),
+            "surface" => Some(self.surface),
+            "overlay" => Some(self.overlay),
+            "accent" => Some(self.accent),
+            "accent_soft" => Some(self.accent_soft),
+            "text" => Some(self.text),
+            "text_muted" => Some(self.text_muted),
+            _ => None,
+        }
+    }
+
+    pub fn slot_mut(&mut self, slot: &str) -> Option<&mut Color> {
+        match slot {
+            "base" => Some(&mut self.base),
+            "base_alt" => Some(&mut self.base_alt),
+            "surface" => Some(&mut self.surface),
+            "overlay" => Some(&mut self.overlay),
+            "accent" => Some(&mut self.accent),
+            "accent_soft" => Some(&mut self.accent_soft),
+            "text" => Some(&mut self.text),
+            "text_muted" => Some(&mut self.text_muted),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a color as written in a theme: `#rrggbb`, a name or 0-255.
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    color::parse(value)
+}
+
+// colors as config strings, with an error that says what's accepted
+mod color {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(color)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse(&value).map_err(serde::de::Error::custom)
+    }
+
+    pub fn parse(value: &str) -> Result<Color, String> {
+        Color::from_str(value)
+            .map_err(|_| format!("invalid color `{value}`; use #rrggbb, a name like `light-blue`, or 0-255"))
+    }
+}
+
+// exactly 16 colors, black through bright white
+mod ansi {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(colors: &[Color], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(colors.iter().map(Color::to_string))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+        let values = Vec::<String>::deserialize(deserializer)?;
+        if values.len() != 16 {
+            return Err(serde::de::Error::custom(format!(
+                "`ansi` needs 16 colors (black to bright white), found {}",
+                values.len()
+            )));
+        }
+        values
+            .iter()
+            .map(|value| super::color::parse(value))
+            .collect::<Result<_, _>>()
+            .map_err(serde::de::Error::custom)
+    }
+}
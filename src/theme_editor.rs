@@ -0,0 +1,294 @@
+use std::path::Path;
+
+use ratatui::style::Color;
+
+use crate::{
+    config::{loader::theme_path, Config},
+    theme::{
+        convert::{from_hsl, to_hsl, to_rgb, Hsl},
+        check_theme_name, parse_color, CustomTheme, ThemeName, SLOTS,
+    },
+    ui::panels::Palette,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorModel {
+    Rgb,
+    Hsl,
+}
+
+impl ColorModel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorModel::Rgb => "RGB",
+            ColorModel::Hsl => "HSL",
+        }
+    }
+}
+
+/// A line of text being typed on the editor screen.
+pub enum EditorInput {
+    /// A color for the selected slot.
+    Hex(String),
+    /// The name to save the theme under.
+    Name(String),
+}
+
+/// One slider: its label, current value and the top of its range.
+pub struct Channel {
+    pub label: &'static str,
+    pub value: f64,
+    pub max: f64,
+}
+
+// state for the theme editor screen; edits a copy of the active theme's
+// palette until it's saved as a new custom theme
+pub struct ThemeEditor {
+    /// The theme the edit started from.
+    pub based_on: ThemeName,
+    pub theme: CustomTheme,
+    original: CustomTheme,
+    /// Index into `SLOTS`.
+    pub slot: usize,
+    pub model: ColorModel,
+    /// The slider ←/→ moves.
+    pub channel: usize,
+    // the selected slot in HSL, kept separately so hue and saturation
+    // survive passing through black, white or grey
+    hsl: Hsl,
+    pub input: Option<EditorInput>,
+    pub notice: Option<String>,
+}
+
+impl ThemeEditor {
+    pub fn new(based_on: ThemeName, palette: Palette) -> Self {
+        let theme = CustomTheme {
+            name: String::new(),
+            base: palette.base,
+            base_alt: palette.base_alt,
+            surface: palette.surface,
+            overlay: palette.overlay,
+            accent: palette.accent,
+            accent_soft: palette.accent_soft,
+            text: palette.text,
+            text_muted: palette.text_muted,
+            ansi: palette.ansi.map(Vec::from).unwrap_or_default(),
+        };
+        let hsl = to_hsl(to_rgb(theme.base));
+        Self {
+            based_on,
+            original: theme.clone(),
+            theme,
+            slot: 0,
+            model: ColorModel::Rgb,
+            channel: 0,
+            hsl,
+            input: None,
+            notice: None,
+        }
+    }
+
+    pub fn slot_name(&self) -> &'static str {
+        SLOTS[self.slot]
+    }
+
+    pub fn color(&self) -> Color {
+        self.theme.slot(self.slot_name()).unwrap_or(Color::Reset)
+    }
+
+    pub fn modified(&self) -> bool {
+        self.theme != self.original
+    }
+
+    pub fn next_slot(&mut self) {
+        self.select((self.slot + 1) % SLOTS.len());
+    }
+
+    pub fn previous_slot(&mut self) {
+        self.select(self.slot.checked_sub(1).unwrap_or(SLOTS.len() - 1));
+    }
+
+    fn select(&mut self, slot: usize) {
+        self.slot = slot;
+        self.hsl = to_hsl(to_rgb(self.color()));
+    }
+
+    pub fn next_channel(&mut self) {
+        self.channel = (self.channel + 1) % 3;
+    }
+
+    pub fn previous_channel(&mut self) {
+        self.channel = self.channel.checked_sub(1).unwrap_or(2);
+    }
+
+    pub fn toggle_model(&mut self) {
+        self.model = match self.model {
+            ColorModel::Rgb => ColorModel::Hsl,
+            ColorModel::Hsl => ColorModel::Rgb,
+        };
+    }
+
+    /// The three sliders for the selected slot in the current model.
+    pub fn channels(&self) -> [Channel; 3] {
+        match self.model {
+            ColorModel::Rgb => {
+                let (r, g, b) = to_rgb(self.color());
+                [
+                    Channel { label: "R", value: f64::from(r), max: 255.0 },
+                    Channel { label: "G", value: f64::from(g), max: 255.0 },
+                    Channel { label: "B", value: f64::from(b), max: 255.0 },
+                ]
+            }
+            ColorModel::Hsl => {
+                let (h, s, l) = self.hsl;
+                [
+                    Channel { label: "H", value: h, max: 360.0 },
+                    Channel { label: "S", value: s, max: 100.0 },
+                    Channel { label: "L", value: l, max: 100.0 },
+                ]
+            }
+        }
+    }
+
+    /// What the selected slot would look like with `channel` at `value`;
+    /// used to shade the sliders.
+    pub fn with_channel(&self, channel: usize, value: f64) -> Color {
+        match self.model {
+            ColorModel::Rgb => {
+                let mut rgb = to_rgb(self.color());
+                let value = value.round().clamp(0.0, 255.0) as u8;
+                match channel {
+                    0 => rgb.0 = value,
+                    1 => rgb.1 = value,
+                    _ => rgb.2 = value,
+                }
+                Color::Rgb(rgb.0, rgb.1, rgb.2)
+            }
+            ColorModel::Hsl => {
+                let mut hsl = self.hsl;
+                match channel {
+                    0 => hsl.0 = value,
+                    1 => hsl.1 = value,
+                    _ => hsl.2 = value,
+                }
+                let (r, g, b) = from_hsl(hsl);
+                Color::Rgb(r, g, b)
+            }
+        }
+    }
+
+    /// Moves the focused slider by `step`; hue wraps around, everything
+    /// else stops at the ends.
+    pub fn adjust(&mut self, step: f64) {
+        let channel = &self.channels()[self.channel];
+        let value = match (self.model, self.channel) {
+            (ColorModel::Hsl, 0) => (channel.value + step).rem_euclid(360.0),
+            _ => (channel.value + step).clamp(0.0, channel.max),
+        };
+        let color = self.with_channel(self.channel, value);
+        match self.model {
+            ColorModel::Rgb => self.set(color),
+            ColorModel::Hsl => {
+                match self.channel {
+                    0 => self.hsl.0 = value,
+                    1 => self.hsl.1 = value,
+                    _ => self.hsl.2 = value,
+                }
+                self.store(color);
+            }
+        }
+    }
+
+    /// Sets the selected slot, e.g. from typed hex.
+    pub fn set(&mut self, color: Color) {
+        self.store(color);
+        self.hsl = to_hsl(to_rgb(color));
+    }
+
+    fn store(&mut self, color: Color) {
+        if let Some(slot) = self.theme.slot_mut(SLOTS[self.slot]) {
+            *slot = color;
+        }
+    }
+
+    /// Puts the selected slot back to what it was when the editor opened.
+    pub fn reset_slot(&mut self) {
+        if let Some(color) = self.original.slot(self.slot_name()) {
+            self.set(color);
+        }
+    }
+
+    pub fn start_hex(&mut self) {
+        self.notice = None;
+        self.input = Some(EditorInput::Hex(String::new()));
+    }
+
+    pub fn start_save(&mut self) {
+        self.notice = None;
+        let name = match &self.based_on {
+            ThemeName::Builtin(theme) => format!("my-{}", theme.key()),
+            ThemeName::Custom(name) => format!("{name}-2"),
+            ThemeName::Auto => "my-theme".to_string(),
+        };
+        self.input = Some(EditorInput::Name(name));
+    }
+
+    pub fn push(&mut self, c: char) {
+        match &mut self.input {
+            Some(EditorInput::Hex(text) | EditorInput::Name(text)) => text.push(c),
+            None => {}
+        }
+    }
+
+    pub fn pop(&mut self) {
+        match &mut self.input {
+            Some(EditorInput::Hex(text) | EditorInput::Name(text)) => {
+                text.pop();
+            }
+            None => {}
+        }
+    }
+
+    /// Applies the typed hex; on a bad color the prompt stays open.
+    pub fn apply_hex(&mut self) {
+        let Some(EditorInput::Hex(text)) = &self.input else {
+            return;
+        };
+        let text = text.trim();
+        // a bare `1d1f21` is taken as hex
+        let value = match text.len() == 6 && text.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => format!("#{text}"),
+            false => text.to_string(),
+        };
+        match parse_color(&value) {
+            Ok(color) => {
+                self.set(color);
+                self.input = None;
+            }
+            Err(err) => self.notice = Some(err),
+        }
+    }
+
+    /// The theme under the typed name, if the name is free to use.
+    pub fn named_theme(&mut self, config: &Config, config_path: &Path) -> Option<CustomTheme> {
+        let Some(EditorInput::Name(name)) = &self.input else {
+            return None;
+        };
+        let name = name.trim();
+        let problem = if let Err(problem) = check_theme_name(name) {
+            Some(problem)
+        } else if config.find_theme(name).is_some() || theme_path(config_path, name).exists() {
+            Some(format!("A theme called {name} already exists; pick another name."))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            self.notice = Some(problem);
+            return None;
+        }
+        Some(CustomTheme {
+            name: name.to_string(),
+            ..self.theme.clone()
+        })
+    }
+}
@@ -1,9 +1,85 @@
-use ratatui::layout::{Layout, Constraint, Rect};
+use ratatui::layout::{Layout, Constraint, Margin, Rect};
 use std::rc::Rc;
 
+/// Drawn between the Navigation tabs.
+pub const TAB_DIVIDER: &str = " · ";
+/// How wide the selected host's details start out beside the host list.
+pub const DETAIL_WIDTH: u16 = 56;
+// narrowest the host list and its details get when the border is dragged
+const MIN_LIST_WIDTH: u16 = 40;
+const MIN_DETAIL_WIDTH: u16 = 30;
+
+// navigation, body, status bar
 pub fn split_main(area: Rect) -> Rc<[Rect]> {
     Layout::vertical([
         Constraint::Length(3),
         Constraint::Fill(1),
+        Constraint::Length(1),
     ]).split(area)
 }
+
+// the area a session's remote pty is drawn into (main body minus borders)
+pub fn session_pane(area: Rect) -> Rect {
+    split_main(area)[1].inner(Margin::new(1, 1))
+}
+
+// which Navigation tab a click at `column` lands on; ratatui pads each
+// title with a space either side and puts the divider between them
+pub fn tab_at(nav: Rect, tabs: &[&str], column: u16) -> Option<usize> {
+    let mut x = nav.x + 1;
+    for (index, tab) in tabs.iter().enumerate() {
+        let width = tab.chars().count() as u16 + 2;
+        if (x..x + width).contains(&column) {
+            return Some(index);
+        }
+        x += width + TAB_DIVIDER.chars().count() as u16;
+    }
+    None
+}
+
+/// Where the Hosts tab puts things inside the main body.
+pub struct HostsPanes {
+    pub list: Rect,
+    /// The selected host's details, with the draggable border on its left.
+    pub detail: Option<Rect>,
+    pub hint: Rect,
+}
+
+// the selected host's details sit beside the list when there's room for both
+pub fn hosts_panes(body: Rect, detail_width: u16, detail: bool) -> HostsPanes {
+    let [main, hint] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(body.inner(Margin::new(1, 1)));
+    if !detail || main.width < 100 {
+        return HostsPanes { list: main, detail: None, hint };
+    }
+    let [list, detail] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(fit_detail(main, detail_width))]).areas(main);
+    HostsPanes { list, detail: Some(detail), hint }
+}
+
+/// The detail width a drag of its border to `column` asks for.
+pub fn drag_detail(body: Rect, column: u16) -> u16 {
+    let main = body.inner(Margin::new(1, 1));
+    fit_detail(main, main.right().saturating_sub(column))
+}
+
+fn fit_detail(main: Rect, width: u16) -> u16 {
+    width.min(main.width.saturating_sub(MIN_LIST_WIDTH)).max(MIN_DETAIL_WIDTH)
+}
+
+/// The fleet screen's host picker and the command/results side, split at
+/// `split` percent.
+pub fn fleet_columns(area: Rect, split: u16) -> [Rect; 2] {
+    Layout::horizontal([Constraint::Percentage(split), Constraint::Fill(1)]).areas(area)
+}
+
+/// The split a drag of the fleet border to `column` asks for.
+pub fn drag_fleet(area: Rect, column: u16) -> u16 {
+    let offset = u32::from(column.saturating_sub(area.x) + 1);
+    (offset * 100 / u32::from(area.width.max(1))).clamp(15, 70) as u16
+}
+
+// the item a click on `row` lands on in a single-line-item list that
+// ratatui has scrolled just far enough to keep `selected` in view
+pub fn list_item_at(list: Rect, selected: usize, row: u16) -> usize {
+    let offset = (selected + 1).saturating_sub(usize::from(list.height));
+    offset + usize::from(row - list.y)
+}
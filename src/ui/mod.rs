@@ -2,15 +2,18 @@ pub mod layout;
 pub mod panels;
 
 use ratatui::Frame;
+use ratatui::buffer::Buffer;
 use ratatui::widgets::{Block, Borders, Tabs};
 use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
 use crate::app::{App, Screen};
+use crate::theme::depth::ColorDepth;
 
-use self::layout::split_main;
-use self::panels::{draw_hosts, draw_session, draw_title_screen, draw_config_screen, draw_menu_screen, palette};
+use self::layout::{split_main, TAB_DIVIDER};
+use self::panels::{draw_hosts, draw_session, draw_title_screen, draw_config_screen, draw_menu_screen, draw_fleet_screen, draw_host_form_screen, draw_theme_editor_screen, draw_command_palette, draw_help, draw_status_bar, palette, Palette};
 
 pub fn draw(frame: &mut Frame, app: &App) {
-    let theme = palette(app.theme);
+    let theme = palette(&app.active_theme(), &app.config);
     let backdrop = Block::default()
         .borders(Borders::NONE)
         .style(Style::default().bg(theme.base));
@@ -18,22 +21,44 @@ pub fn draw(frame: &mut Frame, app: &App) {
 
     match app.screen {
         Screen::Title => {
-            draw_title_screen(frame, theme);
+            draw_title_screen(frame, app, theme);
         }
         Screen::Config => {
             draw_config_screen(frame, app, theme);
         }
         Screen::Menu => {
-            draw_menu_screen(frame, theme);
+            draw_menu_screen(frame, app, theme);
+        }
+        Screen::Fleet => {
+            draw_fleet_screen(frame, app, theme);
+        }
+        Screen::HostForm => {
+            draw_host_form_screen(frame, app, theme);
+        }
+        Screen::ThemeEditor => {
+            draw_theme_editor_screen(frame, app, theme);
         }
         Screen::Main => {
             let chunks = split_main(frame.area());
+            // while a session has the keyboard, say how to get it back
+            let prefix = app.keymap.prefix.label();
+            let indicator = if app.prefix_armed {
+                Line::from(Span::styled(
+                    format!(" {prefix} · waiting for a key "),
+                    Style::default().fg(theme.base).bg(theme.accent).add_modifier(Modifier::BOLD),
+                ))
+            } else if app.in_session() {
+                Line::from(Span::styled(format!(" {prefix} for mangsh "), Style::default().fg(theme.text_muted)))
+            } else {
+                Line::default()
+            };
             let tabs = Tabs::new(app.tabs.clone())
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
                         .border_style(Style::default().fg(theme.accent))
                         .title("Navigation")
+                        .title_top(indicator.right_aligned())
                         .style(Style::default().bg(theme.surface).fg(theme.text)),
                 )
                 .select(app.tab_index)
@@ -43,15 +68,44 @@ pub fn draw(frame: &mut Frame, app: &App) {
                         .fg(theme.accent)
                         .add_modifier(Modifier::BOLD)
                 )
-                .divider(" · ");
+                .divider(TAB_DIVIDER);
 
             frame.render_widget(tabs, chunks[0]);
+            draw_status_bar(frame, chunks[2], app, theme);
 
             match app.tab_index {
-                0 => draw_hosts(frame, chunks[1], theme),
-                1 => draw_session(frame, chunks[1], theme),
-                _ => draw_hosts(frame, chunks[1], theme),
+                0 => draw_hosts(frame, chunks[1], app, theme),
+                1 => draw_session(frame, chunks[1], app, theme),
+                _ => draw_hosts(frame, chunks[1], app, theme),
+            }
+        }
+    }
+    draw_help(frame, app, theme);
+    draw_command_palette(frame, app, theme);
+    fit_colors(frame.buffer_mut(), app.colors.depth, theme);
+}
+
+// everything is drawn in the palette's own colors, then brought down to
+// what the terminal can show. Without color, what the palette would have
+// picked out gets a modifier instead: accent backgrounds (selections,
+// badges) turn into reverse video, accent text bold and muted text dim.
+fn fit_colors(buffer: &mut Buffer, depth: ColorDepth, palette: Palette) {
+    if depth == ColorDepth::TrueColor {
+        return;
+    }
+    let accents = [palette.accent, palette.accent_soft];
+    for cell in &mut buffer.content {
+        if depth == ColorDepth::Mono {
+            if accents.contains(&cell.bg) {
+                cell.modifier.insert(Modifier::REVERSED);
+            } else if accents.contains(&cell.fg) {
+                cell.modifier.insert(Modifier::BOLD);
+            } else if cell.fg == palette.text_muted {
+                cell.modifier.insert(Modifier::DIM);
             }
         }
+        cell.fg = depth.fit(cell.fg);
+        cell.bg = depth.fit(cell.bg);
+        cell.underline_color = depth.fit(cell.underline_color);
     }
 }
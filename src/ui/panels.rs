@@ -1,15 +1,37 @@
+use std::time::SystemTime;
+
 use ratatui::{
+    buffer::Buffer,
     layout::{Constraint, Layout, Rect},
     prelude::Alignment,
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Cell, Clear, List, ListItem, ListState, Padding, Paragraph, Row, Table, TableState, Widget, Wrap},
     Frame,
 };
 
 use crate::{
-    app::App,
-    theme::{all_themes, Theme},
+    app::{App, Screen},
+    command_palette::Command,
+    fleet::{FleetFocus, RowStatus},
+    config::{
+        Config,
+        diagnostic::Severity,
+        inherit::{ResolvedHost, Source},
+        layers::Origin,
+        loader::Backup,
+    },
+    host_form::{FormField, FIELDS},
+    keys::{Action, KeyChord, Scope},
+    hosts::{Grouping, HostHit, HostRow},
+    ssh::{exec::Stream, session::Selection},
+    theme::{
+        background::Brightness,
+        convert::{luminance, to_rgb},
+        CustomTheme, Theme, ThemeName, SLOTS,
+    },
+    theme_editor::{ColorModel, EditorInput},
+    ui::layout::{fleet_columns, hosts_panes},
 };
 
 #[derive(Clone, Copy)]
@@ -22,9 +44,39 @@ pub struct Palette {
     pub accent_soft: Color,
     pub text: Color,
     pub text_muted: Color,
+    /// ANSI colors 0-15 for session output; `None` leaves them to the
+    /// terminal.
+    pub ansi: Option<[Color; 16]>,
+}
+
+/// Colors for the active theme; a custom theme that's gone missing falls
+/// back to the default. `auto` should be resolved first (see
+/// `App::active_theme`); on its own it's taken to mean a dark background.
+pub fn palette(theme: &ThemeName, config: &Config) -> Palette {
+    match theme {
+        ThemeName::Builtin(theme) => builtin_palette(*theme),
+        ThemeName::Custom(name) => config
+            .find_theme(name)
+            .map_or_else(|| builtin_palette(Theme::default()), custom_palette),
+        ThemeName::Auto => palette(&config.auto_theme(Brightness::Dark), config),
+    }
+}
+
+pub fn custom_palette(theme: &CustomTheme) -> Palette {
+    Palette {
+        base: theme.base,
+        base_alt: theme.base_alt,
+        surface: theme.surface,
+        overlay: theme.overlay,
+        accent: theme.accent,
+        accent_soft: theme.accent_soft,
+        text: theme.text,
+        text_muted: theme.text_muted,
+        ansi: theme.ansi(),
+    }
 }
 
-pub fn palette(theme: Theme) -> Palette {
+pub fn builtin_palette(theme: Theme) -> Palette {
     match theme {
         Theme::Mango => Palette {
             base: Color::Rgb(12, 10, 8),
@@ -35,6 +87,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(255, 210, 142),
             text: Color::Rgb(255, 244, 228),
             text_muted: Color::Rgb(214, 183, 150),
+            ansi: None,
         },
         Theme::Forest => Palette {
             base: Color::Rgb(9, 14, 10),
@@ -45,6 +98,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(171, 230, 176),
             text: Color::Rgb(226, 241, 229),
             text_muted: Color::Rgb(170, 193, 176),
+            ansi: None,
         },
         Theme::GruvboxDark => Palette {
             base: Color::Rgb(40, 40, 40),
@@ -55,6 +109,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(214, 93, 14),
             text: Color::Rgb(235, 219, 178),
             text_muted: Color::Rgb(189, 174, 147),
+            ansi: None,
         },
         Theme::GruvboxLight => Palette {
             base: Color::Rgb(249, 245, 215),
@@ -65,6 +120,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(214, 93, 14),
             text: Color::Rgb(60, 56, 54),
             text_muted: Color::Rgb(102, 92, 84),
+            ansi: None,
         },
         Theme::Catppuccin => Palette {
             base: Color::Rgb(24, 25, 38),
@@ -75,6 +131,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(245, 189, 230),
             text: Color::Rgb(202, 211, 245),
             text_muted: Color::Rgb(165, 173, 206),
+            ansi: None,
         },
         Theme::Light => Palette {
             base: Color::Rgb(245, 245, 245),
@@ -85,6 +142,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(129, 161, 255),
             text: Color::Rgb(35, 38, 47),
             text_muted: Color::Rgb(92, 99, 112),
+            ansi: None,
         },
         Theme::Dark => Palette {
             base: Color::Rgb(12, 12, 14),
@@ -95,6 +153,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(167, 139, 250),
             text: Color::Rgb(229, 231, 235),
             text_muted: Color::Rgb(156, 163, 175),
+            ansi: None,
         },
         Theme::Matrix => Palette {
             base: Color::Rgb(0, 0, 0),
@@ -105,6 +164,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(120, 255, 160),
             text: Color::Rgb(180, 255, 200),
             text_muted: Color::Rgb(80, 140, 90),
+            ansi: None,
         },
         Theme::Night => Palette {
             base: Color::Rgb(8, 10, 18),
@@ -115,6 +175,7 @@ pub fn palette(theme: Theme) -> Palette {
             accent_soft: Color::Rgb(170, 193, 255),
             text: Color::Rgb(228, 234, 255),
             text_muted: Color::Rgb(177, 187, 211),
+            ansi: None,
         },
     }
 }
@@ -122,7 +183,7 @@ pub fn palette(theme: Theme) -> Palette {
 //
 // TITLE SCREEN
 //
-pub fn draw_title_screen(frame: &mut Frame, palette: Palette) {
+pub fn draw_title_screen(frame: &mut Frame, app: &App, palette: Palette) {
     let area = frame.area();
 
     let backdrop = Block::default()
@@ -229,11 +290,11 @@ pub fn draw_title_screen(frame: &mut Frame, palette: Palette) {
 
     let hint_box = Paragraph::new(Text::from(vec![
         Line::from(Span::styled(
-            "Enter → jump in",
+            key_hints(app, Scope::Title, &[(Action::OpenHosts, "jump in")]),
             Style::default().fg(palette.text),
         )),
         Line::from(Span::styled(
-            "M → quick menu    q → quit",
+            key_hints(app, Scope::Title, &[(Action::OpenMenu, "quick menu"), (Action::Help, "keys"), (Action::Quit, "quit")]),
             Style::default().fg(palette.text_muted),
         )),
     ]))
@@ -251,7 +312,7 @@ pub fn draw_title_screen(frame: &mut Frame, palette: Palette) {
 //
 // MAIN MENU SCREEN
 //
-pub fn draw_menu_screen(frame: &mut Frame, palette: Palette) {
+pub fn draw_menu_screen(frame: &mut Frame, app: &App, palette: Palette) {
     let area = frame.area();
 
     let backdrop = Block::default()
@@ -261,7 +322,7 @@ pub fn draw_menu_screen(frame: &mut Frame, palette: Palette) {
 
     let inner = Layout::vertical([
         Constraint::Length(8),
-        Constraint::Length(7),
+        Constraint::Length(8),
         Constraint::Fill(1),
     ])
     .margin(2)
@@ -286,10 +347,22 @@ pub fn draw_menu_screen(frame: &mut Frame, palette: Palette) {
 
     frame.render_widget(title, inner[0]);
 
+    // each entry under the first key bound to it
+    let option = |action: Action, label: &str| {
+        let key = app.keymap.keys(Scope::Menu, action).first().map(KeyChord::label);
+        match key {
+            Some(key) => format!("[{key}] {label}"),
+            None => label.to_string(),
+        }
+    };
     let options = vec![
-        Line::from(Span::styled("[1] Hosts & Session", Style::default().fg(palette.text))),
-        Line::from(Span::styled("[2] Config", Style::default().fg(palette.text))),
-        Line::from(Span::styled("[q] Quit   [Esc] Title", Style::default().fg(palette.text_muted))),
+        Line::from(Span::styled(option(Action::OpenHosts, "Hosts & Session"), Style::default().fg(palette.text))),
+        Line::from(Span::styled(option(Action::OpenConfig, "Config"), Style::default().fg(palette.text))),
+        Line::from(Span::styled(option(Action::OpenFleet, "Fleet runner"), Style::default().fg(palette.text))),
+        Line::from(Span::styled(
+            format!("{}   {}", option(Action::Quit, "Quit"), option(Action::Back, "Title")),
+            Style::default().fg(palette.text_muted),
+        )),
     ];
 
     let options_para = Paragraph::new(Text::from(options))
@@ -306,7 +379,7 @@ pub fn draw_menu_screen(frame: &mut Frame, palette: Palette) {
 
     let hint = Paragraph::new(Text::from(vec![
         Line::from(Span::styled(
-            "Navigate with arrows · M to toggle menu",
+            key_hints(app, Scope::Menu, &[(Action::Help, "every key"), (Action::CommandPalette, "command palette")]),
             Style::default()
                 .fg(palette.text)
                 .add_modifier(Modifier::BOLD),
@@ -345,7 +418,7 @@ pub fn draw_config_screen(frame: &mut Frame, app: &App, palette: Palette) {
 
     let inner = body.inner(area);
     let sections = Layout::vertical([
-        Constraint::Length(6),
+        Constraint::Length(10),
         Constraint::Fill(1),
     ])
     .margin(1)
@@ -356,24 +429,50 @@ pub fn draw_config_screen(frame: &mut Frame, app: &App, palette: Palette) {
         .as_ref()
         .map(|p| format!("Config file: {}", p.display()))
         .unwrap_or_else(|| "Config file: not found (using defaults)".to_string());
-    let origin = if app.config.theme.is_some() {
-        "Source: configured theme"
-    } else {
-        "Source: default theme"
+    let origin = match &app.theme {
+        ThemeName::Custom(name) => format!(
+            "Source: {} · defined in {}",
+            origin_text(&app.layers.theme_origin()),
+            origin_text(&app.layers.custom_theme_origin(name))
+        ),
+        ThemeName::Builtin(_) => format!("Source: {}", origin_text(&app.layers.theme_origin())),
+        ThemeName::Auto => {
+            let background = match &app.background {
+                Some(background) => format!("{} background ({})", background.brightness.label(), background.reason),
+                None => "background not checked yet".to_string(),
+            };
+            format!("Source: {} · {background}", origin_text(&app.layers.theme_origin()))
+        }
     };
     let status = app
         .config_error
         .as_deref()
         .unwrap_or("Loaded successfully.");
-    let theme_names = all_themes()
-        .iter()
-        .map(|t| t.label())
-        .collect::<Vec<_>>()
-        .join(" · ");
+    let mut theme_names = vec![Span::styled("Available: ", Style::default().fg(palette.text_muted))];
+    let mut seen_custom = false;
+    for (n, theme) in app.config.theme_names().iter().enumerate() {
+        let separator = match theme {
+            ThemeName::Custom(_) if !seen_custom => {
+                seen_custom = true;
+                " · custom: "
+            }
+            _ if n == 0 => "",
+            _ => " · ",
+        };
+        theme_names.push(Span::styled(separator, Style::default().fg(palette.text_muted)));
+        let style = match *theme == app.theme {
+            true => Style::default().fg(palette.accent).add_modifier(Modifier::BOLD),
+            false => Style::default().fg(palette.text_muted),
+        };
+        theme_names.push(Span::styled(theme.label().to_string(), style));
+    }
 
     let theme_box = Paragraph::new(Text::from(vec![
         Line::from(Span::styled(
-            format!("Theme: {}", app.theme.label()),
+            match app.theme {
+                ThemeName::Auto => format!("Theme: Auto → {}", app.active_theme().label()),
+                _ => format!("Theme: {}", app.theme.label()),
+            },
             Style::default()
                 .fg(palette.base)
                 .bg(palette.accent_soft)
@@ -394,16 +493,34 @@ pub fn draw_config_screen(frame: &mut Frame, app: &App, palette: Palette) {
                 .add_modifier(Modifier::ITALIC),
         )),
         Line::from(""),
-        Line::from(Span::styled(
-            "Use ←/→ to switch theme (auto-saves).",
-            Style::default().fg(palette.text),
-        )),
-        Line::from(Span::styled(
-            format!("Available: {theme_names}"),
-            Style::default().fg(palette.text_muted),
-        )),
+        match &app.theme_import {
+            Some(path) => Line::from(vec![
+                Span::styled("Import scheme: ", Style::default().fg(palette.accent)),
+                Span::styled(path.clone(), Style::default().fg(palette.text)),
+                Span::styled(
+                    "   base16 .yaml, Alacritty .toml or .itermcolors · Enter import · Esc cancel",
+                    Style::default().fg(palette.text_muted),
+                ),
+            ]),
+            None => Line::from(Span::styled(
+                key_hints(
+                    app,
+                    Scope::Config,
+                    &[
+                        (Action::PreviousTheme, "previous theme"),
+                        (Action::NextTheme, "next theme (auto-saves)"),
+                        (Action::EditTheme, "edit colors"),
+                        (Action::ImportTheme, "import a scheme"),
+                        (Action::Backups, "restore a backup"),
+                    ],
+                ),
+                Style::default().fg(palette.text),
+            )),
+        },
+        Line::from(theme_names),
     ]))
     .alignment(Alignment::Left)
+    .wrap(Wrap { trim: true })
     .block(
         Block::default()
             .borders(Borders::ALL)
@@ -412,30 +529,761 @@ pub fn draw_config_screen(frame: &mut Frame, app: &App, palette: Palette) {
     );
 
     frame.render_widget(theme_box, sections[0]);
+    if let Some(path) = &app.theme_import {
+        // inside the box's border, on the prompt line
+        let x = sections[0].x + 1 + "Import scheme: ".len() as u16 + path.chars().count() as u16;
+        frame.set_cursor_position((x.min(sections[0].right().saturating_sub(2)), sections[0].y + 6));
+    }
+
+    if let Some(backups) = &app.backups {
+        draw_backups(frame, sections[1], backups, app.backup_index, palette);
+        return;
+    }
+    if app.diagnostics.is_empty() {
+        draw_sources(frame, sections[1], app, palette);
+        return;
+    }
+    // problems on top, the sources stay visible underneath
+    let split = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).split(sections[1]);
+    draw_diagnostics(frame, split[0], app, palette);
+    draw_sources(frame, split[1], app, palette);
+}
+
+fn origin_text(origin: &Origin) -> String {
+    match origin {
+        Origin::Default => origin.label(),
+        _ => format!("{} {}", origin.kind(), origin.label()),
+    }
+}
+
+// the layers that were loaded and which one each effective value came from
+fn draw_sources(frame: &mut Frame, area: Rect, app: &App, palette: Palette) {
+    let heading = Style::default().fg(palette.accent).add_modifier(Modifier::BOLD);
+    let muted = Style::default().fg(palette.text_muted);
+    let text = Style::default().fg(palette.text);
+    let row = |kind: &str, value: String, from: String| {
+        Line::from(vec![
+            Span::styled(format!("  {kind:<8} "), muted),
+            Span::styled(format!("{value:<24} "), text),
+            Span::styled(from, muted),
+        ])
+    };
+
+    let mut lines = vec![Line::from(Span::styled("Layers (lowest precedence first)", heading))];
+    for layer in &app.layers.layers {
+        let status = match (layer.found, &layer.origin) {
+            (false, _) => "not found".to_string(),
+            (true, Origin::Env(_)) => "set".to_string(),
+            (true, Origin::Theme(_)) => "custom theme".to_string(),
+            (true, _) => format!(
+                "{} host{} · {} group{}",
+                layer.hosts,
+                if layer.hosts == 1 { "" } else { "s" },
+                layer.groups,
+                if layer.groups == 1 { "" } else { "s" },
+            ),
+        };
+        lines.push(row(layer.origin.kind(), layer.origin.label(), status));
+    }
+
+    if !app.layers.migrated.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Upgraded from older versions", heading)));
+        for migrated in &app.layers.migrated {
+            let status = match app.config_path.as_ref() == Some(&migrated.path) {
+                true => "in memory; written on the next save",
+                false => "in memory only",
+            };
+            lines.push(row(&format!("v{}", migrated.from), migrated.path.display().to_string(), status.to_string()));
+            for change in &migrated.changes {
+                lines.push(Line::from(Span::styled(format!("           {change}"), text)));
+            }
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Effective values", heading)));
+    lines.push(row("theme", app.theme.label().to_string(), origin_text(&app.layers.theme_origin())));
+    if app.theme == ThemeName::Auto {
+        for brightness in [Brightness::Light, Brightness::Dark] {
+            let theme = app.config.auto_theme(brightness);
+            let origin = origin_text(&app.layers.auto_theme_origin(brightness));
+            lines.push(row(brightness.label(), theme.label().to_string(), origin));
+        }
+    }
+    lines.push(row("prefix", app.keymap.prefix.label(), origin_text(&app.layers.prefix_origin())));
+    lines.push(row("colors", app.colors.depth.label().to_string(), app.colors.reason.clone()));
+    for theme in &app.config.themes {
+        lines.push(row("palette", theme.name.clone(), origin_text(&app.layers.custom_theme_origin(&theme.name))));
+    }
+    for template in &app.config.templates {
+        let name = template.name.clone();
+        lines.push(row("template", name, origin_text(&app.layers.template_origin(&template.name))));
+    }
+    for host in &app.config.hosts {
+        lines.push(row("host", host.name.clone(), origin_text(&app.layers.host_origin(&host.name))));
+    }
+    for group in &app.config.groups {
+        lines.push(row("group", group.name.clone(), origin_text(&app.layers.group_origin(&group.name))));
+    }
+
+    let max_scroll = (lines.len() as u16).saturating_sub(area.height.saturating_sub(2));
+    let hints = key_hints(
+        app,
+        Scope::Config,
+        &[(Action::Down, "scroll"), (Action::Up, "back"), (Action::Backups, "backups"), (Action::OpenMenu, "menu")],
+    );
+    let panel = Paragraph::new(Text::from(lines))
+        .scroll((app.config_scroll.min(max_scroll), 0))
+        .block(
+            Block::default()
+                .title(format!("Sources · {hints}"))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(palette.accent))
+                .style(Style::default().bg(palette.surface).fg(palette.text)),
+        );
+    frame.render_widget(panel, area);
+}
+
+fn draw_backups(frame: &mut Frame, area: Rect, backups: &[Backup], selected: usize, palette: Palette) {
+    let block = Block::default()
+        .title("Restore backup · Enter restore · Esc close")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.accent))
+        .style(Style::default().bg(palette.surface).fg(palette.text));
+
+    if backups.is_empty() {
+        let empty = Paragraph::new(Line::from(Span::styled(
+            "No backups yet; one is made whenever mangsh saves the config.",
+            Style::default().fg(palette.text_muted),
+        )))
+        .block(block);
+        frame.render_widget(empty, area);
+        return;
+    }
+
+    let items = backups
+        .iter()
+        .map(|backup| {
+            let name = backup
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:>10}  ", age(backup.saved_at)), Style::default().fg(palette.accent)),
+                Span::styled(name, Style::default().fg(palette.text_muted)),
+            ]))
+        })
+        .collect::<Vec<_>>();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().fg(palette.base).bg(palette.accent))
+        .highlight_symbol("› ");
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_diagnostics(frame: &mut Frame, area: Rect, app: &App, palette: Palette) {
+    let mut lines = Vec::new();
+    for diagnostic in &app.diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Error => Style::default().fg(palette.accent_soft).add_modifier(Modifier::BOLD),
+            Severity::Warning => Style::default().fg(palette.accent).add_modifier(Modifier::BOLD),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{}: ", diagnostic.severity.label()), severity),
+            Span::styled(diagnostic.message.clone(), Style::default().fg(palette.text)),
+        ]));
+        lines.push(Line::from(Span::styled(
+            format!("  --> {}", diagnostic.location()),
+            Style::default().fg(palette.text_muted),
+        )));
+        if let Some((text, range)) = &diagnostic.snippet {
+            let number = diagnostic.line.to_string();
+            let gutter = Style::default().fg(palette.text_muted);
+            lines.push(Line::from(vec![
+                Span::styled(format!("{number} | "), gutter),
+                Span::styled(text.clone(), Style::default().fg(palette.text)),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} | {}", " ".repeat(number.len()), " ".repeat(range.start)), gutter),
+                Span::styled("^".repeat(range.len()), severity),
+            ]));
+        }
+        if let Some(help) = &diagnostic.help {
+            lines.push(Line::from(Span::styled(
+                format!("  = help: {help}"),
+                Style::default().fg(palette.accent),
+            )));
+        }
+        lines.push(Line::from(""));
+    }
+
+    // keep the last line reachable but don't scroll past it
+    let max_scroll = (lines.len() as u16).saturating_sub(area.height.saturating_sub(2));
+    let hints = key_hints(app, Scope::Config, &[(Action::Down, "scroll"), (Action::Up, "back")]);
+    let panel = Paragraph::new(Text::from(lines))
+        .wrap(Wrap { trim: false })
+        .scroll((app.config_scroll.min(max_scroll), 0))
+        .block(
+            Block::default()
+                .title(format!("Problems ({}) · {hints}", app.diagnostics.len()))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(palette.accent_soft))
+                .style(Style::default().bg(palette.surface).fg(palette.text)),
+        );
+    frame.render_widget(panel, area);
+}
+
+fn age(at: SystemTime) -> String {
+    let secs = SystemTime::now().duration_since(at).map_or(0, |d| d.as_secs());
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+//
+// FLEET RUNNER SCREEN
+//
+pub fn draw_fleet_screen(frame: &mut Frame, app: &App, palette: Palette) {
+    let area = frame.area();
+    let fleet = &app.fleet;
+
+    let focused = |focus: FleetFocus| {
+        if fleet.focus == focus {
+            Style::default().fg(palette.accent)
+        } else {
+            Style::default().fg(palette.text_muted)
+        }
+    };
+
+    let columns = fleet_columns(area, app.fleet_split);
+
+    // host picker
+    let items = app
+        .config
+        .hosts
+        .iter()
+        .map(|host| {
+            let mark = if fleet.is_selected(&host.name) { "[x] " } else { "[ ] " };
+            ListItem::new(Line::from(vec![
+                Span::styled(mark, Style::default().fg(palette.accent)),
+                Span::raw(host.name.clone()),
+            ]))
+        })
+        .collect::<Vec<_>>();
+    let picked = fleet.selected.len();
+    let hosts = List::new(items)
+        .block(
+            Block::default()
+                .title(match fleet.group.and_then(|index| app.config.groups.get(index)) {
+                    Some(group) => format!("Hosts ({picked} selected · {})", group.name),
+                    None => format!("Hosts ({picked} selected)"),
+                })
+                .borders(Borders::ALL)
+                .border_style(focused(FleetFocus::Hosts))
+                .style(Style::default().bg(palette.surface).fg(palette.text)),
+        )
+        .highlight_style(Style::default().fg(palette.base).bg(palette.accent))
+        .highlight_symbol("› ");
+    let mut state = ListState::default().with_selected(Some(fleet.cursor));
+    frame.render_stateful_widget(hosts, columns[0], &mut state);
 
-    let copy = Paragraph::new(Text::from(vec![
+    let right = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Fill(1),
+        Constraint::Length(1),
+    ])
+    .split(columns[1]);
+
+    // command input
+    let input = Paragraph::new(Line::from(vec![
+        Span::styled("$ ", Style::default().fg(palette.accent)),
+        Span::styled(fleet.command.clone(), Style::default().fg(palette.text)),
+    ]))
+    .block(
+        Block::default()
+            .title("Command")
+            .borders(Borders::ALL)
+            .border_style(focused(FleetFocus::Command))
+            .style(Style::default().bg(palette.surface)),
+    );
+    frame.render_widget(input, right[0]);
+    if fleet.focus == FleetFocus::Command {
+        let x = right[0].x + 3 + fleet.command.chars().count() as u16;
+        frame.set_cursor_position((x.min(right[0].right().saturating_sub(2)), right[0].y + 1));
+    }
+
+    let results_title = match (&fleet.run, fleet.selected_row()) {
+        (Some(_), Some(row)) if fleet.detail => format!("Output · {}", row.host),
+        (Some(run), _) if run.is_running() => format!("Results · {} (running)", run.command),
+        (Some(run), _) => format!("Results · {}", run.command),
+        (None, _) => "Results".to_string(),
+    };
+    let results_block = Block::default()
+        .title(results_title)
+        .borders(Borders::ALL)
+        .border_style(focused(FleetFocus::Results))
+        .style(Style::default().bg(palette.overlay).fg(palette.text));
+
+    match (&fleet.run, fleet.selected_row()) {
+        (Some(_), Some(row)) if fleet.detail => {
+            let lines = row
+                .output
+                .iter()
+                .map(|(stream, line)| match stream {
+                    Stream::Stdout => Line::from(line.as_str()),
+                    Stream::Stderr => Line::from(Span::styled(line.as_str(), Style::default().fg(palette.accent_soft))),
+                })
+                .collect::<Vec<_>>();
+            let detail = Paragraph::new(lines)
+                .scroll((fleet.detail_scroll, 0))
+                .block(results_block);
+            frame.render_widget(detail, right[1]);
+        }
+        (Some(run), _) => {
+            let rows = run.rows.iter().map(|row| {
+                let (status, style) = match &row.status {
+                    RowStatus::Queued => ("queued", Style::default().fg(palette.text_muted)),
+                    RowStatus::Running(_) => ("running", Style::default().fg(palette.accent_soft)),
+                    RowStatus::Done(outcome) if outcome.success() => ("ok", Style::default().fg(palette.accent)),
+                    RowStatus::Done(_) => ("failed", Style::default().fg(palette.accent_soft).add_modifier(Modifier::BOLD)),
+                };
+                let exit = match &row.status {
+                    RowStatus::Done(outcome) => outcome
+                        .exit_code
+                        .map(|code| code.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    _ => String::new(),
+                };
+                let duration = row
+                    .duration()
+                    .map(|d| format!("{:.1}s", d.as_secs_f64()))
+                    .unwrap_or_default();
+                let first = match &row.status {
+                    RowStatus::Done(outcome) if row.output.is_empty() => outcome.error.clone().unwrap_or_default(),
+                    _ => row.first_line().to_string(),
+                };
+                Row::new(vec![
+                    Cell::from(row.host.clone()),
+                    Cell::from(status).style(style),
+                    Cell::from(exit),
+                    Cell::from(duration),
+                    Cell::from(first),
+                ])
+            });
+            let table = Table::new(
+                rows,
+                [
+                    Constraint::Length(16),
+                    Constraint::Length(8),
+                    Constraint::Length(5),
+                    Constraint::Length(8),
+                    Constraint::Fill(1),
+                ],
+            )
+            .header(
+                Row::new(vec!["Host", "Status", "Exit", "Time", "Output"])
+                    .style(Style::default().fg(palette.text_muted).add_modifier(Modifier::BOLD)),
+            )
+            .row_highlight_style(Style::default().fg(palette.base).bg(palette.accent))
+            .block(results_block);
+            let mut state = TableState::default().with_selected(Some(fleet.result_index));
+            frame.render_stateful_widget(table, right[1], &mut state);
+        }
+        (None, _) => {
+            let empty = Paragraph::new(Line::from(Span::styled(
+                "Pick hosts, type a command and press Enter.",
+                Style::default().fg(palette.text_muted),
+            )))
+            .block(results_block);
+            frame.render_widget(empty, right[1]);
+        }
+    }
+
+    // the detail view and the command line take keys directly; the rest go
+    // through the keymap
+    let hint = match (&fleet.notice, fleet.detail, fleet.focus) {
+        (Some(notice), _, _) => notice.clone(),
+        (None, true, _) => "↑/↓ scroll · ←/→ prev/next host · Esc back".to_string(),
+        (None, false, FleetFocus::Hosts) => key_hints(
+            app,
+            Scope::Fleet,
+            &[
+                (Action::Toggle, "select"),
+                (Action::SelectAll, "all"),
+                (Action::NextGroup, "group"),
+                (Action::Select, "command"),
+                (Action::Back, "back"),
+            ],
+        ),
+        (None, false, FleetFocus::Command) => "Enter run · Tab switch · Esc hosts".to_string(),
+        (None, false, FleetFocus::Results) => key_hints(
+            app,
+            Scope::Fleet,
+            &[
+                (Action::Down, "next"),
+                (Action::Up, "previous"),
+                (Action::Select, "full output"),
+                (Action::NextField, "switch"),
+                (Action::Back, "back"),
+            ],
+        ),
+    };
+    let hint = Paragraph::new(Line::from(Span::styled(hint, Style::default().fg(palette.text_muted))))
+        .style(Style::default().bg(palette.base));
+    frame.render_widget(hint, right[2]);
+}
+
+//
+// HOST FORM SCREEN
+//
+pub fn draw_host_form_screen(frame: &mut Frame, app: &App, palette: Palette) {
+    let Some(form) = &app.host_form else {
+        return;
+    };
+    let area = frame.area();
+
+    let title = match form.editing {
+        Some(index) if index < app.config.hosts.len() => format!("Edit host · {}", app.config.hosts[index].name),
+        _ => "Add host".to_string(),
+    };
+    let body = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.accent))
+        .style(Style::default().bg(palette.surface).fg(palette.text));
+
+    frame.render_widget(body.clone(), area);
+
+    let inner = body.inner(area);
+    let sections = Layout::vertical([
+        Constraint::Length(FIELDS.len() as u16 * 2),
+        Constraint::Fill(1),
+        Constraint::Length(2),
+    ])
+    .margin(1)
+    .split(inner);
+
+    let rows = Layout::vertical(FIELDS.iter().map(|_| Constraint::Length(2))).split(sections[0]);
+    let preview = app.config.resolve(&form.draft());
+    for (n, field) in FIELDS.iter().enumerate() {
+        let focused = form.focus == n;
+        let label_style = if focused {
+            Style::default().fg(palette.accent).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(palette.text_muted)
+        };
+        let value = form.value(*field);
+        let value_style = if focused {
+            Style::default().fg(palette.text).bg(palette.overlay)
+        } else {
+            Style::default().fg(palette.text)
+        };
+
+        let mut line = vec![
+            Span::styled(format!("{:>10}  ", field.label()), label_style),
+            Span::styled(format!("{value:<32}"), value_style),
+        ];
+        if let Some(error) = form.error(*field) {
+            line.push(Span::styled(
+                format!("  {error}"),
+                Style::default().fg(palette.accent_soft).add_modifier(Modifier::BOLD),
+            ));
+        }
+        let help = match field {
+            FormField::Template => "name of a [[templates]] entry (optional)",
+            FormField::Port => "digits only",
+            FormField::Tags => "comma separated",
+            FormField::Identity if form.key_count() > 0 => "←/→ pick from ~/.ssh, or type a path",
+            FormField::Identity => "path to a private key (optional)",
+            FormField::Jump => "host to hop through: a host name or [user@]addr[:port]",
+            FormField::Keepalive => "seconds between keepalive probes",
+            FormField::Tor => "Space to cycle on / off / inherit",
+            _ => "",
+        };
+        // blank inheritable fields say what they'll end up as
+        let inherited = field
+            .setting()
+            .filter(|_| value.is_empty())
+            .map(|setting| (preview_value(&preview, *field), preview.source(setting)));
+        let inherits = match inherited {
+            Some((Some(value), source)) => format!("inherits {value} from {}", source.label()),
+            Some((None, _)) if *field == FormField::User => "blank uses your local user name".to_string(),
+            _ => String::new(),
+        };
+        let help = match (help.is_empty(), inherits.is_empty()) {
+            (_, true) => help.to_string(),
+            (true, false) => inherits,
+            (false, false) => format!("{help} · {inherits}"),
+        };
+        let help = Line::from(Span::styled(format!("{:12}{help}", ""), Style::default().fg(palette.text_muted)));
+        frame.render_widget(Paragraph::new(vec![Line::from(line), help]), rows[n]);
+
+        if focused && *field != FormField::Tor {
+            let x = rows[n].x + 12 + value.chars().count() as u16;
+            frame.set_cursor_position((x.min(rows[n].right().saturating_sub(1)), rows[n].y));
+        }
+    }
+
+    let status = match &form.notice {
+        Some(notice) => notice.clone(),
+        None if !form.errors.is_empty() => format!("{} field(s) need attention.", form.errors.len()),
+        None => String::new(),
+    };
+    let footer = Paragraph::new(Text::from(vec![
+        Line::from(Span::styled(status, Style::default().fg(palette.accent).add_modifier(Modifier::ITALIC))),
         Line::from(Span::styled(
-            "Planned: themes, host profiles, SSH keys, Tor mode.",
-            Style::default().fg(palette.text),
+            "Tab/↑/↓ move · Enter save · Esc cancel",
+            Style::default().fg(palette.text_muted),
         )),
+    ]));
+    frame.render_widget(footer, sections[2]);
+}
+
+fn preview_value(host: &ResolvedHost, field: FormField) -> Option<String> {
+    match field {
+        FormField::User => host.user.clone(),
+        FormField::Port => Some(host.port.to_string()),
+        FormField::Identity => host.identity.clone(),
+        FormField::Jump => host.jump.clone(),
+        FormField::Keepalive => host.keepalive.map(|secs| format!("{secs}s")),
+        FormField::Tor => Some(if host.tor { "on" } else { "off" }.to_string()),
+        FormField::Name | FormField::Addr | FormField::Template | FormField::Tags => None,
+    }
+}
+
+//
+// THEME EDITOR SCREEN
+//
+pub fn draw_theme_editor_screen(frame: &mut Frame, app: &App, palette: Palette) {
+    let Some(editor) = &app.theme_editor else {
+        return;
+    };
+    let area = frame.area();
+
+    let mut title = format!("Theme editor · based on {}", editor.based_on.label());
+    if editor.modified() {
+        title.push_str(" · modified");
+    }
+    let body = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.accent))
+        .style(Style::default().bg(palette.surface).fg(palette.text));
+
+    frame.render_widget(body.clone(), area);
+
+    let inner = body.inner(area);
+    let rows = Layout::vertical([Constraint::Fill(1), Constraint::Length(2)])
+        .margin(1)
+        .split(inner);
+    let columns = Layout::horizontal([Constraint::Length(46), Constraint::Fill(1)])
+        .spacing(2)
+        .split(rows[0]);
+    let left = Layout::vertical([
+        Constraint::Length(SLOTS.len() as u16 + 2),
+        Constraint::Length(7),
+        Constraint::Fill(1),
+    ])
+    .split(columns[0]);
+
+    // every slot with a swatch of its current color
+    let slots = SLOTS
+        .iter()
+        .enumerate()
+        .map(|(n, slot)| {
+            let color = editor.theme.slot(slot).unwrap_or(Color::Reset);
+            let selected = n == editor.slot;
+            let label = match selected {
+                true => Style::default().fg(palette.accent).add_modifier(Modifier::BOLD),
+                false => Style::default().fg(palette.text),
+            };
+            Line::from(vec![
+                Span::styled(if selected { "› " } else { "  " }, label),
+                Span::styled(format!("{slot:<13}"), label),
+                Span::styled("    ", Style::default().bg(color)),
+                Span::styled(format!(" {color}"), Style::default().fg(palette.text_muted)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let slots = Paragraph::new(slots).block(
+        Block::default()
+            .title("Slots")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(palette.overlay))
+            .style(Style::default().bg(palette.surface)),
+    );
+    frame.render_widget(slots, left[0]);
+
+    let sliders_block = Block::default()
+        .title(format!("{} · {}", editor.slot_name(), editor.model.label()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.accent))
+        .style(Style::default().bg(palette.surface));
+    let sliders_area = sliders_block.inner(left[1]);
+    frame.render_widget(sliders_block, left[1]);
+
+    // each slider is shaded with the colors it would produce
+    let width = sliders_area.width.saturating_sub(8).max(2);
+    let mut lines = Vec::new();
+    for (n, channel) in editor.channels().iter().enumerate() {
+        let focused = n == editor.channel;
+        let label = match focused {
+            true => Style::default().fg(palette.accent).add_modifier(Modifier::BOLD),
+            false => Style::default().fg(palette.text_muted),
+        };
+        let mut line = vec![Span::styled(format!("{} ", channel.label), label)];
+        let knob = (channel.value / channel.max * f64::from(width - 1)).round() as u16;
+        for cell in 0..width {
+            let shade = editor.with_channel(n, f64::from(cell) / f64::from(width - 1) * channel.max);
+            let symbol = match (cell == knob, focused) {
+                (true, true) => "┃",
+                (true, false) => "│",
+                _ => " ",
+            };
+            let ink = if luminance(to_rgb(shade)) > 0.5 { Color::Black } else { Color::White };
+            line.push(Span::styled(symbol, Style::default().bg(shade).fg(ink)));
+        }
+        let value = match (editor.model, n) {
+            (ColorModel::Rgb, _) => format!(" {:>3}", channel.value.round()),
+            (ColorModel::Hsl, 0) => format!(" {:>3}°", channel.value.round()),
+            (ColorModel::Hsl, _) => format!(" {:>3}%", channel.value.round()),
+        };
+        line.push(Span::styled(value, label));
+        lines.push(Line::from(line));
+    }
+    lines.push(Line::from(""));
+    let color = editor.color();
+    lines.push(match &editor.input {
+        Some(EditorInput::Hex(text)) => Line::from(vec![
+            Span::styled("Color: ", Style::default().fg(palette.accent)),
+            Span::styled(text.clone(), Style::default().fg(palette.text)),
+        ]),
+        _ => Line::from(vec![
+            Span::styled("        ", Style::default().bg(color)),
+            Span::styled(format!(" {color}"), Style::default().fg(palette.text)),
+        ]),
+    });
+    frame.render_widget(Paragraph::new(lines), sliders_area);
+
+    draw_theme_preview(frame, columns[1], app, custom_palette(&editor.theme));
+
+    // typed input takes keys directly; the rest go through the keymap
+    let hint = match &editor.input {
+        Some(EditorInput::Hex(_)) => "Type #rrggbb, a color name or 0-255 · Enter apply · Esc cancel".to_string(),
+        Some(EditorInput::Name(_)) => "Name the new theme · Enter save to themes/ · Esc cancel".to_string(),
+        None => key_hints(
+            app,
+            Scope::Editor,
+            &[
+                (Action::Down, "next slot"),
+                (Action::Up, "previous slot"),
+                (Action::NextField, "next slider"),
+                (Action::Increase, "more"),
+                (Action::Decrease, "less"),
+                (Action::IncreaseMore, "more ×10"),
+                (Action::DecreaseMore, "less ×10"),
+                (Action::ToggleColorModel, "RGB/HSL"),
+                (Action::EnterColor, "type a color"),
+                (Action::ResetColor, "reset"),
+                (Action::Save, "save"),
+                (Action::Back, "back"),
+            ],
+        ),
+    };
+    let status = Style::default().fg(palette.accent).add_modifier(Modifier::ITALIC);
+    let mut footer = match &editor.input {
+        Some(EditorInput::Name(name)) => vec![Line::from(vec![
+            Span::styled("Save as: ", Style::default().fg(palette.accent)),
+            Span::styled(name.clone(), Style::default().fg(palette.text)),
+        ])],
+        _ => Vec::new(),
+    };
+    // a problem takes the place of the key hints
+    footer.push(match &editor.notice {
+        Some(notice) => Line::from(Span::styled(notice.clone(), status)),
+        None => Line::from(Span::styled(hint, Style::default().fg(palette.text_muted))),
+    });
+    frame.render_widget(Paragraph::new(footer).wrap(Wrap { trim: true }), rows[1]);
+
+    match &editor.input {
+        Some(EditorInput::Hex(text)) => {
+            let x = sliders_area.x + "Color: ".len() as u16 + text.chars().count() as u16;
+            frame.set_cursor_position((x.min(sliders_area.right().saturating_sub(1)), sliders_area.y + 4));
+        }
+        Some(EditorInput::Name(name)) => {
+            let x = rows[1].x + "Save as: ".len() as u16 + name.chars().count() as u16;
+            frame.set_cursor_position((x.min(rows[1].right().saturating_sub(1)), rows[1].y));
+        }
+        None => {}
+    }
+}
+
+// the title, hosts and session panels as they'd look in `palette`
+fn draw_theme_preview(frame: &mut Frame, area: Rect, app: &App, palette: Palette) {
+    let backdrop = Block::default()
+        .title("Preview")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.overlay))
+        .style(Style::default().bg(palette.base).fg(palette.text));
+    let inner = backdrop.inner(area);
+    frame.render_widget(backdrop, area);
+
+    let parts = Layout::vertical([
+        Constraint::Length(5),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+    ])
+    .split(inner);
+
+    let header = Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).split(parts[0]);
+    frame.render_widget(Block::default().style(Style::default().bg(palette.accent)), header[0]);
+    let title = Paragraph::new(Text::from(vec![
         Line::from(Span::styled(
-            "Press M for menu or q to exit.",
+            " mangsh ",
+            Style::default()
+                .fg(palette.base)
+                .bg(palette.accent_soft)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            "A warm mango shell for curating your SSH orchards.",
+            Style::default().fg(palette.text).add_modifier(Modifier::ITALIC),
+        )),
+        Line::from(Span::styled(
+            "Enter → jump in    M → quick menu",
             Style::default().fg(palette.text_muted),
         )),
     ]))
-    .alignment(Alignment::Left)
-    .style(Style::default().bg(palette.surface).fg(palette.text_muted));
+    .alignment(Alignment::Center)
+    .style(Style::default().bg(palette.base_alt));
+    frame.render_widget(title, header[1]);
 
-    frame.render_widget(copy, sections[1]);
+    draw_hosts(frame, parts[1], app, palette);
+    draw_session(frame, parts[2], app, palette);
 }
 
 //
 // LEFT PANE: HOSTS
 //
-pub fn draw_hosts(frame: &mut Frame, area: Rect, palette: Palette) {
+pub fn draw_hosts(frame: &mut Frame, area: Rect, app: &App, palette: Palette) {
 
+    let mode = if app.hosts.search.is_some() {
+        "search"
+    } else {
+        app.hosts.grouping.label()
+    };
+    let title = match &app.hosts.tag_filter {
+        Some(tag) => format!("Hosts · {mode} · tag: {tag}"),
+        None => format!("Hosts · {mode}"),
+    };
     let block = Block::default()
-        .title("Hosts")
+        .title(title)
         .borders(Borders::ALL)
         .border_style(Style::default().fg(palette.accent))
         .style(Style::default().bg(palette.surface).fg(palette.text));
@@ -443,36 +1291,270 @@ pub fn draw_hosts(frame: &mut Frame, area: Rect, palette: Palette) {
     frame.render_widget(block.clone(), area);
 
     let inner = block.inner(area);
-    let content = Paragraph::new(Text::from(vec![
-        Line::from(Span::styled(
-            "No hosts added yet.",
+
+    if app.config.hosts.is_empty() {
+        let content = Paragraph::new(Text::from(vec![
+            Line::from(Span::styled(
+                "No hosts added yet.",
+                Style::default()
+                    .fg(palette.text)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Line::from(Span::styled(
+                "Press a to add a host, or add profiles in config.",
+                Style::default().fg(palette.text_muted),
+            )),
+            Line::from(""),
+            Line::from(Span::styled(
+                "Tip: press c to jump into Config.",
+                Style::default().fg(palette.accent),
+            )),
+        ]))
+        .alignment(Alignment::Left)
+        .style(Style::default().bg(palette.surface).fg(palette.text));
+
+        frame.render_widget(content, inner);
+        return;
+    }
+
+    // the resolved settings of the selected host sit beside the list when
+    // there's room for both
+    let selected = app.hosts.selected_host(&app.config);
+    let panes = hosts_panes(area, app.detail_width, selected.is_some());
+    if let (Some(index), Some(detail)) = (selected, panes.detail) {
+        draw_host_detail(frame, detail, app, index, palette);
+    }
+
+    let searching = app.hosts.search.is_some();
+    let nested = app.hosts.grouping != Grouping::Flat && !searching;
+    let items = if searching {
+        app.hosts
+            .search_hits(&app.config)
+            .iter()
+            .map(|hit| ListItem::new(host_line(&app.config.resolve(&app.config.hosts[hit.index]), Some(hit), false, palette)))
+            .collect::<Vec<_>>()
+    } else {
+        app.hosts
+            .rows(&app.config)
+            .into_iter()
+            .map(|row| match row {
+                HostRow::Section { label, count, collapsed } => {
+                    let marker = if collapsed { "▸" } else { "▾" };
+                    ListItem::new(Line::from(Span::styled(
+                        format!("{marker} {label} ({count})"),
+                        Style::default()
+                            .fg(palette.accent)
+                            .add_modifier(Modifier::BOLD),
+                    )))
+                }
+                HostRow::Host { index } => {
+                    ListItem::new(host_line(&app.config.resolve(&app.config.hosts[index]), None, nested, palette))
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let list = List::new(items)
+        .style(Style::default().bg(palette.surface).fg(palette.text))
+        .highlight_style(
             Style::default()
-                .fg(palette.text)
+                .fg(palette.base)
+                .bg(palette.accent)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("› ");
+
+    let mut state = ListState::default().with_selected(Some(app.hosts.cursor));
+    frame.render_stateful_widget(list, panes.list, &mut state);
+
+    let hint = match (&app.hosts.search, app.confirm_delete) {
+        (_, Some(index)) => Line::from(Span::styled(
+            format!("Delete {}? y/n", app.config.hosts[index].name),
+            Style::default()
+                .fg(palette.accent)
                 .add_modifier(Modifier::BOLD),
         )),
-        Line::from(Span::styled(
-            "Add host profiles in config to see them here.",
+        (Some(query), None) => {
+            frame.set_cursor_position((panes.hint.x + 2 + query.chars().count() as u16, panes.hint.y));
+            Line::from(vec![
+                Span::styled("/ ", Style::default().fg(palette.accent)),
+                Span::styled(query.clone(), Style::default().fg(palette.text)),
+                Span::styled(
+                    "   Enter connect · ↑/↓ select · Esc cancel",
+                    Style::default().fg(palette.text_muted),
+                ),
+            ])
+        }
+        (None, None) => Line::from(Span::styled(
+            key_hints(
+                app,
+                Scope::Main,
+                &[
+                    (Action::Select, "connect/fold"),
+                    (Action::Search, "search"),
+                    (Action::AddHost, "add"),
+                    (Action::EditHost, "edit"),
+                    (Action::DeleteHost, "delete"),
+                    (Action::CycleGrouping, "grouping"),
+                    (Action::CycleTagFilter, "tag"),
+                ],
+            ),
             Style::default().fg(palette.text_muted),
         )),
-        Line::from(""),
-        Line::from(Span::styled(
-            "Tip: press c to jump into Config.",
-            Style::default().fg(palette.accent),
-        )),
-    ]))
-    .alignment(Alignment::Left)
-    .style(Style::default().bg(palette.surface).fg(palette.text));
+    };
+    let hint = Paragraph::new(hint).style(Style::default().bg(palette.surface));
 
-    frame.render_widget(content, inner);
+    frame.render_widget(hint, panes.hint);
+}
+
+fn host_line(host: &ResolvedHost, hit: Option<&HostHit>, nested: bool, palette: Palette) -> Line<'static> {
+    let matched = Style::default()
+        .fg(palette.accent)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let muted = Style::default().fg(palette.text_muted);
+    let none = Vec::new();
+    let positions = |pick: fn(&HostHit) -> &Vec<usize>| hit.map(pick).unwrap_or(&none);
+
+    let mut spans = vec![Span::raw(if nested { "  " } else { "" })];
+    spans.extend(highlight(&host.name, positions(|h| &h.name), Style::default().add_modifier(Modifier::BOLD), matched));
+    spans.push(Span::styled("  ", muted));
+    if let Some(user) = &host.user {
+        spans.extend(highlight(user, positions(|h| &h.user), muted, matched));
+        spans.push(Span::styled("@", muted));
+    }
+    spans.extend(highlight(&host.addr, positions(|h| &h.addr), muted, matched));
+    spans.push(Span::styled(format!(":{}", host.port), muted));
+    if host.tor {
+        spans.push(Span::styled("  tor", Style::default().fg(palette.accent_soft)));
+    }
+    for (n, tag) in host.tags.iter().enumerate() {
+        let tag_positions = hit.and_then(|h| h.tags.get(n)).unwrap_or(&none);
+        spans.push(Span::styled(if n == 0 { "  #" } else { " #" }, muted));
+        spans.extend(highlight(tag, tag_positions, muted, matched));
+    }
+    Line::from(spans)
+}
+
+// what the selected host connects with, and where each setting came from
+fn draw_host_detail(frame: &mut Frame, area: Rect, app: &App, index: usize, palette: Palette) {
+    let host = &app.config.hosts[index];
+    let resolved = app.config.resolve(host);
+    let muted = Style::default().fg(palette.text_muted);
+
+    let rows = [
+        ("addr", Some(resolved.addr.clone())),
+        ("user", resolved.user.clone()),
+        ("port", Some(resolved.port.to_string())),
+        ("identity", resolved.identity.clone()),
+        ("jump", resolved.jump.clone()),
+        ("tor", Some(if resolved.tor { "yes" } else { "no" }.to_string())),
+        ("keepalive", resolved.keepalive.map(|secs| format!("{secs}s"))),
+    ]
+    .into_iter()
+    .map(|(setting, value)| {
+        let source = resolved.source(setting);
+        // inherited values stand out from the host's own
+        let value_style = match source {
+            Source::Host => Style::default().fg(palette.text),
+            _ => Style::default().fg(palette.accent),
+        };
+        Row::new(vec![
+            Cell::from(setting).style(muted),
+            Cell::from(value.unwrap_or_else(|| "—".to_string())).style(value_style),
+            Cell::from(source.label()).style(muted),
+        ])
+    })
+    .collect::<Vec<_>>();
+
+    let block = Block::default()
+        .borders(Borders::LEFT)
+        .border_style(muted)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(palette.surface).fg(palette.text));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let sections = Layout::vertical([Constraint::Length(2), Constraint::Length(rows.len() as u16 + 1), Constraint::Fill(1)])
+        .split(inner);
+
+    let title = Paragraph::new(Line::from(Span::styled(
+        resolved.name.clone(),
+        Style::default().fg(palette.text).add_modifier(Modifier::BOLD),
+    )));
+    frame.render_widget(title, sections[0]);
+    let table = Table::new(rows, [Constraint::Length(9), Constraint::Fill(1), Constraint::Length(14)]);
+    frame.render_widget(table, sections[1]);
+
+    let mut notes = vec![Line::from(Span::styled(
+        format!("from {}", origin_text(&app.layers.host_origin(&host.name))),
+        muted,
+    ))];
+    if let Some(name) = host.template.as_deref().filter(|name| app.config.find_template(name).is_none()) {
+        notes.push(Line::from(Span::styled(
+            format!("unknown template {name}"),
+            Style::default().fg(palette.accent_soft).add_modifier(Modifier::BOLD),
+        )));
+    }
+    frame.render_widget(Paragraph::new(notes).wrap(Wrap { trim: false }), sections[2]);
+}
+
+// `key → what` for each action that has a key on `scope`, e.g.
+// `m/M → quick menu · q → quit`; built from the keymap so they follow `[keys]`
+fn key_hints(app: &App, scope: Scope, hints: &[(Action, &str)]) -> String {
+    hints
+        .iter()
+        .filter_map(|(action, what)| {
+            let keys = app.keymap.keys(scope, *action);
+            let keys = keys.iter().map(KeyChord::label).collect::<Vec<_>>();
+            (!keys.is_empty()).then(|| format!("{} → {what}", keys.join("/")))
+        })
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+// splits text into spans, styling the chars at `positions` as matches
+fn highlight(text: &str, positions: &[usize], base: Style, matched: Style) -> Vec<Span<'static>> {
+    if positions.is_empty() {
+        return vec![Span::styled(text.to_string(), base)];
+    }
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let is_match = positions.contains(&i);
+        if is_match != run_matched && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), if run_matched { matched } else { base }));
+        }
+        run_matched = is_match;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, if run_matched { matched } else { base }));
+    }
+    spans
 }
 
 //
 // RIGHT PANE: SESSION
 //
-pub fn draw_session(frame: &mut Frame, area: Rect, palette: Palette) {
+pub fn draw_session(frame: &mut Frame, area: Rect, app: &App, palette: Palette) {
+
+    let title = match app.active_session() {
+        Some(session) => {
+            let mut title = match &session.status {
+                Some(status) => format!("Session · {} ({status})", session.host),
+                None => format!("Session · {}", session.host),
+            };
+            let scrolled = session.parser.screen().scrollback();
+            if scrolled > 0 {
+                title.push_str(&format!(" · {scrolled} lines back"));
+            }
+            title
+        }
+        None => "Session".to_string(),
+    };
 
     let block = Block::default()
-        .title("Session")
+        .title(title)
         .borders(Borders::ALL)
         .border_style(Style::default().fg(palette.accent_soft))
         .style(Style::default().bg(palette.overlay).fg(palette.text));
@@ -480,6 +1562,19 @@ pub fn draw_session(frame: &mut Frame, area: Rect, palette: Palette) {
     frame.render_widget(block.clone(), area);
 
     let inner = block.inner(area);
+
+    if let Some(session) = app.active_session() {
+        let screen = session.parser.screen();
+        frame.render_widget(TerminalView { screen, selection: session.selection, palette }, inner);
+
+        // the remote cursor is on the live screen, not in the scrollback
+        if session.is_open() && !screen.hide_cursor() && screen.scrollback() == 0 && matches!(app.screen, Screen::Main) {
+            let (row, col) = screen.cursor_position();
+            frame.set_cursor_position((inner.x + col, inner.y + row));
+        }
+        return;
+    }
+
     let content = Paragraph::new(Text::from(vec![
         Line::from(Span::styled(
             "Session preview",
@@ -497,7 +1592,11 @@ pub fn draw_session(frame: &mut Frame, area: Rect, palette: Palette) {
             Style::default().fg(palette.text),
         )),
         Line::from(Span::styled(
-            "←/→ to switch tabs · M for menu · q to quit",
+            key_hints(app, Scope::Main, &[(Action::PreviousTab, "previous tab"), (Action::NextTab, "next tab"), (Action::OpenMenu, "menu"), (Action::Help, "keys")]),
+            Style::default().fg(palette.text_muted),
+        )),
+        Line::from(Span::styled(
+            format!("While connected every key goes to the session; {} first for mangsh.", app.keymap.prefix.label()),
             Style::default().fg(palette.text_muted),
         )),
     ]))
@@ -506,3 +1605,350 @@ pub fn draw_session(frame: &mut Frame, area: Rect, palette: Palette) {
 
     frame.render_widget(content, inner);
 }
+
+// renders the emulated remote screen cell by cell
+struct TerminalView<'a> {
+    screen: &'a vt100::Screen,
+    selection: Option<Selection>,
+    palette: Palette,
+}
+
+impl Widget for TerminalView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (rows, cols) = self.screen.size();
+        for row in 0..rows.min(area.height) {
+            for col in 0..cols.min(area.width) {
+                let Some(cell) = self.screen.cell(row, col) else {
+                    continue;
+                };
+                if cell.is_wide_continuation() {
+                    continue;
+                }
+
+                let mut style = Style::default()
+                    .fg(terminal_color(cell.fgcolor(), self.palette.text, self.palette.ansi))
+                    .bg(terminal_color(cell.bgcolor(), self.palette.overlay, self.palette.ansi));
+                if cell.bold() {
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                if cell.italic() {
+                    style = style.add_modifier(Modifier::ITALIC);
+                }
+                if cell.underline() {
+                    style = style.add_modifier(Modifier::UNDERLINED);
+                }
+                if cell.inverse() {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                if self.selection.is_some_and(|selection| selection.contains(row, col)) {
+                    style = style.fg(self.palette.base).bg(self.palette.accent_soft).remove_modifier(Modifier::REVERSED);
+                }
+
+                let contents = cell.contents();
+                let symbol = if contents.is_empty() { " " } else { contents.as_str() };
+                buf[(area.x + col, area.y + row)]
+                    .set_symbol(symbol)
+                    .set_style(style);
+            }
+        }
+    }
+}
+
+fn terminal_color(color: vt100::Color, default: Color, ansi: Option<[Color; 16]>) -> Color {
+    match color {
+        vt100::Color::Default => default,
+        vt100::Color::Idx(idx) => match ansi {
+            Some(ansi) if idx < 16 => ansi[usize::from(idx)],
+            _ => Color::Indexed(idx),
+        },
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+//
+// STATUS BAR
+//
+pub fn draw_status_bar(frame: &mut Frame, area: Rect, app: &App, palette: Palette) {
+    let muted = Style::default().fg(palette.text_muted);
+    let separator = || Span::styled(" · ", muted);
+
+    let mut left = vec![Span::raw(" ")];
+    match app.active_session() {
+        Some(session) => {
+            let state = match session.is_open() {
+                true => Style::default().fg(palette.accent),
+                false => Style::default().fg(palette.accent_soft).add_modifier(Modifier::BOLD),
+            };
+            left.push(Span::styled(session.host.clone(), Style::default().fg(palette.text).add_modifier(Modifier::BOLD)));
+            left.push(separator());
+            left.push(Span::styled(session.state().to_string(), state));
+            if let Some(latency) = session.latency.filter(|_| session.is_open()) {
+                left.push(separator());
+                left.push(Span::styled(format!("{}ms", latency.as_millis()), muted));
+            }
+            if session.tor {
+                left.push(separator());
+                left.push(Span::styled("tor", Style::default().fg(palette.accent_soft)));
+            }
+            if let Some(jump) = &session.jump {
+                left.push(separator());
+                left.push(Span::styled(format!("via {jump}"), muted));
+            }
+        }
+        None => left.push(Span::styled("no session", muted)),
+    }
+
+    // a fresh message first, otherwise whatever is wrong with the config
+    let message = match app.notice() {
+        Some(notice) => vec![Span::styled(notice.to_string(), Style::default().fg(palette.accent).add_modifier(Modifier::BOLD))],
+        None => config_problems(app, palette),
+    };
+    let mut clock = vec![Span::styled(clock(), Style::default().fg(palette.text)), Span::raw(" ")];
+    if !message.is_empty() {
+        clock.insert(0, separator());
+    }
+    let message = Line::from(message);
+    let clock = Line::from(clock);
+
+    // a long message is cut short rather than pushing the session out; the
+    // Config screen has it in full
+    let message_width = (message.width() as u16).min(area.width / 2);
+    let [left_area, message_area, clock_area] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(message_width),
+        Constraint::Length(clock.width() as u16),
+    ])
+    .areas(area);
+    let style = Style::default().bg(palette.surface);
+    frame.render_widget(Paragraph::new(Line::from(left)).style(style), left_area);
+    frame.render_widget(Paragraph::new(message).style(style), message_area);
+    frame.render_widget(Paragraph::new(clock).style(style), clock_area);
+}
+
+// e.g. `2 config errors · c to review`; empty when the config loaded cleanly
+fn config_problems(app: &App, palette: Palette) -> Vec<Span<'static>> {
+    let errors = app.diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    let warnings = app.diagnostics.len() - errors;
+    let (count, what, style) = match (errors, warnings) {
+        (0, 0) => return Vec::new(),
+        (0, n) => (n, "warning", Style::default().fg(palette.accent)),
+        (n, _) => (n, "error", Style::default().fg(palette.accent_soft).add_modifier(Modifier::BOLD)),
+    };
+    let mut spans = vec![Span::styled(format!("{count} config {what}{}", if count == 1 { "" } else { "s" }), style)];
+    let hint = key_hints(app, Scope::Main, &[(Action::OpenConfig, "review")]);
+    if !hint.is_empty() {
+        spans.push(Span::styled(format!(" · {hint}"), Style::default().fg(palette.text_muted)));
+    }
+    spans
+}
+
+// local wall-clock time as HH:MM
+#[cfg(unix)]
+fn clock() -> String {
+    // SAFETY: a null pointer asks time() to only return the time
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    // SAFETY: tm is plain integers (and a nullable pointer on some
+    // platforms), so all zeroes is a valid value
+    let mut local = unsafe { std::mem::zeroed::<libc::tm>() };
+    // SAFETY: both pointers are to live locals; localtime_r is the
+    // thread-safe variant and only writes to `local`
+    if unsafe { libc::localtime_r(&now, &mut local) }.is_null() {
+        return String::new();
+    }
+    format!("{:02}:{:02}", local.tm_hour, local.tm_min)
+}
+
+// without localtime_r there's no time zone to go by, so say it's UTC
+#[cfg(not(unix))]
+fn clock() -> String {
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    format!("{:02}:{:02} UTC", now / 3600 % 24, now / 60 % 60)
+}
+
+//
+// COMMAND PALETTE
+//
+pub fn draw_command_palette(frame: &mut Frame, app: &App, palette: Palette) {
+    let Some(commands) = &app.command_palette else {
+        return;
+    };
+    let screen = frame.area();
+    let width = screen.width.saturating_sub(4).min(76);
+    let height = screen.height.saturating_sub(2).min(18);
+    let area = Rect::new(screen.x + (screen.width - width) / 2, screen.y + screen.height / 8, width, height);
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title("Command palette · Enter run · Esc close")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.accent))
+        .style(Style::default().bg(palette.surface).fg(palette.text));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let [prompt, list] = Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]).areas(inner);
+
+    let query = Line::from(vec![
+        Span::styled("> ", Style::default().fg(palette.accent).add_modifier(Modifier::BOLD)),
+        Span::raw(commands.query.clone()),
+    ]);
+    frame.render_widget(Paragraph::new(query), prompt);
+    let x = prompt.x + 2 + commands.query.chars().count() as u16;
+    frame.set_cursor_position((x.min(prompt.right().saturating_sub(1)), prompt.y));
+
+    let hits = commands.hits();
+    if hits.is_empty() {
+        let none = Paragraph::new(Line::from(Span::styled("Nothing matches.", Style::default().fg(palette.text_muted))));
+        frame.render_widget(none, list);
+        return;
+    }
+    let matched = Style::default()
+        .fg(palette.accent)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let items = hits
+        .iter()
+        .map(|hit| {
+            let entry = &commands.entries[hit.entry];
+            let label = match entry.command {
+                Command::Unavailable(_) => Style::default().fg(palette.text_muted),
+                _ => Style::default(),
+            };
+            let mut spans = highlight(&entry.label, &hit.positions, label, matched);
+            // the detail sits against the right edge, past the "› " marker
+            let used = 2 + entry.label.chars().count() + entry.detail.chars().count();
+            let gap = usize::from(list.width).saturating_sub(used).max(1);
+            spans.push(Span::raw(" ".repeat(gap)));
+            spans.push(Span::styled(entry.detail.clone(), Style::default().fg(palette.text_muted)));
+            ListItem::new(Line::from(spans))
+        })
+        .collect::<Vec<_>>();
+    let list_widget = List::new(items)
+        .highlight_style(Style::default().fg(palette.base).bg(palette.accent))
+        .highlight_symbol("› ");
+    let mut state = ListState::default().with_selected(Some(commands.selected));
+    frame.render_stateful_widget(list_widget, list, &mut state);
+}
+
+//
+// HELP OVERLAY
+//
+// the help overlay's `keys → what it does` rows for `scope`
+fn help_rows(app: &App, scope: Scope) -> Vec<(String, String)> {
+    let prefix = app.keymap.prefix.label();
+    // in a session the bindings only work after the prefix
+    let key_text = |keys: Vec<KeyChord>| {
+        let keys = keys.iter().map(KeyChord::label).collect::<Vec<_>>().join("/");
+        match scope {
+            Scope::Session => format!("{prefix} {keys}"),
+            _ => keys,
+        }
+    };
+    let mut rows = Action::ALL
+        .into_iter()
+        .filter_map(|action| {
+            let keys = app.keymap.keys(scope, action);
+            (!keys.is_empty()).then(|| (key_text(keys), action.label().to_string()))
+        })
+        .collect::<Vec<_>>();
+    if scope == Scope::Session {
+        rows.push((format!("{prefix} {prefix}"), format!("Send {prefix} to the session")));
+    }
+    rows
+}
+
+// the overlay is as tall as its text, up to the screen minus a line either side
+fn help_area(screen: Rect, lines: usize) -> Rect {
+    let width = screen.width.saturating_sub(4).min(72);
+    let height = screen.height.saturating_sub(2).min(lines as u16 + 2);
+    Rect::new(
+        screen.x + (screen.width - width) / 2,
+        screen.y + (screen.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// How far the help overlay can scroll on a `screen`-sized terminal.
+pub fn help_max_scroll(app: &App, screen: Rect) -> u16 {
+    let Some(scope) = app.help else {
+        return 0;
+    };
+    // the session's help has a note and a blank line above the rows
+    let lines = help_rows(app, scope).len() + if scope == Scope::Session { 2 } else { 0 };
+    (lines as u16).saturating_sub(help_area(screen, lines).height.saturating_sub(2))
+}
+
+pub fn draw_help(frame: &mut Frame, app: &App, palette: Palette) {
+    let Some(scope) = app.help else {
+        return;
+    };
+    let prefix = app.keymap.prefix.label();
+    let rows = help_rows(app, scope);
+    let width = rows.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+
+    let mut lines = Vec::new();
+    if scope == Scope::Session {
+        lines.push(Line::from(Span::styled(
+            format!("Everything else goes to the session; press {prefix} first for these."),
+            Style::default().fg(palette.text_muted),
+        )));
+        lines.push(Line::from(""));
+    }
+    for (keys, label) in rows {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{keys:>width$}  "),
+                Style::default().fg(palette.accent).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(label, Style::default().fg(palette.text)),
+        ]));
+    }
+
+    let area = help_area(frame.area(), lines.len());
+    // a resize can leave the scroll past the end until the next key
+    let scroll = app.help_scroll.min(help_max_scroll(app, frame.area()));
+    frame.render_widget(Clear, area);
+    let help = Paragraph::new(Text::from(lines))
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .title(format!("Keys for {}", scope.label()))
+                .title_bottom(Line::from(" ↑/↓ scroll · any other key closes ").right_aligned())
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(palette.accent))
+                .style(Style::default().bg(palette.surface).fg(palette.text)),
+        );
+    frame.render_widget(help, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::{Modifier, Style};
+
+    use super::highlight;
+
+    fn runs(text: &str, positions: &[usize]) -> Vec<(String, bool)> {
+        let matched = Style::default().add_modifier(Modifier::BOLD);
+        highlight(text, positions, Style::default(), matched)
+            .into_iter()
+            .map(|span| (span.content.to_string(), span.style == matched))
+            .collect()
+    }
+
+    #[test]
+    fn splits_runs_at_matches() {
+        assert_eq!(runs("web-01", &[]), [("web-01".to_string(), false)]);
+        assert_eq!(
+            runs("web-01", &[0, 1, 4]),
+            [("we".to_string(), true), ("b-".to_string(), false), ("0".to_string(), true), ("1".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn positions_count_chars_not_bytes() {
+        assert_eq!(
+            runs("café-db", &[3, 5, 6]),
+            [("caf".to_string(), false), ("é".to_string(), true), ("-".to_string(), false), ("db".to_string(), true)]
+        );
+        assert_eq!(runs("東京-01", &[1]), [("東".to_string(), false), ("京".to_string(), true), ("-01".to_string(), false)]);
+    }
+}